@@ -0,0 +1,268 @@
+//! Administrative helpers that expose cluster metadata as polars dataframes.
+use polars::prelude::*;
+
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::ClientOptions,
+    sync::Client,
+};
+
+fn connect(connection_str: &str) -> PolarsResult<Client> {
+    let client_options = ClientOptions::parse(connection_str).map_err(|e| {
+        PolarsError::InvalidOperation(format!("unable to connect to mongodb: {}", e).into())
+    })?;
+    Client::with_options(client_options)
+        .map_err(|e| PolarsError::ComputeError(format!("{:#?}", e).into()))
+}
+
+fn run_command(connection_str: &str, db: &str, command: Document) -> PolarsResult<Document> {
+    connect(connection_str)?
+        .database(db)
+        .run_command(command, None)
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))
+}
+
+/// Extract the documents stored in the array field `key` of a command response.
+fn documents<'a>(response: &'a Document, key: &str) -> impl Iterator<Item = &'a Document> {
+    response
+        .get_array(key)
+        .map(|arr| arr.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Bson::as_document)
+}
+
+/// Roles are identified as `<db>.<role>`, the same way mongodb names them in `system.roles`.
+fn role_names(doc: &Document, key: &str) -> Series {
+    let names: Vec<String> = documents(doc, key)
+        .map(|role| {
+            format!(
+                "{}.{}",
+                role.get_str("db").unwrap_or_default(),
+                role.get_str("role").unwrap_or_default()
+            )
+        })
+        .collect();
+    Series::new("", names)
+}
+
+fn string_list(doc: &Document, key: &str) -> Series {
+    let values: Vec<&str> = doc
+        .get_array(key)
+        .map(|arr| arr.iter().filter_map(Bson::as_str).collect())
+        .unwrap_or_default();
+    Series::new("", values)
+}
+
+/// List the users defined on `db` using the `usersInfo` command.
+///
+/// Returns a dataframe with the columns `user`, `db`, `roles` and `mechanisms`.
+pub fn list_users(connection_str: &str, db: &str) -> PolarsResult<DataFrame> {
+    let response = run_command(connection_str, db, doc! {"usersInfo": 1})?;
+
+    let mut user = Vec::new();
+    let mut user_db = Vec::new();
+    let mut roles = Vec::new();
+    let mut mechanisms = Vec::new();
+    for doc in documents(&response, "users") {
+        user.push(doc.get_str("user").ok());
+        user_db.push(doc.get_str("db").ok());
+        roles.push(role_names(doc, "roles"));
+        mechanisms.push(string_list(doc, "mechanisms"));
+    }
+
+    DataFrame::new(vec![
+        Series::new("user", user),
+        Series::new("db", user_db),
+        Series::new("roles", roles),
+        Series::new("mechanisms", mechanisms),
+    ])
+}
+
+/// List the roles defined on `db` using the `rolesInfo` command.
+///
+/// Returns a dataframe with the columns `role`, `db`, `is_builtin` and `roles`,
+/// where `roles` holds the roles this role inherits from.
+pub fn list_roles(connection_str: &str, db: &str) -> PolarsResult<DataFrame> {
+    let response = run_command(
+        connection_str,
+        db,
+        doc! {"rolesInfo": 1, "showBuiltinRoles": true},
+    )?;
+
+    let mut role = Vec::new();
+    let mut role_db = Vec::new();
+    let mut is_builtin = Vec::new();
+    let mut roles = Vec::new();
+    for doc in documents(&response, "roles") {
+        role.push(doc.get_str("role").ok());
+        role_db.push(doc.get_str("db").ok());
+        is_builtin.push(doc.get_bool("isBuiltin").ok());
+        roles.push(role_names(doc, "roles"));
+    }
+
+    DataFrame::new(vec![
+        Series::new("role", role),
+        Series::new("db", role_db),
+        Series::new("is_builtin", is_builtin),
+        Series::new("roles", roles),
+    ])
+}
+
+/// Read a numeric field that the server may return as any of the bson number types.
+fn get_number(doc: &Document, key: &str) -> Option<i64> {
+    match doc.get(key)? {
+        Bson::Int32(v) => Some(*v as i64),
+        Bson::Int64(v) => Some(*v),
+        Bson::Double(v) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+/// List the databases of the cluster using the `listDatabases` command.
+///
+/// Returns a dataframe with the columns `name`, `size_on_disk` and `empty`.
+pub fn list_databases(connection_str: &str) -> PolarsResult<DataFrame> {
+    let response = run_command(connection_str, "admin", doc! {"listDatabases": 1})?;
+
+    let mut name = Vec::new();
+    let mut size_on_disk = Vec::new();
+    let mut empty = Vec::new();
+    for doc in documents(&response, "databases") {
+        name.push(doc.get_str("name").ok());
+        size_on_disk.push(get_number(doc, "sizeOnDisk"));
+        empty.push(doc.get_bool("empty").ok());
+    }
+
+    DataFrame::new(vec![
+        Series::new("name", name),
+        Series::new("size_on_disk", size_on_disk),
+        Series::new("empty", empty),
+    ])
+}
+
+/// List the collections and views of `db` using the `listCollections` command,
+/// with the statistics of every collection from the `collStats` command.
+///
+/// Returns a dataframe with the columns `name`, `type`, `options` as extended JSON,
+/// `count`, `size`, `storage_size` and `total_index_size`. The statistics are null for views,
+/// and `count` is the estimate from the collection metadata.
+pub fn list_collections(connection_str: &str, db: &str) -> PolarsResult<DataFrame> {
+    let database = connect(connection_str)?.database(db);
+    let mut specs = database
+        .run_command(doc! {"listCollections": 1, "cursor": {}}, None)
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+    let mut batch = specs
+        .get_document("cursor")
+        .ok()
+        .cloned()
+        .unwrap_or_default();
+    let mut collections: Vec<Document> = documents(&batch, "firstBatch").cloned().collect();
+    while let Ok(id) = batch.get_i64("id") {
+        if id == 0 {
+            break;
+        }
+        specs = database
+            .run_command(
+                doc! {"getMore": id, "collection": "$cmd.listCollections"},
+                None,
+            )
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+        batch = specs
+            .get_document("cursor")
+            .ok()
+            .cloned()
+            .unwrap_or_default();
+        collections.extend(documents(&batch, "nextBatch").cloned());
+    }
+
+    let mut name = Vec::new();
+    let mut kind = Vec::new();
+    let mut options = Vec::new();
+    let mut count = Vec::new();
+    let mut size = Vec::new();
+    let mut storage_size = Vec::new();
+    let mut total_index_size = Vec::new();
+    for doc in &collections {
+        let coll = doc.get_str("name").ok();
+        name.push(coll);
+        kind.push(doc.get_str("type").ok());
+        options.push(doc.get_document("options").ok().map(|opts| {
+            Bson::Document(opts.clone())
+                .into_relaxed_extjson()
+                .to_string()
+        }));
+
+        // views don't have any storage of their own.
+        let stats = match (coll, doc.get_str("type")) {
+            (Some(coll), Ok("collection")) => {
+                database.run_command(doc! {"collStats": coll}, None).ok()
+            }
+            _ => None,
+        };
+        let stat = |key: &str| stats.as_ref().and_then(|stats| get_number(stats, key));
+        count.push(stat("count"));
+        size.push(stat("size"));
+        storage_size.push(stat("storageSize"));
+        total_index_size.push(stat("totalIndexSize"));
+    }
+
+    DataFrame::new(vec![
+        Series::new("name", name),
+        Series::new("type", kind),
+        Series::new("options", options),
+        Series::new("count", count),
+        Series::new("size", size),
+        Series::new("storage_size", storage_size),
+        Series::new("total_index_size", total_index_size),
+    ])
+}
+
+/// List the indexes of `collection` using the `listIndexes` command,
+/// with their sizes from the `collStats` command.
+///
+/// Returns a dataframe with the columns `name`, `key` as extended JSON, e.g. `{"ts":-1}`,
+/// `unique`, `sparse` and `size` in bytes.
+pub fn list_indexes(connection_str: &str, db: &str, collection: &str) -> PolarsResult<DataFrame> {
+    let database = connect(connection_str)?.database(db);
+    let response = database
+        .run_command(doc! {"listIndexes": collection, "cursor": {}}, None)
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+    let cursor = response
+        .get_document("cursor")
+        .ok()
+        .cloned()
+        .unwrap_or_default();
+    // index sizes are only reported by the storage engine, and not at all on views.
+    let sizes = database
+        .run_command(doc! {"collStats": collection}, None)
+        .ok()
+        .and_then(|stats| stats.get_document("indexSizes").ok().cloned())
+        .unwrap_or_default();
+
+    let mut name = Vec::new();
+    let mut key = Vec::new();
+    let mut unique = Vec::new();
+    let mut sparse = Vec::new();
+    let mut size = Vec::new();
+    for doc in documents(&cursor, "firstBatch") {
+        let index = doc.get_str("name").ok();
+        name.push(index);
+        key.push(doc.get_document("key").ok().map(|key| {
+            Bson::Document(key.clone())
+                .into_relaxed_extjson()
+                .to_string()
+        }));
+        unique.push(doc.get_bool("unique").unwrap_or(false));
+        sparse.push(doc.get_bool("sparse").unwrap_or(false));
+        size.push(index.and_then(|index| get_number(&sizes, index)));
+    }
+
+    DataFrame::new(vec![
+        Series::new("name", name),
+        Series::new("key", key),
+        Series::new("unique", unique),
+        Series::new("sparse", sparse),
+        Series::new("size", size),
+    ])
+}
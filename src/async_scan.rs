@@ -0,0 +1,544 @@
+//! Async variant of the mongodb scan, built on the tokio based mongodb driver.
+//!
+//! It shares the schema inference, buffers and conversions with the blocking scan,
+//! and only reads the documents differently.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::OnceCell;
+use polars::prelude::*;
+
+use mongodb::{
+    bson::{doc, Bson, Document},
+    error::ErrorKind,
+    options::{AggregateOptions, ClientOptions, FindOptions},
+    Client, Collection,
+};
+
+use crate::predicate::{self, FilterContext, SourceTypes};
+use crate::{
+    add_document, apply_categorical_columns, apply_dtype_overrides, buffer::init_buffers,
+    infer_documents_schema, infer_source_types,
+};
+use crate::{
+    conversion::ConversionOptions, CancellationToken, FallbackDtype, MongoScanOptions, ObjectIdAs,
+    ParseMode, ParseReport, PolarsMongoError, RegexAs, TimestampAs, UuidAs,
+};
+
+pub struct MongoScanAsync {
+    client_options: ClientOptions,
+    client: OnceCell<Client>,
+    db: String,
+    collection_name: String,
+    pub filter: Option<Document>,
+    pub sort: Option<Document>,
+    pub columns: Option<Vec<String>>,
+    pub include_id: bool,
+    pub skip: Option<usize>,
+    pub batch_size: Option<usize>,
+    pub max_time: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub allow_disk_use: bool,
+    pub no_cursor_timeout: bool,
+    pub infer_schema_sample: bool,
+    pub cancellation: Option<CancellationToken>,
+    conversion: ConversionOptions,
+    /// Bson types of the fields sampled by the first schema inference, to translate predicates.
+    source_types: OnceCell<SourceTypes>,
+}
+
+impl MongoScanAsync {
+    pub async fn new(connection_str: String, db: String, collection: String) -> PolarsResult<Self> {
+        let client_options = ClientOptions::parse(connection_str)
+            .await
+            .map_err(|err| PolarsMongoError::from_driver(err, format!("{}.{}", db, collection)))?;
+        Ok(Self::from_client_options(client_options, db, collection))
+    }
+
+    /// Scan a collection with driver options built elsewhere, e.g. by [`MongoConnection`](crate::MongoConnection).
+    pub fn from_client_options(
+        client_options: ClientOptions,
+        db: String,
+        collection: String,
+    ) -> Self {
+        MongoScanAsync {
+            client_options,
+            client: OnceCell::new(),
+            db,
+            collection_name: collection,
+            filter: None,
+            sort: None,
+            columns: None,
+            include_id: true,
+            skip: None,
+            batch_size: None,
+            max_time: None,
+            timeout: None,
+            allow_disk_use: false,
+            no_cursor_timeout: false,
+            infer_schema_sample: false,
+            cancellation: None,
+            conversion: ConversionOptions::default(),
+            source_types: OnceCell::new(),
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: Option<usize>) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Infer the schema from a random `$sample` of the collection instead of the first documents.
+    pub fn with_infer_schema_sample(mut self, infer_schema_sample: bool) -> Self {
+        self.infer_schema_sample = infer_schema_sample;
+        self
+    }
+
+    pub fn with_decimal128_as_float(mut self, decimal128_as_float: bool) -> Self {
+        self.conversion.decimal128_as_float = decimal128_as_float;
+        self
+    }
+
+    /// Set how `ObjectId` values are represented. Defaults to [`ObjectIdAs::Hex`].
+    pub fn with_object_id_as(mut self, object_id_as: ObjectIdAs) -> Self {
+        self.conversion.object_id_as = object_id_as;
+        self
+    }
+
+    /// Set how `Timestamp` values are represented. Defaults to [`TimestampAs::String`].
+    pub fn with_timestamp_as(mut self, timestamp_as: TimestampAs) -> Self {
+        self.conversion.timestamp_as = timestamp_as;
+        self
+    }
+
+    /// Set how regular expressions are represented. Defaults to [`RegexAs::String`].
+    pub fn with_regex_as(mut self, regex_as: RegexAs) -> Self {
+        self.conversion.regex_as = regex_as;
+        self
+    }
+
+    /// Set how UUIDs stored as `Binary` values are represented. Defaults to [`UuidAs::Binary`].
+    pub fn with_uuid_as(mut self, uuid_as: UuidAs) -> Self {
+        self.conversion.uuid_as = uuid_as;
+        self
+    }
+
+    /// Set how bson types without a polars counterpart are read.
+    /// Defaults to [`FallbackDtype::Utf8Json`].
+    pub fn with_fallback_dtype(mut self, fallback_dtype: FallbackDtype) -> Self {
+        self.conversion.fallback_dtype = fallback_dtype;
+        self
+    }
+
+    /// Flatten nested documents into top-level columns named by their path, joined by
+    /// `separator`, e.g. `address.city`, instead of reading them as `Struct` columns.
+    pub fn with_flatten(mut self, separator: Option<String>) -> Self {
+        self.conversion.flatten = separator;
+        self
+    }
+
+    /// Time unit of the `Datetime` columns. Defaults to milliseconds, the precision of bson datetimes.
+    pub fn with_datetime_time_unit(mut self, time_unit: Option<TimeUnit>) -> Self {
+        self.conversion.time_unit = time_unit;
+        self
+    }
+
+    /// Set what happens with values that don't match the dtype of their column.
+    pub fn with_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.conversion.parse_mode = parse_mode;
+        self
+    }
+
+    /// Add the counts of invalid values and skipped documents to `report` while scanning.
+    pub fn with_parse_report(mut self, report: Option<Arc<Mutex<ParseReport>>>) -> Self {
+        self.conversion.parse_report = report;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Option<Document>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sort the documents server side, e.g. `doc! {"ts": 1}`.
+    pub fn with_sort(mut self, sort: Option<Document>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Only infer and fetch these fields, in this order, instead of every field of the documents.
+    pub fn with_columns(mut self, columns: Option<Vec<String>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Read the `_id` field. Defaults to `true`.
+    pub fn with_include_id(mut self, include_id: bool) -> Self {
+        self.include_id = include_id;
+        self
+    }
+
+    /// Skip this many documents on the server before reading any, with `skip`.
+    pub fn with_skip(mut self, skip: Option<usize>) -> Self {
+        self.skip = skip;
+        self
+    }
+
+    /// Let the server abort queries that run longer than `max_time` (`maxTimeMS`).
+    pub fn with_max_time(mut self, max_time: Option<Duration>) -> Self {
+        self.max_time = max_time;
+        self
+    }
+
+    /// Abort the scan with an error once it has been running for longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Let aggregations and sorted `find`s write temporary files on the server.
+    pub fn with_allow_disk_use(mut self, allow_disk_use: bool) -> Self {
+        self.allow_disk_use = allow_disk_use;
+        self
+    }
+
+    /// Keep idle cursors open instead of letting the server close them after 10 minutes.
+    pub fn with_no_cursor_timeout(mut self, no_cursor_timeout: bool) -> Self {
+        self.no_cursor_timeout = no_cursor_timeout;
+        self
+    }
+
+    /// Stop the scan once `cancellation` is cancelled, and fail it with
+    /// [`PolarsMongoError::Cancelled`].
+    pub fn with_cancellation(mut self, cancellation: Option<CancellationToken>) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    fn get_collection(&self) -> PolarsResult<Collection<Document>> {
+        let client = self.client.get_or_try_init(|| {
+            Client::with_options(self.client_options.clone()).map_err(|err| {
+                PolarsError::from(PolarsMongoError::from_driver(
+                    err,
+                    format!("{}.{}", self.db, self.collection_name),
+                ))
+            })
+        })?;
+
+        let database = client.database(&self.db);
+        Ok(database.collection::<Document>(&self.collection_name))
+    }
+
+    /// Projection of the fields in `columns`, if only those are read.
+    fn projection(&self) -> Option<Document> {
+        let mut prj = Document::new();
+        for name in self.columns.iter().flatten() {
+            for path in self.conversion.flattened_paths(name) {
+                prj.insert(path, Bson::Int64(1));
+            }
+        }
+        if !self.include_id {
+            prj.insert("_id", Bson::Int32(0));
+        }
+        (!prj.is_empty()).then_some(prj)
+    }
+
+    /// The columns of `schema` that are read: those in `columns`, in the order of `columns`,
+    /// and `_id` only if it is included.
+    fn select_columns(&self, schema: Schema) -> Schema {
+        let schema = match &self.columns {
+            Some(columns) => columns
+                .iter()
+                .filter_map(|name| schema.get_field(name))
+                .collect(),
+            None => schema,
+        };
+        match self.include_id {
+            true => schema,
+            false => schema
+                .iter_fields()
+                .filter(|fld| fld.name() != "_id")
+                .collect(),
+        }
+    }
+
+    async fn find(
+        &self,
+        collection: &Collection<Document>,
+        skip: Option<usize>,
+        limit: Option<usize>,
+    ) -> mongodb::error::Result<mongodb::Cursor<Document>> {
+        let find_options = FindOptions::builder()
+            .skip(skip.map(|n| n as u64))
+            .limit(limit.map(|n| n as i64))
+            .projection(self.projection())
+            .batch_size(self.batch_size.map(|b| b as u32))
+            .sort(self.sort.clone())
+            .max_time(self.max_time)
+            .allow_disk_use(self.allow_disk_use.then_some(true))
+            .no_cursor_timeout(self.no_cursor_timeout.then_some(true))
+            .build();
+        collection
+            .find(self.filter.clone(), Some(find_options))
+            .await
+    }
+
+    /// Infer the schema from the first `infer_schema_length` documents, or a random sample
+    /// of them with [`MongoScanAsync::with_infer_schema_sample`]. Defaults to `100`.
+    pub async fn schema(&self, infer_schema_length: Option<usize>) -> PolarsResult<Schema> {
+        let collection = self.get_collection()?;
+        let infer_schema_length = infer_schema_length.unwrap_or(100);
+
+        let mut docs = vec![];
+        async {
+            let mut cursor = match self.infer_schema_sample {
+                true => {
+                    let mut pipeline = vec![];
+                    if let Some(filter) = &self.filter {
+                        pipeline.push(doc! {"$match": filter.clone()});
+                    }
+                    pipeline.push(doc! {"$sample": {"size": infer_schema_length as i64}});
+                    pipeline.extend(self.projection().map(|prj| doc! {"$project": prj}));
+                    let options = AggregateOptions::builder()
+                        .allow_disk_use(self.allow_disk_use.then_some(true))
+                        .build();
+                    collection.aggregate(pipeline, options).await?
+                }
+                false => {
+                    self.find(&collection, None, Some(infer_schema_length))
+                        .await?
+                }
+            };
+            while cursor.advance().await? {
+                docs.push(cursor.deserialize_current()?);
+            }
+            Ok(())
+        }
+        .await
+        .map_err(|err: mongodb::error::Error| {
+            PolarsError::ComputeError(format!("{:#?}", err).into())
+        })?;
+
+        let _ = self.source_types.set(infer_source_types(
+            docs.iter(),
+            infer_schema_length,
+            &self.conversion,
+        ));
+        Ok(self.select_columns(infer_documents_schema(
+            docs.iter(),
+            infer_schema_length,
+            &self.conversion,
+        )))
+    }
+
+    /// Split `predicate` into a mongodb filter and the part polars has to evaluate,
+    /// for a scan with the given `schema`.
+    fn split_predicate(
+        &self,
+        predicate: &Expr,
+        schema: &Schema,
+    ) -> (Option<Document>, Option<Expr>) {
+        if !self.conversion.filterable() {
+            return (None, Some(predicate.clone()));
+        }
+        // the schema was given if it was not inferred.
+        let source_types = match self.source_types.get() {
+            Some(source_types) => source_types.clone(),
+            None => predicate::given_source_types(schema),
+        };
+        let ctx = FilterContext {
+            schema,
+            source_types: &source_types,
+            conversion: &self.conversion,
+        };
+        predicate::split_predicate(predicate, &ctx)
+    }
+
+    /// Read up to `n_rows` documents, after the first `skip` ones, into a [`DataFrame`] with
+    /// the given `schema`.
+    pub async fn scan(&self, schema: &Schema, n_rows: Option<usize>) -> PolarsResult<DataFrame> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let collection = self.get_collection()?;
+        let mut buffers = init_buffers(schema, n_rows.unwrap_or(1024))?;
+
+        let mut report = ParseReport::default();
+        let mut cursor = self
+            .find(&collection, self.skip, n_rows)
+            .await
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+        while cursor
+            .advance()
+            .await
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?
+        {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(PolarsError::ComputeError(
+                    format!(
+                        "scan of {}.{} did not finish within {:?}",
+                        self.db,
+                        self.collection_name,
+                        self.timeout.unwrap_or_default()
+                    )
+                    .into(),
+                ));
+            }
+            // dropping the cursor kills it on the server.
+            if self
+                .cancellation
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled())
+            {
+                return Err(PolarsMongoError::Cancelled {
+                    namespace: format!("{}.{}", self.db, self.collection_name),
+                }
+                .into());
+            }
+            match cursor.deserialize_current() {
+                Ok(doc) => add_document(&doc, &mut buffers, &self.conversion, &mut report)?,
+                Err(err)
+                    if matches!(*err.kind, ErrorKind::BsonDeserialization(_))
+                        && self.conversion.parse_mode == ParseMode::SkipDocument =>
+                {
+                    report.skipped_documents += 1
+                }
+                Err(err) => return Err(PolarsError::ComputeError(format!("{:#?}", err).into())),
+            }
+        }
+        self.conversion.report(report);
+
+        DataFrame::new(
+            buffers
+                .into_values()
+                .map(|buf| buf.into_series())
+                .collect::<PolarsResult<_>>()?,
+        )
+    }
+}
+
+/// Read a mongodb collection without blocking the async runtime.
+///
+/// The documents are fetched with the async driver when the returned future is awaited,
+/// and the resulting [`LazyFrame`] is backed by the fetched data.
+///
+/// `unnest_columns` and `include_raw` are only supported by the blocking scan, and fail the
+/// scan with an error.
+pub async fn scan_mongo_collection_async(options: MongoScanOptions) -> PolarsResult<LazyFrame> {
+    if !options.unnest_columns.is_empty() || options.include_raw {
+        return Err(PolarsError::InvalidOperation(
+            "unnest_columns and include_raw are not supported by async scans".into(),
+        ));
+    }
+    let f = match &options.connection {
+        Some(connection) => MongoScanAsync::from_client_options(
+            connection.client_options()?,
+            options.db.clone(),
+            options.collection.clone(),
+        ),
+        None => {
+            MongoScanAsync::new(
+                options.connection_str.clone(),
+                options.db.clone(),
+                options.collection.clone(),
+            )
+            .await?
+        }
+    };
+    let f = f
+        .with_batch_size(options.batch_size)
+        .with_infer_schema_sample(options.infer_schema_sample)
+        .with_decimal128_as_float(options.decimal128_as_float)
+        .with_object_id_as(options.object_id_as)
+        .with_timestamp_as(options.timestamp_as)
+        .with_regex_as(options.regex_as)
+        .with_uuid_as(options.uuid_as)
+        .with_fallback_dtype(options.fallback_dtype)
+        .with_flatten(options.flattening())
+        .with_datetime_time_unit(options.datetime_time_unit)
+        .with_parse_mode(options.parse_mode)
+        .with_parse_report(options.parse_report.clone());
+
+    let f = f
+        .with_filter(options.filter.clone())
+        .with_sort(options.sort.clone())
+        .with_columns(options.columns.clone())
+        .with_include_id(options.include_id)
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_timeout(options.timeout_ms.map(Duration::from_millis))
+        .with_allow_disk_use(options.allow_disk_use)
+        .with_no_cursor_timeout(options.no_cursor_timeout)
+        .with_cancellation(options.cancellation.clone());
+
+    let mut schema = match &options.schema {
+        Some(schema) => f.select_columns(schema.clone()),
+        None => {
+            let mut schema = f.schema(options.infer_schema_length).await?;
+            apply_dtype_overrides(&mut schema, options.dtype_overrides.as_deref());
+            schema
+        }
+    };
+    apply_categorical_columns(&mut schema, &options.categorical_columns);
+    // the predicate is translated against the schema, so it is not part of the inference.
+    let (filter, remaining) = match &options.predicate {
+        Some(predicate) => f.split_predicate(predicate, &schema),
+        None => (None, None),
+    };
+    let filter = predicate::and_filters(f.filter.clone(), filter);
+    // rows can only be skipped and limited at the source if all filtering happens server side.
+    let f = f
+        .with_filter(filter)
+        .with_skip(options.skip_rows.filter(|_| remaining.is_none()));
+    let n_rows = options.n_rows.filter(|_| remaining.is_none());
+    let mut lf = f.scan(&schema, n_rows).await?.lazy();
+
+    if let Some(remaining) = remaining {
+        lf = lf.filter(remaining);
+        match (options.skip_rows, options.n_rows) {
+            (None, None) => {}
+            (skip_rows, n_rows) => {
+                let len = n_rows.map_or(IdxSize::MAX, |n| n as IdxSize);
+                lf = lf.slice(skip_rows.unwrap_or(0) as i64, len);
+            }
+        }
+    }
+    if let Some(row_count) = &options.row_count {
+        lf = lf.with_row_count(&row_count.name, Some(row_count.offset));
+    }
+    Ok(options.rename_columns(lf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan() -> MongoScanAsync {
+        MongoScanAsync::from_client_options(ClientOptions::default(), "db".into(), "coll".into())
+    }
+
+    #[test]
+    fn columns_are_projected_and_selected() {
+        let f = scan()
+            .with_columns(Some(vec!["b".into(), "a".into()]))
+            .with_include_id(false);
+        assert_eq!(
+            f.projection(),
+            Some(doc! {"b": 1_i64, "a": 1_i64, "_id": 0})
+        );
+
+        let schema = Schema::from(
+            [
+                Field::new("_id", DataType::Utf8),
+                Field::new("a", DataType::Int64),
+                Field::new("b", DataType::Utf8),
+                Field::new("c", DataType::Boolean),
+            ]
+            .into_iter(),
+        );
+        let selected = f.select_columns(schema);
+        assert_eq!(selected.iter_names().collect::<Vec<_>>(), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn whole_documents_are_read_by_default() {
+        assert_eq!(scan().projection(), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! Read a collection in chunks, without materializing it as a whole.
+use std::time::Duration;
+
+use polars::prelude::*;
+
+use mongodb::{bson::Document, error::ErrorKind, options::FindOptions, sync::Cursor};
+
+use crate::{projection, read_documents, MongoScan, MongoScanOptions, ParseMode, ParseReport};
+
+/// Reads a collection as a sequence of [`DataFrame`]s of at most `chunk_size` rows.
+///
+/// All chunks share the schema of the collection, which is inferred up front if not provided.
+/// Only a single chunk is held in memory at a time, so collections that don't fit in memory
+/// can be processed out-of-core, e.g. by writing every chunk to a file.
+pub struct MongoBatchedReader {
+    scan: MongoScan,
+    cursor: Option<Cursor<Document>>,
+    schema: Schema,
+    remaining: Option<Expr>,
+    n_rows: Option<usize>,
+    n_read: usize,
+    skip_rows: Option<usize>,
+    /// Rows that are still to be skipped, if they can't be skipped on the server.
+    n_skip: usize,
+    pub chunk_size: usize,
+}
+
+impl MongoBatchedReader {
+    /// Read the collection in `options`. Supported parts of `options.predicate` are evaluated
+    /// server side, the rest is applied to every chunk.
+    pub fn new(options: MongoScanOptions) -> PolarsResult<Self> {
+        let scan = MongoScan::from_options(&options)?
+            .with_batch_size(options.batch_size)
+            .with_target_batch_bytes(options.target_batch_bytes)
+            .with_filter(options.filter.clone())
+            .with_sort(options.sort.clone())
+            .with_ordered(options.ordered)
+            .with_max_time(options.max_time_ms.map(Duration::from_millis))
+            .with_allow_disk_use(options.allow_disk_use)
+            .with_no_cursor_timeout(options.no_cursor_timeout)
+            .with_schema(options.schema.clone())
+            .with_dtype_overrides(options.dtype_overrides.clone())
+            .with_infer_schema_sample(options.infer_schema_sample)
+            .with_columns(options.columns.clone())
+            .with_include_id(options.include_id)
+            .with_categorical_columns(options.categorical_columns.clone())
+            .with_decimal128_as_float(options.decimal128_as_float)
+            .with_object_id_as(options.object_id_as)
+            .with_timestamp_as(options.timestamp_as)
+            .with_regex_as(options.regex_as)
+            .with_uuid_as(options.uuid_as)
+            .with_fallback_dtype(options.fallback_dtype)
+            .with_include_raw(options.include_raw)
+            .with_raw_format(options.raw_format)
+            .with_flatten(options.flattening())
+            .with_datetime_time_unit(options.datetime_time_unit)
+            .with_parse_mode(options.parse_mode)
+            .with_parse_report(options.parse_report.clone())
+            .with_cancellation(options.cancellation.clone())
+            .with_max_docs_per_second(options.max_docs_per_second);
+        let schema = scan.schema(options.infer_schema_length)?;
+        let (scan, remaining) = match &options.predicate {
+            Some(predicate) => scan.with_predicate(predicate, &schema),
+            None => (scan, None),
+        };
+        // rows can only be skipped at the source if all filtering happens server side.
+        let n_skip = match remaining {
+            Some(_) => options.skip_rows.unwrap_or(0),
+            None => 0,
+        };
+
+        Ok(MongoBatchedReader {
+            scan,
+            cursor: None,
+            schema,
+            remaining,
+            n_rows: options.n_rows,
+            n_read: 0,
+            skip_rows: options.skip_rows,
+            n_skip,
+            chunk_size: 100_000,
+        })
+    }
+
+    /// Maximum number of rows per chunk. Defaults to `100_000`.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Schema of the chunks.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open_cursor(&mut self) -> PolarsResult<()> {
+        if self.cursor.is_none() {
+            // rows can only be limited at the source if all filtering happens server side.
+            let limit = self.n_rows.filter(|_| self.remaining.is_none());
+            let skip = self.skip_rows.filter(|_| self.remaining.is_none());
+            let mut prj = projection(&self.schema, &self.scan.conversion);
+            self.scan.project_id(&mut prj);
+            let find_options = FindOptions::builder()
+                .projection(Some(prj))
+                .batch_size(self.scan.effective_batch_size())
+                .sort(self.scan.scan_sort())
+                .max_time(self.scan.max_time)
+                .allow_disk_use(self.scan.allow_disk_use.then_some(true))
+                .no_cursor_timeout(self.scan.no_cursor_timeout.then_some(true))
+                .skip(skip.map(|n| n as u64))
+                .limit(limit.map(|n| n as i64))
+                .build();
+            let cursor = self
+                .scan
+                .get_collection()?
+                .find(self.scan.filter.clone(), Some(find_options))
+                .map_err(|err| self.scan.driver_error(err))?;
+            self.cursor = Some(cursor);
+        }
+        Ok(())
+    }
+
+    /// Read the next chunk, or `None` once the collection is exhausted.
+    pub fn next_batch(&mut self) -> PolarsResult<Option<DataFrame>> {
+        let n_left = self.n_rows.map_or(usize::MAX, |n| n - self.n_read);
+        if n_left == 0 {
+            return Ok(None);
+        }
+        if let Err(err) = self.scan.check_cancelled() {
+            // dropping the cursor kills it on the server.
+            self.cursor = None;
+            return Err(err);
+        }
+        let chunk_size = self.chunk_size;
+        let parse_mode = self.scan.conversion.parse_mode;
+        self.open_cursor()?;
+        let cursor = self.cursor.as_mut().unwrap();
+        let throttle = &self.scan.throttle;
+
+        let mut report = ParseReport::default();
+        let mut exhausted = false;
+        let mut next_document = || -> PolarsResult<Option<Document>> {
+            for doc in cursor.by_ref() {
+                match doc {
+                    Ok(doc) => {
+                        throttle.document();
+                        return Ok(Some(doc));
+                    }
+                    Err(err)
+                        if matches!(*err.kind, ErrorKind::BsonDeserialization(_))
+                            && parse_mode == ParseMode::SkipDocument =>
+                    {
+                        report.skipped_documents += 1
+                    }
+                    Err(err) => {
+                        return Err(PolarsError::ComputeError(format!("{:#?}", err).into()))
+                    }
+                }
+            }
+            exhausted = true;
+            Ok(None)
+        };
+        let mut df = read_documents(
+            &mut next_document,
+            Some(self.schema.clone()),
+            None,
+            Some(chunk_size),
+            &self.scan.conversion,
+        )?;
+        self.scan.conversion.report(report);
+
+        if df.height() == 0 && exhausted {
+            return Ok(None);
+        }
+        if let Some(remaining) = &self.remaining {
+            df = df.lazy().filter(remaining.clone()).collect()?;
+        }
+        if self.n_skip > 0 {
+            let n_skip = self.n_skip.min(df.height());
+            df = df.slice(n_skip as i64, df.height() - n_skip);
+            self.n_skip -= n_skip;
+        }
+        if df.height() > n_left {
+            df = df.head(Some(n_left));
+        }
+        self.n_read += df.height();
+        Ok(Some(df))
+    }
+}
+
+impl Iterator for MongoBatchedReader {
+    type Item = PolarsResult<DataFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
+    }
+}
@@ -0,0 +1,115 @@
+//! Read `.bson` files, as written by `mongodump`, without a running server.
+use std::io::{Read, Seek, SeekFrom};
+
+use polars::prelude::*;
+
+use mongodb::bson::Document;
+
+use crate::{
+    conversion::ConversionOptions, read_documents, FallbackDtype, ObjectIdAs, RegexAs, TimestampAs,
+    UuidAs,
+};
+
+/// Read a `.bson` dump file into a [`DataFrame`].
+///
+/// A dump is a plain concatenation of bson documents. The schema is inferred from
+/// the first `infer_schema_length` documents, after which the rest of the file is
+/// streamed into the column buffers.
+pub struct BsonFileReader<R> {
+    reader: R,
+    infer_schema_length: Option<usize>,
+    schema: Option<Schema>,
+    n_rows: Option<usize>,
+    conversion: ConversionOptions,
+}
+
+impl<R> BsonFileReader<R>
+where
+    R: Read + Seek,
+{
+    /// Number of documents used to infer the schema. Defaults to `100`.
+    pub fn with_infer_schema_length(mut self, infer_schema_length: Option<usize>) -> Self {
+        self.infer_schema_length = infer_schema_length;
+        self
+    }
+
+    /// Use `schema` instead of inferring it from the file.
+    pub fn with_schema(mut self, schema: Option<Schema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Stop reading after `n_rows` documents.
+    pub fn with_n_rows(mut self, n_rows: Option<usize>) -> Self {
+        self.n_rows = n_rows;
+        self
+    }
+
+    pub fn with_decimal128_as_float(mut self, decimal128_as_float: bool) -> Self {
+        self.conversion.decimal128_as_float = decimal128_as_float;
+        self
+    }
+
+    pub fn with_object_id_as(mut self, object_id_as: ObjectIdAs) -> Self {
+        self.conversion.object_id_as = object_id_as;
+        self
+    }
+
+    pub fn with_timestamp_as(mut self, timestamp_as: TimestampAs) -> Self {
+        self.conversion.timestamp_as = timestamp_as;
+        self
+    }
+
+    pub fn with_regex_as(mut self, regex_as: RegexAs) -> Self {
+        self.conversion.regex_as = regex_as;
+        self
+    }
+
+    pub fn with_uuid_as(mut self, uuid_as: UuidAs) -> Self {
+        self.conversion.uuid_as = uuid_as;
+        self
+    }
+
+    pub fn with_fallback_dtype(mut self, fallback_dtype: FallbackDtype) -> Self {
+        self.conversion.fallback_dtype = fallback_dtype;
+        self
+    }
+}
+
+impl<R> SerReader<R> for BsonFileReader<R>
+where
+    R: Read + Seek,
+{
+    fn new(reader: R) -> Self {
+        BsonFileReader {
+            reader,
+            infer_schema_length: None,
+            schema: None,
+            n_rows: None,
+            conversion: ConversionOptions::default(),
+        }
+    }
+
+    fn finish(mut self) -> PolarsResult<DataFrame> {
+        let len = self.reader.seek(SeekFrom::End(0))?;
+        let mut pos = self.reader.seek(SeekFrom::Start(0))?;
+        let reader = &mut self.reader;
+        let next_document = || -> PolarsResult<Option<Document>> {
+            if pos >= len {
+                return Ok(None);
+            }
+            let doc = Document::from_reader(&mut *reader)
+                .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+            pos = reader.stream_position()?;
+            Ok(Some(doc))
+        };
+
+        read_documents(
+            next_document,
+            self.schema,
+            self.infer_schema_length,
+            self.n_rows,
+            &self.conversion,
+        )
+    }
+}
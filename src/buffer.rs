@@ -3,36 +3,58 @@ use mongodb::bson::Bson;
 use num::traits::NumCast;
 use polars::export::arrow::types::NativeType;
 use polars::prelude::*;
+use polars_core::chunked_array::builder::get_list_builder;
+use std::borrow::Cow;
 
 pub(crate) fn init_buffers(
     schema: &polars::prelude::Schema,
     capacity: usize,
-) -> PolarsResult<PlIndexMap<String, Buffer>> {
+) -> PolarsResult<PlIndexMap<String, Buffer<'_>>> {
     schema
         .iter()
-        .map(|(name, dtype)| {
-            let builder = match &dtype {
-                DataType::Boolean => Buffer::Boolean(BooleanChunkedBuilder::new(name, capacity)),
-                DataType::Int32 => Buffer::Int32(PrimitiveChunkedBuilder::new(name, capacity)),
-                DataType::Int64 => Buffer::Int64(PrimitiveChunkedBuilder::new(name, capacity)),
-                DataType::UInt32 => Buffer::UInt32(PrimitiveChunkedBuilder::new(name, capacity)),
-                DataType::UInt64 => Buffer::UInt64(PrimitiveChunkedBuilder::new(name, capacity)),
-                DataType::Float32 => Buffer::Float32(PrimitiveChunkedBuilder::new(name, capacity)),
-                DataType::Float64 => Buffer::Float64(PrimitiveChunkedBuilder::new(name, capacity)),
-                DataType::Utf8 => {
-                    Buffer::Utf8(Utf8ChunkedBuilder::new(name, capacity, capacity * 5))
-                }
-                DataType::Datetime(_, _) => {
-                    Buffer::Datetime(PrimitiveChunkedBuilder::new(name, capacity))
-                }
-                DataType::Date => Buffer::Date(PrimitiveChunkedBuilder::new(name, capacity)),
-                _ => Buffer::All((Vec::with_capacity(capacity), name)),
-            };
-            Ok((name.clone(), builder))
-        })
+        .map(|(name, dtype)| Ok((name.clone(), init_buffer(name, dtype, capacity))))
         .collect()
 }
 
+fn init_buffer<'a>(name: &'a str, dtype: &'a DataType, capacity: usize) -> Buffer<'a> {
+    match dtype {
+        DataType::Boolean => Buffer::Boolean(BooleanChunkedBuilder::new(name, capacity)),
+        DataType::Int32 => Buffer::Int32(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::Int64 => Buffer::Int64(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::UInt32 => Buffer::UInt32(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::UInt64 => Buffer::UInt64(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::Float32 => Buffer::Float32(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::Float64 => Buffer::Float64(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::Utf8 => Buffer::Utf8(Utf8ChunkedBuilder::new(name, capacity, capacity * 5)),
+        DataType::Categorical(_) => {
+            Buffer::Categorical(Utf8ChunkedBuilder::new(name, capacity, capacity * 5))
+        }
+        DataType::Datetime(_, _) => {
+            Buffer::Datetime((PrimitiveChunkedBuilder::new(name, capacity), dtype))
+        }
+        DataType::Date => Buffer::Date(PrimitiveChunkedBuilder::new(name, capacity)),
+        DataType::List(inner) if inner.as_ref() == &DataType::UInt8 => Buffer::Binary(
+            ListPrimitiveChunkedBuilder::new(name, capacity, capacity * 16, DataType::UInt8),
+        ),
+        DataType::List(inner) if inner.as_ref() != &DataType::Null => {
+            // the builder only fails for object dtypes, which are never inferred.
+            let builder = get_list_builder(inner, capacity * 5, capacity, name).unwrap();
+            Buffer::List((builder, inner))
+        }
+        DataType::Struct(fields) if !fields.is_empty() => {
+            let fields = fields
+                .iter()
+                .map(|fld| {
+                    let buf = init_buffer(fld.name(), fld.data_type(), capacity);
+                    (fld.name().clone(), buf)
+                })
+                .collect();
+            Buffer::Struct((fields, name))
+        }
+        _ => Buffer::All((Vec::with_capacity(capacity), name)),
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum Buffer<'a> {
     Boolean(BooleanChunkedBuilder),
@@ -43,8 +65,13 @@ pub(crate) enum Buffer<'a> {
     Float32(PrimitiveChunkedBuilder<Float32Type>),
     Float64(PrimitiveChunkedBuilder<Float64Type>),
     Utf8(Utf8ChunkedBuilder),
-    Datetime(PrimitiveChunkedBuilder<Int64Type>),
+    /// Read as strings and encoded once the buffer is finished.
+    Categorical(Utf8ChunkedBuilder),
+    Datetime((PrimitiveChunkedBuilder<Int64Type>, &'a DataType)),
     Date(PrimitiveChunkedBuilder<Int32Type>),
+    Binary(ListPrimitiveChunkedBuilder<UInt8Type>),
+    List((Box<dyn ListBuilderTrait>, &'a DataType)),
+    Struct((PlIndexMap<String, Buffer<'a>>, &'a str)),
     All((Vec<AnyValue<'a>>, &'a str)),
 }
 
@@ -58,13 +85,22 @@ impl<'a> Buffer<'a> {
             Buffer::UInt64(v) => v.finish().into_series(),
             Buffer::Float32(v) => v.finish().into_series(),
             Buffer::Float64(v) => v.finish().into_series(),
-            Buffer::Datetime(v) => v
-                .finish()
-                .into_series()
-                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
-                .unwrap(),
+            Buffer::Datetime((v, dtype)) => v.finish().into_series().cast(dtype).unwrap(),
             Buffer::Date(v) => v.finish().into_series().cast(&DataType::Date).unwrap(),
             Buffer::Utf8(v) => v.finish().into_series(),
+            Buffer::Categorical(v) => v
+                .finish()
+                .into_series()
+                .cast(&DataType::Categorical(None))?,
+            Buffer::Binary(mut v) => v.finish().into_series(),
+            Buffer::List((mut v, _)) => v.finish().into_series(),
+            Buffer::Struct((fields, name)) => {
+                let fields = fields
+                    .into_values()
+                    .map(|buf| buf.into_series())
+                    .collect::<PolarsResult<Vec<_>>>()?;
+                StructChunked::new(name, &fields)?.into_series()
+            }
             Buffer::All((vals, name)) => Series::new(name, vals),
         };
         Ok(s)
@@ -79,12 +115,62 @@ impl<'a> Buffer<'a> {
             Buffer::UInt64(v) => v.append_null(),
             Buffer::Float32(v) => v.append_null(),
             Buffer::Float64(v) => v.append_null(),
-            Buffer::Utf8(v) => v.append_null(),
-            Buffer::Datetime(v) => v.append_null(),
+            Buffer::Utf8(v) | Buffer::Categorical(v) => v.append_null(),
+            Buffer::Datetime((v, _)) => v.append_null(),
             Buffer::Date(v) => v.append_null(),
+            Buffer::Binary(v) => v.append_null(),
+            Buffer::List((v, _)) => v.append_null(),
+            Buffer::Struct((fields, _)) => fields.values_mut().for_each(|buf| buf.add_null()),
             Buffer::All((v, _)) => v.push(AnyValue::Null),
         };
     }
+    /// Whether `value` can be read into this buffer, instead of being replaced by a null.
+    pub(crate) fn accepts(&self, value: &Bson) -> bool {
+        use Buffer::*;
+        if matches!(value, Bson::Null | Bson::Undefined) {
+            return true;
+        }
+        match self {
+            Boolean(_) => matches!(value, Bson::Boolean(_)),
+            Int32(_) => is_integer::<i32>(value),
+            Int64(_) => is_integer::<i64>(value),
+            UInt32(_) => is_integer::<u32>(value),
+            UInt64(_) => is_integer::<u64>(value),
+            Float32(_) => deserialize_float::<f32>(value).is_some(),
+            Float64(_) => deserialize_float::<f64>(value).is_some(),
+            Utf8(_) | Categorical(_) => deserialize_string(value).is_some(),
+            Datetime((_, dtype)) => deserialize_datetime(value, dtype).is_some(),
+            Date(_) => deserialize_date::<i32>(value).is_some(),
+            Binary(_) => match value {
+                Bson::Binary(_) | Bson::ObjectId(_) => true,
+                Bson::Array(arr) => arr
+                    .iter()
+                    .all(|v| v == &Bson::Null || deserialize_number::<u8>(v).is_some()),
+                _ => false,
+            },
+            List((_, inner)) => match value {
+                Bson::Array(arr) => {
+                    let values = init_buffer("", inner, 0);
+                    arr.iter().all(|v| values.accepts(v))
+                }
+                _ => false,
+            },
+            Struct((fields, _)) => {
+                let doc = match value {
+                    Bson::Document(doc) => Cow::Borrowed(doc),
+                    Bson::ObjectId(oid) => Cow::Owned(object_id_document(oid)),
+                    Bson::Timestamp(ts) => Cow::Owned(timestamp_document(ts)),
+                    Bson::RegularExpression(r) => Cow::Owned(regex_document(r)),
+                    _ => return false,
+                };
+                fields
+                    .iter()
+                    .all(|(name, buf)| doc.get(name).is_none_or(|v| buf.accepts(v)))
+            }
+            All(_) => true,
+        }
+    }
+
     pub(crate) fn add(&mut self, value: &Bson) -> PolarsResult<()> {
         use Buffer::*;
         match self {
@@ -96,7 +182,7 @@ impl<'a> Buffer<'a> {
                 Ok(())
             }
             Int32(buf) => {
-                let n = deserialize_number::<i32>(value);
+                let n = deserialize_integer::<i32>(value);
                 match n {
                     Some(v) => buf.append_value(v),
                     None => buf.append_null(),
@@ -104,7 +190,7 @@ impl<'a> Buffer<'a> {
                 Ok(())
             }
             Int64(buf) => {
-                let n = deserialize_number::<i64>(value);
+                let n = deserialize_integer::<i64>(value);
                 match n {
                     Some(v) => buf.append_value(v),
                     None => buf.append_null(),
@@ -112,7 +198,7 @@ impl<'a> Buffer<'a> {
                 Ok(())
             }
             UInt64(buf) => {
-                let n = deserialize_number::<u64>(value);
+                let n = deserialize_integer::<u64>(value);
                 match n {
                     Some(v) => buf.append_value(v),
                     None => buf.append_null(),
@@ -120,7 +206,7 @@ impl<'a> Buffer<'a> {
                 Ok(())
             }
             UInt32(buf) => {
-                let n = deserialize_number::<u32>(value);
+                let n = deserialize_integer::<u32>(value);
                 match n {
                     Some(v) => buf.append_value(v),
                     None => buf.append_null(),
@@ -144,22 +230,12 @@ impl<'a> Buffer<'a> {
                 Ok(())
             }
 
-            Utf8(buf) => {
-                match value {
-                    Bson::RegularExpression(r) => buf.append_value(r.to_string()),
-                    Bson::ObjectId(oid) => buf.append_value(oid.to_hex()),
-                    Bson::JavaScriptCode(v) => buf.append_value(v),
-                    Bson::String(v) => buf.append_value(v),
-                    Bson::Document(doc) => buf.append_value(doc.to_string()),
-                    Bson::Array(arr) => buf.append_value(format!("{:#?}", arr)),
-                    Bson::Symbol(s) => buf.append_value(s),
-                    _ => buf.append_null(),
-                }
+            Utf8(buf) | Categorical(buf) => {
+                buf.append_option(deserialize_string(value));
                 Ok(())
             }
-            Datetime(buf) => {
-                let v = deserialize_date::<i64>(value);
-                buf.append_option(v);
+            Datetime((buf, dtype)) => {
+                buf.append_option(deserialize_datetime(value, dtype));
                 Ok(())
             }
             Date(buf) => {
@@ -167,6 +243,63 @@ impl<'a> Buffer<'a> {
                 buf.append_option(v);
                 Ok(())
             }
+            Binary(buf) => {
+                match value {
+                    Bson::Binary(b) => buf.append_slice(Some(&b.bytes)),
+                    Bson::ObjectId(oid) => buf.append_slice(Some(&oid.bytes())),
+                    Bson::Array(arr) => buf.append_iter(
+                        arr.iter()
+                            .map(deserialize_number::<u8>)
+                            .collect::<Vec<_>>()
+                            .into_iter(),
+                    ),
+                    _ => buf.append_null(),
+                }
+                Ok(())
+            }
+            List((buf, inner)) => {
+                match value {
+                    Bson::Array(arr) => {
+                        let mut values = init_buffer("", inner, arr.len());
+                        for v in arr {
+                            values.add(v)?;
+                        }
+                        buf.append_series(&values.into_series()?)
+                    }
+                    _ => buf.append_null(),
+                }
+                Ok(())
+            }
+            Struct((fields, _)) => {
+                let value_doc;
+                let value = match value {
+                    Bson::ObjectId(oid) => {
+                        value_doc = Bson::Document(object_id_document(oid));
+                        &value_doc
+                    }
+                    Bson::Timestamp(ts) => {
+                        value_doc = Bson::Document(timestamp_document(ts));
+                        &value_doc
+                    }
+                    Bson::RegularExpression(r) => {
+                        value_doc = Bson::Document(regex_document(r));
+                        &value_doc
+                    }
+                    value => value,
+                };
+                match value {
+                    Bson::Document(doc) => {
+                        for (name, buf) in fields.iter_mut() {
+                            match doc.get(name) {
+                                Some(v) => buf.add(v)?,
+                                None => buf.add_null(),
+                            }
+                        }
+                    }
+                    _ => fields.values_mut().for_each(|buf| buf.add_null()),
+                }
+                Ok(())
+            }
             All((buf, _)) => {
                 let av: Wrap<AnyValue> = value.into();
                 buf.push(av.0);
@@ -175,12 +308,47 @@ impl<'a> Buffer<'a> {
         }
     }
 }
+fn deserialize_string(value: &Bson) -> Option<Cow<'_, str>> {
+    let s = match value {
+        Bson::RegularExpression(r) => Cow::Owned(r.to_string()),
+        Bson::ObjectId(oid) => Cow::Owned(oid.to_hex()),
+        Bson::JavaScriptCode(v) => Cow::Borrowed(v.as_str()),
+        Bson::String(v) => Cow::Borrowed(v.as_str()),
+        Bson::Document(doc) => Cow::Owned(doc.to_string()),
+        Bson::Array(_) => Cow::Owned(value.to_string()),
+        Bson::Symbol(s) => Cow::Borrowed(s.as_str()),
+        Bson::Decimal128(d) => Cow::Owned(decimal128_to_string(d)),
+        // fields with mixed types are read as strings, see `merge_dtypes`.
+        Bson::Int32(v) => Cow::Owned(v.to_string()),
+        Bson::Int64(v) => Cow::Owned(v.to_string()),
+        Bson::Double(v) => Cow::Owned(v.to_string()),
+        Bson::Boolean(v) => Cow::Owned(v.to_string()),
+        Bson::DateTime(v) => Cow::Owned(v.to_string()),
+        Bson::Timestamp(v) => Cow::Owned(format!("{:#?}", v)),
+        Bson::Binary(b) if is_uuid(b) => Cow::Owned(uuid_to_string(&b.bytes)),
+        value if is_fallback(value) => Cow::Owned(fallback_json(value)),
+        _ => return None,
+    };
+    Some(s)
+}
+
+fn deserialize_datetime(value: &Bson, dtype: &DataType) -> Option<i64> {
+    // bson datetimes are stored in milliseconds.
+    let factor = match dtype {
+        DataType::Datetime(TimeUnit::Nanoseconds, _) => 1_000_000,
+        DataType::Datetime(TimeUnit::Microseconds, _) => 1_000,
+        _ => 1,
+    };
+    deserialize_date::<i64>(value).and_then(|v| v.checked_mul(factor))
+}
+
 fn deserialize_float<T: NativeType + NumCast>(value: &Bson) -> Option<T> {
     match value {
         Bson::Double(num) => num::traits::cast::<f64, T>(*num),
         Bson::Int32(num) => num::traits::cast::<i32, T>(*num),
         Bson::Int64(num) => num::traits::cast::<i64, T>(*num),
         Bson::Boolean(b) => num::traits::cast::<i32, T>(*b as i32),
+        Bson::Decimal128(d) => num::traits::cast::<f64, T>(decimal128_to_f64(d)),
         _ => None,
     }
 }
@@ -191,10 +359,31 @@ fn deserialize_number<T: NativeType + NumCast>(value: &Bson) -> Option<T> {
         Bson::Int32(num) => num::traits::cast::<i32, T>(*num),
         Bson::Int64(num) => num::traits::cast::<i64, T>(*num),
         Bson::Boolean(b) => num::traits::cast::<i32, T>(*b as i32),
+        Bson::Decimal128(d) => num::traits::cast::<f64, T>(decimal128_to_f64(d)),
         _ => None,
     }
 }
 
+/// Reads `value` as a `T` if it is an integer in its range, see `is_integer`.
+fn deserialize_integer<T: NativeType + NumCast>(value: &Bson) -> Option<T> {
+    if is_integer::<T>(value) {
+        deserialize_number::<T>(value)
+    } else {
+        None
+    }
+}
+
+/// Whether `value` is an integer in the range of `T`, stored as an integer or a whole double.
+/// Booleans and `Decimal128`s are not, even though they can be read as one.
+fn is_integer<T: NativeType + NumCast>(value: &Bson) -> bool {
+    match value {
+        Bson::Int32(num) => num::traits::cast::<i32, T>(*num).is_some(),
+        Bson::Int64(num) => num::traits::cast::<i64, T>(*num).is_some(),
+        Bson::Double(num) => num.fract() == 0.0 && num::traits::cast::<f64, T>(*num).is_some(),
+        _ => false,
+    }
+}
+
 fn deserialize_date<T: NativeType + NumCast>(value: &Bson) -> Option<T> {
     match value {
         Bson::Double(num) => num::traits::cast::<f64, T>(*num),
@@ -202,6 +391,49 @@ fn deserialize_date<T: NativeType + NumCast>(value: &Bson) -> Option<T> {
         Bson::Int64(num) => num::traits::cast::<i64, T>(*num),
         Bson::Boolean(b) => num::traits::cast::<i32, T>(*b as i32),
         Bson::DateTime(dt) => num::traits::cast::<i64, T>(dt.timestamp_millis()),
+        // bson timestamps are stored in seconds.
+        Bson::Timestamp(ts) => num::traits::cast::<i64, T>(ts.time as i64 * 1000),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(dtype: &DataType, value: Bson) -> (bool, Series) {
+        let mut buf = init_buffer("a", dtype, 1);
+        let accepted = buf.accepts(&value);
+        buf.add(&value).unwrap();
+        (accepted, buf.into_series().unwrap())
+    }
+
+    #[test]
+    fn fractional_double_is_null_in_an_integer_column() {
+        let (accepted, s) = read(&DataType::Int64, Bson::Double(1.5));
+        assert!(!accepted);
+        assert_eq!(s.null_count(), 1);
+
+        let (accepted, s) = read(&DataType::Int64, Bson::Double(2.0));
+        assert!(accepted);
+        assert_eq!(s.i64().unwrap().get(0), Some(2));
+    }
+
+    #[test]
+    fn out_of_range_int64_is_null_in_an_int32_column() {
+        let (accepted, s) = read(&DataType::Int32, Bson::Int64(i64::from(i32::MAX) + 1));
+        assert!(!accepted);
+        assert_eq!(s.null_count(), 1);
+
+        let (accepted, s) = read(&DataType::Int32, Bson::Int64(7));
+        assert!(accepted);
+        assert_eq!(s.i32().unwrap().get(0), Some(7));
+    }
+
+    #[test]
+    fn booleans_are_null_in_an_integer_column() {
+        let (accepted, s) = read(&DataType::Int64, Bson::Boolean(true));
+        assert!(!accepted);
+        assert_eq!(s.null_count(), 1);
+    }
+}
@@ -0,0 +1,43 @@
+//! Stopping scans that are already running.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag to stop a running scan from another thread.
+///
+/// The workers of the scan check the token between documents. Once it is cancelled, they
+/// close their cursors and the scan fails with [`PolarsMongoError::Cancelled`](crate::PolarsMongoError::Cancelled).
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::prelude::*;
+///
+/// # fn main() -> PolarsResult<()> {
+/// let token = CancellationToken::new();
+/// let options = MongoScanOptions {
+///     cancellation: Some(token.clone()),
+///     ..MongoScanOptions::default()
+/// };
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(60));
+///     token.cancel();
+/// });
+/// let df = LazyFrame::scan_mongo_collection(options)?.collect()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop every scan holding a clone of this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
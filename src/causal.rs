@@ -0,0 +1,59 @@
+//! Reading the writes of the same program with causally consistent scans.
+use std::sync::Mutex;
+
+use mongodb::bson::Timestamp;
+use mongodb::options::ClientOptions;
+use once_cell::sync::Lazy;
+use polars::prelude::PlHashMap;
+
+/// Operation time of the latest causally consistent write per cluster.
+static LAST_WRITES: Lazy<Mutex<PlHashMap<String, Timestamp>>> =
+    Lazy::new(|| Mutex::new(PlHashMap::default()));
+
+/// The hosts of a cluster, in the same order whatever order they are configured in.
+pub(crate) fn cluster_key(client_options: &ClientOptions) -> String {
+    let mut hosts: Vec<String> = client_options
+        .hosts
+        .iter()
+        .map(|host| host.to_string())
+        .collect();
+    hosts.sort();
+    hosts.join(",")
+}
+
+/// Record the operation time of a write, unless a later write was already recorded.
+pub(crate) fn record_write(cluster: String, operation_time: Timestamp) {
+    let mut last_writes = LAST_WRITES.lock().unwrap();
+    let last = last_writes.entry(cluster).or_insert(operation_time);
+    if (operation_time.time, operation_time.increment) > (last.time, last.increment) {
+        *last = operation_time;
+    }
+}
+
+/// Operation time of the latest causally consistent write to the cluster, if any.
+pub(crate) fn last_write(cluster: &str) -> Option<Timestamp> {
+    LAST_WRITES.lock().unwrap().get(cluster).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_write_keeps_the_latest() {
+        let cluster = "record_write_keeps_the_latest:27017";
+        assert_eq!(last_write(cluster), None);
+        let first = Timestamp {
+            time: 10,
+            increment: 2,
+        };
+        let later = Timestamp {
+            time: 10,
+            increment: 3,
+        };
+        record_write(cluster.to_string(), first);
+        record_write(cluster.to_string(), later);
+        record_write(cluster.to_string(), first);
+        assert_eq!(last_write(cluster), Some(later));
+    }
+}
@@ -0,0 +1,191 @@
+//! Read the change stream of a collection as a sequence of micro-batch dataframes.
+use std::time::{Duration, Instant};
+
+use polars::prelude::*;
+
+use mongodb::{
+    bson::{Bson, Document},
+    change_stream::event::{ChangeStreamEvent, ResumeToken},
+    options::{ChangeStreamOptions, ClientOptions, FullDocumentType},
+    sync::{ChangeStream, Client, Collection},
+};
+
+use crate::{
+    conversion::ConversionOptions, read_documents, FallbackDtype, ObjectIdAs, RegexAs, TimestampAs,
+    UuidAs,
+};
+
+/// Column holding the kind of change, e.g. `insert`, `update` or `delete`.
+pub const OPERATION_TYPE_COLUMN: &str = "_operation_type";
+
+/// Tails a collection with a change stream and yields the changes as dataframes.
+///
+/// Every row holds the full document after the change, the `_id` of the changed
+/// document and the [`OPERATION_TYPE_COLUMN`]. For deletes only the `_id` is known.
+///
+/// A batch is flushed once `batch_size` changes are collected, or the flush interval
+/// has passed since the first change of the batch. Store [`MongoChangeStreamReader::resume_token`]
+/// after processing a batch to continue from there with [`MongoChangeStreamReader::with_resume_after`].
+pub struct MongoChangeStreamReader {
+    collection: Collection<Document>,
+    stream: Option<ChangeStream<ChangeStreamEvent<Document>>>,
+    resume_after: Option<ResumeToken>,
+    pub schema: Option<Schema>,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    conversion: ConversionOptions,
+}
+
+impl MongoChangeStreamReader {
+    pub fn new(connection_str: String, db: String, collection: String) -> PolarsResult<Self> {
+        let client_options = ClientOptions::parse(connection_str).map_err(|e| {
+            PolarsError::InvalidOperation(format!("unable to connect to mongodb: {}", e).into())
+        })?;
+        let client = Client::with_options(client_options)
+            .map_err(|e| PolarsError::ComputeError(format!("{:#?}", e).into()))?;
+
+        Ok(MongoChangeStreamReader {
+            collection: client.database(&db).collection(&collection),
+            stream: None,
+            resume_after: None,
+            schema: None,
+            batch_size: 1000,
+            flush_interval: Duration::from_secs(1),
+            conversion: ConversionOptions::default(),
+        })
+    }
+
+    /// Maximum number of changes in a single batch. Defaults to `1000`.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Maximum time to wait for more changes once a batch has started. Defaults to one second.
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Use `schema` for every batch, instead of inferring it from the changes in the batch.
+    pub fn with_schema(mut self, schema: Option<Schema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Continue the change stream after the change identified by `resume_token`.
+    pub fn with_resume_after(mut self, resume_token: Option<ResumeToken>) -> Self {
+        self.resume_after = resume_token;
+        self
+    }
+
+    pub fn with_decimal128_as_float(mut self, decimal128_as_float: bool) -> Self {
+        self.conversion.decimal128_as_float = decimal128_as_float;
+        self
+    }
+
+    pub fn with_object_id_as(mut self, object_id_as: ObjectIdAs) -> Self {
+        self.conversion.object_id_as = object_id_as;
+        self
+    }
+
+    pub fn with_timestamp_as(mut self, timestamp_as: TimestampAs) -> Self {
+        self.conversion.timestamp_as = timestamp_as;
+        self
+    }
+
+    pub fn with_regex_as(mut self, regex_as: RegexAs) -> Self {
+        self.conversion.regex_as = regex_as;
+        self
+    }
+
+    pub fn with_uuid_as(mut self, uuid_as: UuidAs) -> Self {
+        self.conversion.uuid_as = uuid_as;
+        self
+    }
+
+    pub fn with_fallback_dtype(mut self, fallback_dtype: FallbackDtype) -> Self {
+        self.conversion.fallback_dtype = fallback_dtype;
+        self
+    }
+
+    /// Token to resume the change stream after the last change that was returned.
+    pub fn resume_token(&self) -> Option<ResumeToken> {
+        match &self.stream {
+            Some(stream) => stream.resume_token(),
+            None => self.resume_after.clone(),
+        }
+    }
+
+    fn stream(&mut self) -> PolarsResult<&mut ChangeStream<ChangeStreamEvent<Document>>> {
+        if self.stream.is_none() {
+            let options = ChangeStreamOptions::builder()
+                .full_document(Some(FullDocumentType::UpdateLookup))
+                .resume_after(self.resume_after.clone())
+                .max_await_time(Some(self.flush_interval))
+                .build();
+            let stream = self
+                .collection
+                .watch(None, options)
+                .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+            self.stream = Some(stream);
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    /// Block until at least one change is available and return the next batch of changes.
+    ///
+    /// Returns `None` once the change stream is closed, e.g. because the collection was dropped.
+    pub fn next_batch(&mut self) -> PolarsResult<Option<DataFrame>> {
+        let batch_size = self.batch_size;
+        let flush_interval = self.flush_interval;
+        let stream = self.stream()?;
+
+        let mut docs = Vec::with_capacity(batch_size);
+        let mut started: Option<Instant> = None;
+        while stream.is_alive() && docs.len() < batch_size {
+            if started.is_some_and(|t| t.elapsed() >= flush_interval) {
+                break;
+            }
+            let event = stream
+                .next_if_any()
+                .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+            if let Some(event) = event {
+                docs.push(event_to_document(event));
+                started.get_or_insert_with(Instant::now);
+            }
+        }
+        if docs.is_empty() {
+            return Ok(None);
+        }
+
+        let n_docs = docs.len();
+        let mut docs = docs.into_iter();
+        read_documents(
+            || Ok(docs.next()),
+            self.schema.clone(),
+            Some(n_docs),
+            None,
+            &self.conversion,
+        )
+        .map(Some)
+    }
+}
+
+impl Iterator for MongoChangeStreamReader {
+    type Item = PolarsResult<DataFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
+    }
+}
+
+fn event_to_document(event: ChangeStreamEvent<Document>) -> Document {
+    let mut doc = event.full_document.unwrap_or_default();
+    if let Some(id) = event.document_key.and_then(|mut key| key.remove("_id")) {
+        doc.insert("_id", id);
+    }
+    let operation_type = mongodb::bson::to_bson(&event.operation_type).unwrap_or(Bson::Null);
+    doc.insert(OPERATION_TYPE_COLUMN, operation_type);
+    doc
+}
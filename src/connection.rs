@@ -0,0 +1,267 @@
+//! Typed connection settings, as an alternative to a connection string.
+use std::{fmt, path::PathBuf, time::Duration};
+
+use polars::prelude::*;
+
+#[cfg(feature = "aws-auth")]
+use mongodb::bson::doc;
+use mongodb::bson::Document;
+#[cfg(any(feature = "zstd", feature = "snappy", feature = "zlib"))]
+use mongodb::options::Compressor;
+use mongodb::options::{AuthMechanism, ClientOptions, Credential, ServerAddress, Tls, TlsOptions};
+
+/// Compression of the messages between the client and the servers.
+///
+/// Every compressor needs the crate feature of the same name, and the server has to support
+/// it too. Compression trades CPU for bandwidth, which pays off for scans over slow networks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Best ratio. The level ranges from `1` to `22`, and defaults to `3`.
+    Zstd { level: Option<i32> },
+    /// Fastest, with the lowest ratio.
+    Snappy,
+    /// The level ranges from `0` to `9`, and defaults to `6`.
+    Zlib { level: Option<u32> },
+}
+
+impl Compression {
+    #[cfg(any(feature = "zstd", feature = "snappy", feature = "zlib"))]
+    fn compressor(self) -> PolarsResult<Compressor> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level } => Ok(Compressor::Zstd { level }),
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => Ok(Compressor::Snappy),
+            #[cfg(feature = "zlib")]
+            Compression::Zlib { level } => Ok(Compressor::Zlib { level }),
+            #[allow(unreachable_patterns)]
+            compression => Err(compression.disabled()),
+        }
+    }
+
+    fn disabled(self) -> PolarsError {
+        let feature = match self {
+            Compression::Zstd { .. } => "zstd",
+            Compression::Snappy => "snappy",
+            Compression::Zlib { .. } => "zlib",
+        };
+        PolarsError::InvalidOperation(
+            format!("{} compression needs the `{}` feature", feature, feature).into(),
+        )
+    }
+}
+
+/// Settings to connect to mongodb, without having to encode them into a connection string.
+///
+/// This keeps secrets out of the connection string, and supports client certificate
+/// authentication with `MONGODB-X509` and, with the `aws-auth` feature, IAM authentication
+/// with `MONGODB-AWS`.
+#[derive(Clone, Default)]
+pub struct MongoConnection {
+    /// `host` or `host:port` of the servers to connect to.
+    pub hosts: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Database the user is defined in. Defaults to `admin`.
+    pub auth_source: Option<String>,
+    pub auth_mechanism: Option<AuthMechanism>,
+    /// Session token of temporary AWS credentials, passed as `username` and `password`.
+    #[cfg(feature = "aws-auth")]
+    pub aws_session_token: Option<String>,
+    pub tls: bool,
+    /// Certificate authority used to verify the server certificate.
+    pub tls_ca_file: Option<PathBuf>,
+    /// File containing the client certificate and private key.
+    pub tls_cert_key_file: Option<PathBuf>,
+    pub tls_allow_invalid_certificates: bool,
+    /// Name of the application, shown in the server logs.
+    pub app_name: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    /// Compressors to negotiate with the server, in order of preference.
+    pub compressors: Vec<Compression>,
+}
+
+impl fmt::Debug for MongoConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("MongoConnection");
+        debug
+            .field("hosts", &self.hosts)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("auth_source", &self.auth_source)
+            .field("auth_mechanism", &self.auth_mechanism);
+        #[cfg(feature = "aws-auth")]
+        debug.field(
+            "aws_session_token",
+            &self.aws_session_token.as_ref().map(|_| "***"),
+        );
+        debug
+            .field("tls", &self.tls)
+            .field("tls_ca_file", &self.tls_ca_file)
+            .field("tls_cert_key_file", &self.tls_cert_key_file)
+            .field(
+                "tls_allow_invalid_certificates",
+                &self.tls_allow_invalid_certificates,
+            )
+            .field("app_name", &self.app_name)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("compressors", &self.compressors)
+            .finish()
+    }
+}
+
+impl MongoConnection {
+    pub fn new<S: Into<String>>(hosts: impl IntoIterator<Item = S>) -> Self {
+        MongoConnection {
+            hosts: hosts.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_credentials(mut self, username: String, password: Option<String>) -> Self {
+        self.username = Some(username);
+        self.password = password;
+        self
+    }
+
+    pub fn with_auth_source(mut self, auth_source: Option<String>) -> Self {
+        self.auth_source = auth_source;
+        self
+    }
+
+    /// Authentication mechanism, negotiated with the server if not set.
+    ///
+    /// `GSSAPI` (Kerberos) is not implemented by the mongodb driver this crate is built on,
+    /// so connecting with it fails. Enterprise deployments can use `PLAIN` (LDAP) or
+    /// `MONGODB-X509` with a client certificate instead.
+    pub fn with_auth_mechanism(mut self, auth_mechanism: Option<AuthMechanism>) -> Self {
+        self.auth_mechanism = auth_mechanism;
+        self
+    }
+
+    /// Authenticate with an AWS IAM identity using `MONGODB-AWS`, against the `$external` database.
+    ///
+    /// Without credentials, the driver takes them from the `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN` environment variables, as set on Lambda,
+    /// or from the role of the ECS task or EC2 instance. Otherwise pass the access key id and
+    /// secret access key with [`with_credentials`](Self::with_credentials), and the session token
+    /// of temporary credentials here.
+    #[cfg(feature = "aws-auth")]
+    pub fn with_aws_auth(mut self, session_token: Option<String>) -> Self {
+        self.auth_mechanism = Some(AuthMechanism::MongoDbAws);
+        self.auth_source = Some("$external".to_string());
+        self.aws_session_token = session_token;
+        self
+    }
+
+    /// Connect over TLS. Implied by setting any of the TLS files.
+    pub fn with_tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn with_tls_ca_file(mut self, path: Option<PathBuf>) -> Self {
+        self.tls_ca_file = path;
+        self
+    }
+
+    pub fn with_tls_cert_key_file(mut self, path: Option<PathBuf>) -> Self {
+        self.tls_cert_key_file = path;
+        self
+    }
+
+    pub fn with_tls_allow_invalid_certificates(mut self, allow: bool) -> Self {
+        self.tls_allow_invalid_certificates = allow;
+        self
+    }
+
+    pub fn with_app_name(mut self, app_name: Option<String>) -> Self {
+        self.app_name = app_name;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Option<Duration>) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Compress the messages with the first of `compressors` the server supports.
+    /// Connection strings enable compression with the `compressors` option instead.
+    pub fn with_compressors(mut self, compressors: Vec<Compression>) -> Self {
+        self.compressors = compressors;
+        self
+    }
+
+    #[cfg(feature = "aws-auth")]
+    fn mechanism_properties(&self) -> Option<Document> {
+        let token = self.aws_session_token.as_ref()?;
+        Some(doc! {"AWS_SESSION_TOKEN": token})
+    }
+
+    #[cfg(not(feature = "aws-auth"))]
+    fn mechanism_properties(&self) -> Option<Document> {
+        None
+    }
+
+    fn uses_tls(&self) -> bool {
+        self.tls || self.tls_ca_file.is_some() || self.tls_cert_key_file.is_some()
+    }
+
+    /// Build the mongodb driver options for these settings.
+    pub fn client_options(&self) -> PolarsResult<ClientOptions> {
+        let hosts = self
+            .hosts
+            .iter()
+            .map(ServerAddress::parse)
+            .collect::<mongodb::error::Result<Vec<_>>>()
+            .map_err(|e| {
+                PolarsError::InvalidOperation(format!("unable to connect to mongodb: {}", e).into())
+            })?;
+
+        let credential = match (&self.username, &self.auth_mechanism) {
+            (None, None) => None,
+            _ => Some(
+                Credential::builder()
+                    .username(self.username.clone())
+                    .password(self.password.clone())
+                    .source(self.auth_source.clone())
+                    .mechanism(self.auth_mechanism.clone())
+                    .mechanism_properties(self.mechanism_properties())
+                    .build(),
+            ),
+        };
+
+        let tls = self.uses_tls().then(|| {
+            Tls::Enabled(
+                TlsOptions::builder()
+                    .ca_file_path(self.tls_ca_file.clone())
+                    .cert_key_file_path(self.tls_cert_key_file.clone())
+                    .allow_invalid_certificates(Some(self.tls_allow_invalid_certificates))
+                    .build(),
+            )
+        });
+
+        #[allow(unused_mut)]
+        let mut options = ClientOptions::builder()
+            .hosts(hosts)
+            .credential(credential)
+            .tls(tls)
+            .app_name(self.app_name.clone())
+            .connect_timeout(self.connect_timeout)
+            .build();
+        #[cfg(any(feature = "zstd", feature = "snappy", feature = "zlib"))]
+        if !self.compressors.is_empty() {
+            let compressors = self
+                .compressors
+                .iter()
+                .map(|compression| compression.compressor())
+                .collect::<PolarsResult<_>>()?;
+            options.compressors = Some(compressors);
+        }
+        #[cfg(not(any(feature = "zstd", feature = "snappy", feature = "zlib")))]
+        if let Some(compression) = self.compressors.first() {
+            return Err(compression.disabled());
+        }
+        Ok(options)
+    }
+}
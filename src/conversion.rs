@@ -1,6 +1,16 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
 use polars::prelude::*;
 
-use mongodb::bson::{Bson, Document};
+use mongodb::bson::{
+    doc, oid::ObjectId, spec::BinarySubtype, Binary, Bson, DateTime, Decimal128, Document, Regex,
+    Timestamp,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::PolarsMongoError;
 
 #[derive(Debug)]
 #[repr(transparent)]
@@ -20,55 +30,521 @@ impl<T> From<T> for Wrap<T> {
     }
 }
 
-impl From<&Document> for Wrap<DataType> {
-    fn from(doc: &Document) -> Self {
-        let fields = doc.iter().map(|(key, value)| {
-            let dtype: Wrap<DataType> = value.into();
-            Field::new(key, dtype.0)
-        });
-        DataType::Struct(fields.collect()).into()
+/// How `ObjectId` values are represented in the dataframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ObjectIdAs {
+    /// 24 character hex string.
+    #[default]
+    Hex,
+    /// The raw 12 bytes as a `List(UInt8)`, polars does not have a binary dtype.
+    Binary,
+    /// A struct with the embedded creation `timestamp` as a `Datetime`
+    /// and the `counter` as a `UInt32`.
+    Struct,
+}
+
+/// How bson `Timestamp` values, mostly found in oplog and change stream documents,
+/// are represented in the dataframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TimestampAs {
+    /// Debug formatted string of the time and increment.
+    #[default]
+    String,
+    /// A struct with the seconds since the epoch as a `Datetime` `time`
+    /// and the ordinal within that second as a `UInt32` `increment`.
+    Struct,
+    /// The seconds since the epoch as a `Datetime`, dropping the increment.
+    Datetime,
+}
+
+/// How bson regular expressions are represented in the dataframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RegexAs {
+    /// `/pattern/options` string.
+    #[default]
+    String,
+    /// A struct with the `pattern` and the `options` flags as `Utf8` fields.
+    Struct,
+}
+
+/// How `Binary` values of the UUID subtypes (3 and 4) are represented in the dataframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum UuidAs {
+    /// The raw 16 bytes as a `List(UInt8)`, like any other binary value.
+    #[default]
+    Binary,
+    /// Canonical hyphenated hex string, e.g. `550e8400-e29b-41d4-a716-446655440000`.
+    /// The bytes of legacy (subtype 3) UUIDs are formatted in the order they are stored in.
+    String,
+}
+
+/// How bson types without a polars counterpart (`MinKey`, `MaxKey`, `DBPointer` and
+/// `JavaScriptCodeWithScope`) are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FallbackDtype {
+    /// Relaxed extended JSON string, e.g. `{"$maxKey":1}`.
+    #[default]
+    Utf8Json,
+    /// Debug formatted string of the bson value, as produced by the generic `AnyValue`
+    /// conversion of the connector before this option existed.
+    AnyValue,
+    /// Fail the read, naming the field and the bson type of the value.
+    Error,
+}
+
+/// How the documents are serialized into the `_raw` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RawFormat {
+    /// Relaxed extended JSON `Utf8` string.
+    #[default]
+    ExtendedJson,
+    /// The bson bytes of the document as a `List(UInt8)`.
+    Bson,
+}
+
+/// Name of the column holding the serialized documents.
+pub(crate) const RAW_COLUMN: &str = "_raw";
+
+/// What to do with values that don't match the dtype of their column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParseMode {
+    /// Return an error naming the field, the bson type and the `_id` of the document.
+    Strict,
+    /// Skip the documents containing such a value, or that can't be deserialized at all.
+    SkipDocument,
+    /// Read the value as a null.
+    #[default]
+    Permissive,
+}
+
+/// Counts of the values that did not match the dtype of their column during a scan.
+#[derive(Debug, Clone, Default)]
+pub struct ParseReport {
+    /// Number of documents left out with [`ParseMode::SkipDocument`].
+    pub skipped_documents: usize,
+    /// Number of values per field that did not match the dtype of the column.
+    pub invalid_values: PlHashMap<String, usize>,
+}
+
+impl ParseReport {
+    fn merge(&mut self, other: ParseReport) {
+        self.skipped_documents += other.skipped_documents;
+        for (field, count) in other.invalid_values {
+            *self.invalid_values.entry(field).or_default() += count;
+        }
     }
 }
 
-impl From<&Bson> for Wrap<DataType> {
-    fn from(bson: &Bson) -> Self {
-        let dt = match bson {
-            Bson::Double(_) => DataType::Float64,
-            Bson::String(_) => DataType::Utf8,
+/// Settings that control how bson values are mapped onto polars dtypes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConversionOptions {
+    /// read `Decimal128` values as a lossy `Float64` instead of an exact decimal string.
+    pub(crate) decimal128_as_float: bool,
+    pub(crate) object_id_as: ObjectIdAs,
+    pub(crate) timestamp_as: TimestampAs,
+    pub(crate) regex_as: RegexAs,
+    pub(crate) uuid_as: UuidAs,
+    pub(crate) fallback_dtype: FallbackDtype,
+    /// add the serialized documents as a `_raw` column.
+    pub(crate) include_raw: bool,
+    pub(crate) raw_format: RawFormat,
+    /// time unit of `Datetime` columns, `None` keeps the millisecond precision of bson.
+    pub(crate) time_unit: Option<TimeUnit>,
+    pub(crate) parse_mode: ParseMode,
+    /// shared report that the counts of every scanned part are added to.
+    pub(crate) parse_report: Option<Arc<Mutex<ParseReport>>>,
+    /// separator of the paths that nested documents are flattened into, `None` keeps them as structs.
+    pub(crate) flatten: Option<String>,
+}
+
+impl ConversionOptions {
+    /// Whether every value has to be checked against the dtype of its column.
+    pub(crate) fn validate(&self) -> bool {
+        self.parse_mode != ParseMode::Permissive || self.parse_report.is_some()
+    }
+
+    /// Add the counts of a finished part of the scan to the shared report.
+    pub(crate) fn report(&self, report: ParseReport) {
+        if let Some(shared) = &self.parse_report {
+            shared.lock().unwrap().merge(report);
+        }
+    }
+
+    /// The document with its nested documents flattened into top-level fields, if enabled.
+    pub(crate) fn flatten<'a>(&self, doc: &'a Document) -> Cow<'a, Document> {
+        match &self.flatten {
+            Some(separator) => {
+                let mut flat = Document::new();
+                flatten_into(None, doc, separator, &mut flat);
+                Cow::Owned(flat)
+            }
+            None => Cow::Borrowed(doc),
+        }
+    }
+
+    pub(crate) fn raw_dtype(&self) -> DataType {
+        match self.raw_format {
+            RawFormat::ExtendedJson => DataType::Utf8,
+            RawFormat::Bson => DataType::List(Box::new(DataType::UInt8)),
+        }
+    }
+
+    /// The value of the `_raw` column for `doc`.
+    pub(crate) fn raw_value(&self, doc: &Document) -> PolarsResult<Bson> {
+        match self.raw_format {
+            RawFormat::ExtendedJson => Ok(Bson::String(
+                Bson::Document(doc.clone())
+                    .into_relaxed_extjson()
+                    .to_string(),
+            )),
+            RawFormat::Bson => {
+                let mut bytes = Vec::new();
+                doc.to_writer(&mut bytes)
+                    .map_err(|err| PolarsMongoError::Conversion(format!("{:#?}", err)))?;
+                Ok(Bson::Binary(Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes,
+                }))
+            }
+        }
+    }
 
+    /// Replace the values without a polars counterpart in `doc` according to `fallback_dtype`.
+    ///
+    /// Extended JSON is the default and produced by the buffers themselves, so the document
+    /// is only rewritten for the other policies and if it contains such a value.
+    pub(crate) fn resolve_fallbacks<'a>(
+        &self,
+        doc: Cow<'a, Document>,
+    ) -> PolarsResult<Cow<'a, Document>> {
+        if self.fallback_dtype == FallbackDtype::Utf8Json || !doc.values().any(contains_fallback) {
+            return Ok(doc);
+        }
+        let mut doc = doc.into_owned();
+        for (name, value) in doc.iter_mut() {
+            self.resolve_fallback(name, value)?;
+        }
+        Ok(Cow::Owned(doc))
+    }
+
+    fn resolve_fallback(&self, path: &str, value: &mut Bson) -> PolarsResult<()> {
+        match value {
+            Bson::Document(doc) => {
+                for (name, value) in doc.iter_mut() {
+                    self.resolve_fallback(&format!("{}.{}", path, name), value)?;
+                }
+            }
             Bson::Array(arr) => {
-                use polars::frame::row::coerce_data_type;
+                for value in arr.iter_mut() {
+                    self.resolve_fallback(path, value)?;
+                }
+            }
+            value if is_fallback(value) => match self.fallback_dtype {
+                FallbackDtype::Utf8Json => *value = Bson::String(fallback_json(value)),
+                FallbackDtype::AnyValue => *value = Bson::String(format!("{:#?}", value)),
+                FallbackDtype::Error => {
+                    return Err(PolarsMongoError::Conversion(format!(
+                        "field '{}' has a {:?} value, which has no polars dtype",
+                        path,
+                        value.element_type()
+                    ))
+                    .into())
+                }
+            },
+            _ => {}
+        }
+        Ok(())
+    }
 
-                let dtypes: Vec<_> = arr
-                    .iter()
-                    .map(|doc| {
-                        let dt: Self = doc.into();
-                        dt.0
+    /// Fields to project to read the column `name` of a flattened document.
+    ///
+    /// With a separator other than `.` it is unknown which separators were dots, so every
+    /// top-level field the column could be read from is projected.
+    #[cfg(any(feature = "sync", feature = "async"))]
+    pub(crate) fn flattened_paths(&self, name: &str) -> Vec<String> {
+        match self.flatten.as_deref() {
+            Some(separator) if separator != "." && !separator.is_empty() => {
+                let mut prefix = String::new();
+                name.split(separator)
+                    .map(|part| {
+                        if !prefix.is_empty() {
+                            prefix.push_str(separator);
+                        }
+                        prefix.push_str(part);
+                        prefix.clone()
                     })
-                    .collect();
-                let dtype = if dtypes.is_empty() {
-                    DataType::Null
-                } else {
-                    coerce_data_type(&dtypes)
-                };
+                    .collect()
+            }
+            _ => vec![name.to_string()],
+        }
+    }
+
+    /// Whether predicates on the columns can be evaluated as mongodb filters.
+    pub(crate) fn filterable(&self) -> bool {
+        matches!(self.flatten.as_deref(), None | Some("."))
+    }
+
+    pub(crate) fn document_dtype(&self, doc: &Document) -> DataType {
+        let fields = doc
+            .iter()
+            .map(|(key, value)| Field::new(key, self.dtype(value)));
+        DataType::Struct(fields.collect())
+    }
+
+    pub(crate) fn datetime_dtype(&self) -> DataType {
+        DataType::Datetime(self.time_unit.unwrap_or(TimeUnit::Milliseconds), None)
+    }
+
+    pub(crate) fn object_id_dtype(&self) -> DataType {
+        match self.object_id_as {
+            ObjectIdAs::Hex => DataType::Utf8,
+            ObjectIdAs::Binary => DataType::List(Box::new(DataType::UInt8)),
+            ObjectIdAs::Struct => DataType::Struct(vec![
+                Field::new("timestamp", self.datetime_dtype()),
+                Field::new("counter", DataType::UInt32),
+            ]),
+        }
+    }
+
+    pub(crate) fn timestamp_dtype(&self) -> DataType {
+        match self.timestamp_as {
+            TimestampAs::String => DataType::Utf8,
+            TimestampAs::Struct => DataType::Struct(vec![
+                Field::new("time", self.datetime_dtype()),
+                Field::new("increment", DataType::UInt32),
+            ]),
+            TimestampAs::Datetime => self.datetime_dtype(),
+        }
+    }
+
+    pub(crate) fn regex_dtype(&self) -> DataType {
+        match self.regex_as {
+            RegexAs::String => DataType::Utf8,
+            RegexAs::Struct => DataType::Struct(vec![
+                Field::new("pattern", DataType::Utf8),
+                Field::new("options", DataType::Utf8),
+            ]),
+        }
+    }
+
+    /// Whether `dtype` is a struct that is read from a single bson value, not a document.
+    pub(crate) fn is_value_struct(&self, dtype: &DataType) -> bool {
+        dtype == &self.object_id_dtype()
+            || dtype == &self.timestamp_dtype()
+            || dtype == &self.regex_dtype()
+    }
+
+    pub(crate) fn dtype(&self, bson: &Bson) -> DataType {
+        match bson {
+            Bson::Double(_) => DataType::Float64,
+            Bson::String(_) => DataType::Utf8,
+
+            Bson::Array(arr) => {
+                let dtype = arr.iter().fold(DataType::Null, |acc, value| {
+                    merge_dtypes(&acc, &self.dtype(value))
+                });
                 DataType::List(Box::new(dtype))
             }
             Bson::Boolean(_) => DataType::Boolean,
             Bson::Null => DataType::Null,
             Bson::Int32(_) => DataType::Int32,
             Bson::Int64(_) => DataType::Int64,
-            Bson::Timestamp(_) => DataType::Utf8,
-            Bson::Document(doc) => return doc.into(),
-            Bson::DateTime(_) => DataType::Datetime(TimeUnit::Milliseconds, None),
-            Bson::ObjectId(_) => DataType::Utf8,
+            Bson::Timestamp(_) => self.timestamp_dtype(),
+            Bson::Document(doc) => self.document_dtype(doc),
+            Bson::DateTime(_) => self.datetime_dtype(),
+            Bson::ObjectId(_) => self.object_id_dtype(),
+            Bson::RegularExpression(_) => self.regex_dtype(),
             Bson::Symbol(_) => DataType::Utf8,
+            // polars has no binary dtype, so the raw bytes are read as a list of bytes.
+            Bson::Binary(b) if self.uuid_as == UuidAs::String && is_uuid(b) => DataType::Utf8,
+            Bson::Binary(_) => DataType::List(Box::new(DataType::UInt8)),
+            Bson::Decimal128(_) if self.decimal128_as_float => DataType::Float64,
             Bson::Undefined => DataType::Unknown,
+            // read as strings, or rejected when read, see `FallbackDtype`.
             _ => DataType::Utf8,
+        }
+    }
+}
+
+/// Whether `value` is of a bson type without a polars counterpart.
+pub(crate) fn is_fallback(value: &Bson) -> bool {
+    matches!(
+        value,
+        Bson::MinKey | Bson::MaxKey | Bson::DbPointer(_) | Bson::JavaScriptCodeWithScope(_)
+    )
+}
+
+fn contains_fallback(value: &Bson) -> bool {
+    match value {
+        Bson::Document(doc) => doc.values().any(contains_fallback),
+        Bson::Array(arr) => arr.iter().any(contains_fallback),
+        value => is_fallback(value),
+    }
+}
+
+/// Relaxed extended JSON of a value without a polars counterpart.
+pub(crate) fn fallback_json(value: &Bson) -> String {
+    value.clone().into_relaxed_extjson().to_string()
+}
+
+/// Add the fields of `doc` to `flat`, with the fields of nested documents named by their path.
+fn flatten_into(prefix: Option<&str>, doc: &Document, separator: &str, flat: &mut Document) {
+    for (key, value) in doc {
+        let path = match prefix {
+            Some(prefix) => format!("{}{}{}", prefix, separator, key),
+            None => key.clone(),
         };
-        Wrap(dt)
+        match value {
+            Bson::Document(nested) if !nested.is_empty() => {
+                flatten_into(Some(&path), nested, separator, flat)
+            }
+            _ => {
+                flat.insert(path, value.clone());
+            }
+        }
+    }
+}
+
+impl From<&Document> for Wrap<DataType> {
+    fn from(doc: &Document) -> Self {
+        Wrap(ConversionOptions::default().document_dtype(doc))
+    }
+}
+
+impl From<&Bson> for Wrap<DataType> {
+    fn from(bson: &Bson) -> Self {
+        Wrap(ConversionOptions::default().dtype(bson))
     }
 }
 
+/// Format a `Decimal128` as an exact decimal string, following the
+/// [decimal128 spec](https://github.com/mongodb/specifications/blob/master/source/bson-decimal128/decimal128.rst#to-string-representation).
+pub(crate) fn decimal128_to_string(dec: &Decimal128) -> String {
+    const EXPONENT_BIAS: i64 = 6176;
+    const MAX_COEFFICIENT: u128 = 9_999_999_999_999_999_999_999_999_999_999_999;
+
+    let bits = u128::from_le_bytes(dec.bytes());
+    let sign = if bits >> 127 == 1 { "-" } else { "" };
+    let combination = (bits >> 122) & 0x1f;
+
+    if combination == 0x1f {
+        return "NaN".to_string();
+    }
+    if combination == 0x1e {
+        return format!("{}Infinity", sign);
+    }
+
+    let (exponent, coefficient) = if (bits >> 125) & 0b11 == 0b11 {
+        // this form can only represent coefficients larger than the maximum, which are read as 0.
+        ((bits >> 111) & 0x3fff, 0)
+    } else {
+        ((bits >> 113) & 0x3fff, bits & ((1 << 113) - 1))
+    };
+    let exponent = exponent as i64 - EXPONENT_BIAS;
+    let coefficient = if coefficient > MAX_COEFFICIENT {
+        0
+    } else {
+        coefficient
+    };
+
+    let digits = coefficient.to_string();
+    let adjusted = exponent + digits.len() as i64 - 1;
+
+    if exponent <= 0 && adjusted >= -6 {
+        if exponent == 0 {
+            return format!("{}{}", sign, digits);
+        }
+        let point = digits.len() as i64 + exponent;
+        if point > 0 {
+            let (int, frac) = digits.split_at(point as usize);
+            format!("{}{}.{}", sign, int, frac)
+        } else {
+            format!("{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+        }
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        format!("{}{}{}{}E{:+}", sign, first, point, rest, adjusted)
+    }
+}
+
+/// Parse a decimal string, e.g. the value of an extended JSON `$numberDecimal`, into a `Decimal128`.
+///
+/// Returns `None` for malformed strings, and for values that can't be represented
+/// exactly, as rounding is not supported.
+pub(crate) fn parse_decimal128(s: &str) -> Option<Decimal128> {
+    const EXPONENT_BIAS: i64 = 6176;
+    const MAX_EXPONENT: i64 = 6111;
+    const MAX_DIGITS: usize = 34;
+
+    let s = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let sign = (negative as u128) << 127;
+    match s.to_ascii_lowercase().as_str() {
+        "inf" | "infinity" => {
+            return Some(Decimal128::from_bytes((sign | 0x1e << 122).to_le_bytes()))
+        }
+        "nan" => return Some(Decimal128::from_bytes((0x1f_u128 << 122).to_le_bytes())),
+        _ => {}
+    }
+
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(idx) => (&s[..idx], s[idx + 1..].parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+    if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut digits = format!("{}{}", int, frac)
+        .trim_start_matches('0')
+        .to_string();
+    let mut exponent = exponent - frac.len() as i64;
+    // drop trailing zeros that don't fit, or that push the exponent below its minimum.
+    while (digits.len() > MAX_DIGITS || exponent < -EXPONENT_BIAS) && digits.ends_with('0') {
+        digits.pop();
+        exponent += 1;
+    }
+    // a too large exponent is folded into the coefficient, if there is room.
+    while exponent > MAX_EXPONENT && !digits.is_empty() && digits.len() < MAX_DIGITS {
+        digits.push('0');
+        exponent -= 1;
+    }
+    if digits.is_empty() {
+        exponent = exponent.clamp(-EXPONENT_BIAS, MAX_EXPONENT);
+    }
+    if digits.len() > MAX_DIGITS || !(-EXPONENT_BIAS..=MAX_EXPONENT).contains(&exponent) {
+        return None;
+    }
+
+    let coefficient = if digits.is_empty() {
+        0
+    } else {
+        digits.parse::<u128>().ok()?
+    };
+    let bits = sign | ((exponent + EXPONENT_BIAS) as u128) << 113 | coefficient;
+    Some(Decimal128::from_bytes(bits.to_le_bytes()))
+}
+
+/// Lossy conversion of a `Decimal128` to a float.
+pub(crate) fn decimal128_to_f64(dec: &Decimal128) -> f64 {
+    decimal128_to_string(dec).parse().unwrap_or(f64::NAN)
+}
+
 impl<'a> From<Bson> for Wrap<AnyValue<'a>> {
     fn from(bson: Bson) -> Self {
         let dt = match bson {
@@ -77,7 +553,8 @@ impl<'a> From<Bson> for Wrap<AnyValue<'a>> {
             Bson::Array(arr) => {
                 let vals: Vec<Wrap<AnyValue>> = arr.iter().map(|v| v.into()).collect();
                 // Wrap is transparent, so this is safe
-                let vals = unsafe { std::mem::transmute::<_, Vec<AnyValue>>(vals) };
+                let vals =
+                    unsafe { std::mem::transmute::<Vec<Wrap<AnyValue>>, Vec<AnyValue>>(vals) };
                 let s = Series::new("", vals);
                 AnyValue::List(s)
             }
@@ -95,6 +572,8 @@ impl<'a> From<Bson> for Wrap<AnyValue<'a>> {
             }
             Bson::ObjectId(oid) => AnyValue::Utf8Owned(oid.to_string()),
             Bson::Symbol(s) => AnyValue::Utf8Owned(s),
+            Bson::Decimal128(d) => AnyValue::Utf8Owned(decimal128_to_string(&d)),
+            v if is_fallback(&v) => AnyValue::Utf8Owned(fallback_json(&v)),
             v => AnyValue::Utf8Owned(format!("{:#?}", v)),
         };
         Wrap(dt)
@@ -109,7 +588,8 @@ impl<'a, 'b> From<&'b Bson> for Wrap<AnyValue<'a>> {
             Bson::Array(arr) => {
                 let vals: Vec<Wrap<AnyValue>> = arr.iter().map(|v| v.into()).collect();
                 // Wrap is transparent, so this is safe
-                let vals = unsafe { std::mem::transmute::<_, Vec<AnyValue>>(vals) };
+                let vals =
+                    unsafe { std::mem::transmute::<Vec<Wrap<AnyValue>>, Vec<AnyValue>>(vals) };
                 let s = Series::new("", vals);
                 AnyValue::List(s)
             }
@@ -140,8 +620,219 @@ impl<'a, 'b> From<&'b Bson> for Wrap<AnyValue<'a>> {
             }
             Bson::ObjectId(oid) => AnyValue::Utf8Owned(oid.to_string()),
             Bson::Symbol(s) => AnyValue::Utf8Owned(s.to_string()),
+            Bson::Decimal128(d) => AnyValue::Utf8Owned(decimal128_to_string(d)),
+            v if is_fallback(v) => AnyValue::Utf8Owned(fallback_json(v)),
             v => AnyValue::Utf8Owned(format!("{:#?}", v)),
         };
         Wrap(dt)
     }
 }
+
+impl<'a> From<&AnyValue<'a>> for Wrap<Bson> {
+    fn from(av: &AnyValue<'a>) -> Self {
+        let bson = match av {
+            AnyValue::Null => Bson::Null,
+            AnyValue::Boolean(b) => Bson::Boolean(*b),
+            AnyValue::Utf8(v) => Bson::String(v.to_string()),
+            AnyValue::Utf8Owned(v) => Bson::String(v.clone()),
+            AnyValue::UInt8(v) => Bson::Int32(*v as i32),
+            AnyValue::UInt16(v) => Bson::Int32(*v as i32),
+            AnyValue::UInt32(v) => Bson::Int64(*v as i64),
+            AnyValue::UInt64(v) => match i64::try_from(*v) {
+                Ok(v) => Bson::Int64(v),
+                Err(_) => Bson::Double(*v as f64),
+            },
+            AnyValue::Int8(v) => Bson::Int32(*v as i32),
+            AnyValue::Int16(v) => Bson::Int32(*v as i32),
+            AnyValue::Int32(v) => Bson::Int32(*v),
+            AnyValue::Int64(v) => Bson::Int64(*v),
+            AnyValue::Float32(v) => Bson::Double(*v as f64),
+            AnyValue::Float64(v) => Bson::Double(*v),
+            AnyValue::Date(days) => {
+                Bson::DateTime(DateTime::from_millis(*days as i64 * 86_400_000))
+            }
+            AnyValue::Datetime(v, tu, _) => {
+                let millis = match tu {
+                    TimeUnit::Nanoseconds => v / 1_000_000,
+                    TimeUnit::Microseconds => v / 1_000,
+                    TimeUnit::Milliseconds => *v,
+                };
+                Bson::DateTime(DateTime::from_millis(millis))
+            }
+            AnyValue::Duration(v, _) => Bson::Int64(*v),
+            AnyValue::Time(v) => Bson::Int64(*v),
+            AnyValue::Categorical(idx, rev) => Bson::String(rev.get(*idx).to_string()),
+            AnyValue::List(s) => {
+                let arr = s.iter().map(|av| Wrap::<Bson>::from(&av).0).collect();
+                Bson::Array(arr)
+            }
+            AnyValue::Struct(vals, fields) => Bson::Document(struct_to_document(vals, fields)),
+            AnyValue::StructOwned(payload) => {
+                let (vals, fields) = payload.as_ref();
+                Bson::Document(struct_to_document(vals, fields))
+            }
+            av => Bson::String(av.to_string()),
+        };
+        Wrap(bson)
+    }
+}
+
+/// Embedded document with a field per struct field, converted recursively.
+fn struct_to_document(vals: &[AnyValue], fields: &[Field]) -> Document {
+    fields
+        .iter()
+        .zip(vals)
+        .map(|(fld, av)| (fld.name().clone(), Wrap::<Bson>::from(av).0))
+        .collect()
+}
+
+/// The fields of an `ObjectId` read with [`ObjectIdAs::Struct`].
+pub(crate) fn object_id_document(oid: &ObjectId) -> Document {
+    let bytes = oid.bytes();
+    let counter = u32::from_be_bytes([0, bytes[9], bytes[10], bytes[11]]);
+    doc! {"timestamp": oid.timestamp(), "counter": counter as i64}
+}
+
+/// The fields of a `Timestamp` read with [`TimestampAs::Struct`].
+pub(crate) fn timestamp_document(ts: &Timestamp) -> Document {
+    let time = DateTime::from_millis(ts.time as i64 * 1000);
+    doc! {"time": time, "increment": ts.increment as i64}
+}
+
+/// The fields of a regular expression read with [`RegexAs::Struct`].
+pub(crate) fn regex_document(regex: &Regex) -> Document {
+    doc! {"pattern": regex.pattern.as_str(), "options": regex.options.as_str()}
+}
+
+/// Whether `binary` holds a UUID, in the current or the legacy representation.
+pub(crate) fn is_uuid(binary: &Binary) -> bool {
+    matches!(binary.subtype, BinarySubtype::Uuid | BinarySubtype::UuidOld)
+        && binary.bytes.len() == 16
+}
+
+/// Canonical hyphenated string of the 16 bytes of a UUID.
+pub(crate) fn uuid_to_string(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(36);
+    for (i, byte) in bytes.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            s.push('-');
+        }
+        s.push_str(&format!("{:02x}", byte));
+    }
+    s
+}
+
+/// Convert a single polars value into its bson equivalent.
+///
+/// Structs become sub-documents, lists become arrays and dates and datetimes become
+/// bson `DateTime`s with millisecond precision.
+pub fn any_value_to_bson(av: &AnyValue) -> Bson {
+    Wrap::<Bson>::from(av).0
+}
+
+/// Convert every row of `df` into a document, keyed by the column names.
+///
+/// `Struct` columns become embedded documents and `List` columns arrays, at any depth,
+/// so documents read from a collection are written back in the same shape.
+pub fn df_to_documents(df: &DataFrame) -> Vec<Document> {
+    let columns = df.get_columns();
+    (0..df.height())
+        .map(|idx| {
+            columns
+                .iter()
+                .map(|s| (s.name().to_string(), any_value_to_bson(&s.get(idx))))
+                .collect()
+        })
+        .collect()
+}
+
+fn is_number(dtype: &DataType) -> bool {
+    matches!(
+        dtype,
+        DataType::UInt8
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Float32
+            | DataType::Float64
+    )
+}
+
+/// Merge two inferred dtypes of the same field into a dtype that can hold both.
+///
+/// Numbers are widened (`Int32` + `Int64` -> `Int64`, `Int32` + `Float64` -> `Float64`)
+/// and booleans are read as numbers. Nested documents are merged field by field, so
+/// documents with a different shape still resolve to a single `Struct` dtype.
+/// Any other combination falls back to `Utf8`, as every bson value can be read as a string.
+pub(crate) fn merge_dtypes(left: &DataType, right: &DataType) -> DataType {
+    match (left, right) {
+        (l, r) if l == r => l.clone(),
+        (DataType::Null, other) | (other, DataType::Null) => other.clone(),
+        (DataType::List(l), DataType::List(r)) => DataType::List(Box::new(merge_dtypes(l, r))),
+        (DataType::Struct(l), DataType::Struct(r)) => {
+            let mut fields: PlIndexMap<&str, DataType> = l
+                .iter()
+                .map(|fld| (fld.name().as_str(), fld.data_type().clone()))
+                .collect();
+            for fld in r {
+                let dtype = match fields.get(fld.name().as_str()) {
+                    Some(dtype) => merge_dtypes(dtype, fld.data_type()),
+                    None => fld.data_type().clone(),
+                };
+                fields.insert(fld.name(), dtype);
+            }
+            DataType::Struct(
+                fields
+                    .into_iter()
+                    .map(|(name, dtype)| Field::new(name, dtype))
+                    .collect(),
+            )
+        }
+        (l, r) if is_number(l) && is_number(r) => {
+            polars_core::utils::get_supertype(l, r).unwrap_or(DataType::Float64)
+        }
+        (DataType::Boolean, n) | (n, DataType::Boolean) if is_number(n) => n.clone(),
+        _ => DataType::Utf8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents_to_df;
+
+    #[test]
+    fn nested_lists_and_structs_round_trip() {
+        let item = DataType::Struct(vec![
+            Field::new("sku", DataType::Utf8),
+            Field::new("qty", DataType::Int64),
+        ]);
+        let address = DataType::Struct(vec![
+            Field::new("city", DataType::Utf8),
+            Field::new("lines", DataType::List(Box::new(DataType::Utf8))),
+        ]);
+        let schema = Schema::from(
+            vec![
+                Field::new("items", DataType::List(Box::new(item))),
+                Field::new("address", address),
+            ]
+            .into_iter(),
+        );
+        let docs = vec![
+            doc! {
+                "items": [{"sku": "a", "qty": 1_i64}, {"sku": "b", "qty": 2_i64}],
+                "address": {"city": "Berlin", "lines": ["Main St 1", "2nd floor"]},
+            },
+            doc! {
+                "items": [{"sku": "c", "qty": 3_i64}],
+                "address": {"city": "Paris", "lines": ["Rue 2"]},
+            },
+        ];
+        let df = documents_to_df(&docs, Some(schema.clone())).unwrap();
+        assert_eq!(df.schema(), schema);
+
+        let round_trip = documents_to_df(&df_to_documents(&df), Some(schema)).unwrap();
+        assert!(df.frame_equal(&round_trip));
+    }
+}
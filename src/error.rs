@@ -0,0 +1,96 @@
+//! Errors of reading from and writing to mongodb, with the collection they occurred on.
+use std::fmt;
+
+use polars::prelude::*;
+
+use mongodb::error::{Error, ErrorKind};
+
+/// An error of the connector. Converts into a [`PolarsError`], so it can be returned from
+/// the scan and the query engine.
+#[derive(Debug)]
+pub enum PolarsMongoError {
+    /// The connection string is invalid, or no server could be reached.
+    Connection { namespace: String, source: Error },
+    /// The server rejected the credentials.
+    Auth { namespace: String, source: Error },
+    /// A query or one of its cursors failed on the server.
+    Cursor { namespace: String, source: Error },
+    /// A value could not be converted between bson and polars.
+    Conversion(String),
+    /// A document does not match the schema of the scan.
+    SchemaMismatch(String),
+    /// The scan of `namespace` was stopped with its [`CancellationToken`](crate::CancellationToken).
+    Cancelled { namespace: String },
+}
+
+impl PolarsMongoError {
+    /// Classify an error of the mongodb driver that occurred on `namespace`, i.e. `db.collection`.
+    pub fn from_driver(source: Error, namespace: impl Into<String>) -> Self {
+        let namespace = namespace.into();
+        match *source.kind {
+            ErrorKind::Authentication { .. } => PolarsMongoError::Auth { namespace, source },
+            ErrorKind::InvalidArgument { .. }
+            | ErrorKind::InvalidTlsConfig { .. }
+            | ErrorKind::DnsResolve { .. }
+            | ErrorKind::ServerSelection { .. }
+            | ErrorKind::Io(_) => PolarsMongoError::Connection { namespace, source },
+            _ => PolarsMongoError::Cursor { namespace, source },
+        }
+    }
+}
+
+impl fmt::Display for PolarsMongoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolarsMongoError::Connection { namespace, source } => {
+                write!(
+                    f,
+                    "unable to connect to mongodb for {}: {}",
+                    namespace, source
+                )
+            }
+            PolarsMongoError::Auth { namespace, source } => {
+                write!(f, "authentication failed for {}: {}", namespace, source)
+            }
+            PolarsMongoError::Cursor { namespace, source } => {
+                write!(f, "query on {} failed: {}", namespace, source)
+            }
+            PolarsMongoError::Conversion(msg) => write!(f, "conversion error: {}", msg),
+            PolarsMongoError::SchemaMismatch(msg) => write!(f, "schema mismatch: {}", msg),
+            PolarsMongoError::Cancelled { namespace } => {
+                write!(f, "scan of {} was cancelled", namespace)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolarsMongoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PolarsMongoError::Connection { source, .. }
+            | PolarsMongoError::Auth { source, .. }
+            | PolarsMongoError::Cursor { source, .. } => Some(source),
+            PolarsMongoError::Conversion(_)
+            | PolarsMongoError::SchemaMismatch(_)
+            | PolarsMongoError::Cancelled { .. } => None,
+        }
+    }
+}
+
+impl From<PolarsMongoError> for PolarsError {
+    fn from(err: PolarsMongoError) -> Self {
+        match err {
+            PolarsMongoError::Connection { .. } | PolarsMongoError::Auth { .. } => {
+                PolarsError::InvalidOperation(err.to_string().into())
+            }
+            PolarsMongoError::Cursor { .. }
+            | PolarsMongoError::Conversion(_)
+            | PolarsMongoError::Cancelled { .. } => {
+                PolarsError::ComputeError(err.to_string().into())
+            }
+            PolarsMongoError::SchemaMismatch(_) => {
+                PolarsError::SchemaMisMatch(err.to_string().into())
+            }
+        }
+    }
+}
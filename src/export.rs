@@ -0,0 +1,95 @@
+//! Export collections to files chunk by chunk, for collections that don't fit in memory.
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+#[cfg(feature = "ipc")]
+use polars::export::arrow::io::ipc;
+#[cfg(feature = "parquet")]
+use polars::export::arrow::io::parquet::write::{
+    transverse, CompressionOptions, Encoding, FileWriter, RowGroupIterator, Version, WriteOptions,
+};
+use polars::prelude::*;
+
+use crate::batched::MongoBatchedReader;
+use crate::{MongoScan, MongoScanOptions};
+
+impl MongoScan {
+    /// Write the documents of the collection in `options` to a parquet file at `path`,
+    /// and return the number of rows written.
+    ///
+    /// Every chunk of the [`MongoBatchedReader`] is written as its own row group, so only
+    /// a single chunk is held in memory at a time.
+    #[cfg(feature = "parquet")]
+    pub fn export_parquet(
+        path: impl AsRef<Path>,
+        options: MongoScanOptions,
+    ) -> PolarsResult<usize> {
+        let mut reader = MongoBatchedReader::new(options)?;
+        let schema = reader.schema().to_arrow();
+        let write_options = WriteOptions {
+            write_statistics: true,
+            compression: CompressionOptions::Snappy,
+            version: Version::V2,
+        };
+        let encodings: Vec<Vec<Encoding>> = schema
+            .fields
+            .iter()
+            .map(|fld| transverse(fld.data_type(), |_| Encoding::Plain))
+            .collect();
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = FileWriter::try_new(file, schema.clone(), write_options)?;
+        let n_rows = export_batches(&mut reader, |df| {
+            let chunks = df.iter_chunks().map(Ok);
+            let row_groups =
+                RowGroupIterator::try_new(chunks, &schema, write_options, encodings.clone())?;
+            for group in row_groups {
+                writer.write(group?)?;
+            }
+            Ok(())
+        })?;
+        writer.end(None)?;
+        Ok(n_rows)
+    }
+
+    /// Write the documents of the collection in `options` to an arrow ipc file at `path`,
+    /// and return the number of rows written.
+    ///
+    /// Every chunk of the [`MongoBatchedReader`] is written as its own record batch, so only
+    /// a single chunk is held in memory at a time.
+    #[cfg(feature = "ipc")]
+    pub fn export_ipc(path: impl AsRef<Path>, options: MongoScanOptions) -> PolarsResult<usize> {
+        let mut reader = MongoBatchedReader::new(options)?;
+        let schema = reader.schema().to_arrow();
+
+        let file = BufWriter::new(File::create(path)?);
+        let write_options = ipc::write::WriteOptions { compression: None };
+        let mut writer = ipc::write::FileWriter::try_new(file, &schema, None, write_options)?;
+        let n_rows = export_batches(&mut reader, |df| {
+            for chunk in df.iter_chunks() {
+                writer.write(&chunk, None)?;
+            }
+            Ok(())
+        })?;
+        writer.finish()?;
+        Ok(n_rows)
+    }
+}
+
+/// Pass every non-empty batch of `reader` to `write`, as a single chunk per column.
+fn export_batches(
+    reader: &mut MongoBatchedReader,
+    mut write: impl FnMut(&DataFrame) -> PolarsResult<()>,
+) -> PolarsResult<usize> {
+    let mut n_rows = 0;
+    while let Some(mut df) = reader.next_batch()? {
+        if df.height() == 0 {
+            continue;
+        }
+        df.rechunk();
+        n_rows += df.height();
+        write(&df)?;
+    }
+    Ok(n_rows)
+}
@@ -0,0 +1,81 @@
+//! Expressions on columns read from mongodb.
+use polars::prelude::*;
+
+use mongodb::bson::{oid::ObjectId, Bson, Document};
+
+use crate::conversion::ConversionOptions;
+use crate::extjson_file::extjson_to_bson;
+use crate::read_documents;
+
+/// Creation time embedded in the hex `ObjectId` strings of `expr`, as a `Datetime`.
+/// `ObjectId`s only store whole seconds.
+///
+/// Values that are not valid `ObjectId`s become nulls.
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::expr::oid_timestamp;
+///
+/// # fn example(lf: LazyFrame) -> LazyFrame {
+/// lf.with_column(oid_timestamp(col("_id")).alias("created_at"))
+/// # }
+/// ```
+pub fn oid_timestamp(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let mut ca: Int64Chunked = s
+                .utf8()?
+                .into_iter()
+                .map(|opt_hex| {
+                    opt_hex
+                        .and_then(|hex| ObjectId::parse_str(hex).ok())
+                        .map(|oid| oid.timestamp().timestamp_millis())
+                })
+                .collect();
+            ca.rename(s.name());
+            Ok(ca.into_datetime(TimeUnit::Milliseconds, None).into_series())
+        },
+        GetOutput::from_type(DataType::Datetime(TimeUnit::Milliseconds, None)),
+    )
+}
+
+/// Parse the extended JSON documents in the `Utf8` column of `expr` into a `Struct` column,
+/// with the same dtypes as when the documents are read from a collection.
+///
+/// The fields are inferred from all values, so the dtype of the column is only known once
+/// it is computed. Nulls become structs of null fields.
+pub fn parse_extended_json(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let ca = s.utf8()?;
+            let mut values = ca.into_iter();
+            let next_document = || -> PolarsResult<Option<Document>> {
+                let json = match values.next() {
+                    Some(Some(json)) => json,
+                    Some(None) => return Ok(Some(Document::new())),
+                    None => return Ok(None),
+                };
+                let value = serde_json::from_str(json)
+                    .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+                match extjson_to_bson(value)? {
+                    Bson::Document(doc) => Ok(Some(doc)),
+                    other => Err(PolarsError::ComputeError(
+                        format!("expected an extended JSON document, got {}", other).into(),
+                    )),
+                }
+            };
+            let df = read_documents(
+                next_document,
+                None,
+                Some(ca.len()),
+                None,
+                &ConversionOptions::default(),
+            )?;
+            // a struct needs at least one field.
+            if df.width() == 0 {
+                return Ok(Series::full_null(s.name(), s.len(), &DataType::Null));
+            }
+            Ok(df.into_struct(s.name()).into_series())
+        },
+        GetOutput::from_type(DataType::Struct(vec![])),
+    )
+}
@@ -0,0 +1,157 @@
+//! Read newline delimited extended JSON files, as written by `mongoexport`.
+use std::io::{BufRead, BufReader, Read, Seek};
+
+use polars::prelude::*;
+
+use mongodb::bson::{Bson, Document};
+use serde_json::Value;
+
+use crate::conversion::{parse_decimal128, ConversionOptions};
+use crate::{read_documents, FallbackDtype, ObjectIdAs, RegexAs, TimestampAs, UuidAs};
+
+/// Read a newline delimited extended JSON export into a [`DataFrame`].
+///
+/// Both relaxed and canonical extended JSON are supported. Type wrappers such as
+/// `$oid`, `$date` and `$numberDecimal` are decoded into their bson values first,
+/// so they end up with the same dtypes as when reading the collection directly.
+pub struct ExtendedJsonReader<R> {
+    reader: R,
+    infer_schema_length: Option<usize>,
+    schema: Option<Schema>,
+    n_rows: Option<usize>,
+    conversion: ConversionOptions,
+}
+
+impl<R> ExtendedJsonReader<R>
+where
+    R: Read + Seek,
+{
+    /// Number of documents used to infer the schema. Defaults to `100`.
+    pub fn with_infer_schema_length(mut self, infer_schema_length: Option<usize>) -> Self {
+        self.infer_schema_length = infer_schema_length;
+        self
+    }
+
+    /// Use `schema` instead of inferring it from the file.
+    pub fn with_schema(mut self, schema: Option<Schema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Stop reading after `n_rows` documents.
+    pub fn with_n_rows(mut self, n_rows: Option<usize>) -> Self {
+        self.n_rows = n_rows;
+        self
+    }
+
+    pub fn with_decimal128_as_float(mut self, decimal128_as_float: bool) -> Self {
+        self.conversion.decimal128_as_float = decimal128_as_float;
+        self
+    }
+
+    pub fn with_object_id_as(mut self, object_id_as: ObjectIdAs) -> Self {
+        self.conversion.object_id_as = object_id_as;
+        self
+    }
+
+    pub fn with_timestamp_as(mut self, timestamp_as: TimestampAs) -> Self {
+        self.conversion.timestamp_as = timestamp_as;
+        self
+    }
+
+    pub fn with_regex_as(mut self, regex_as: RegexAs) -> Self {
+        self.conversion.regex_as = regex_as;
+        self
+    }
+
+    pub fn with_uuid_as(mut self, uuid_as: UuidAs) -> Self {
+        self.conversion.uuid_as = uuid_as;
+        self
+    }
+
+    pub fn with_fallback_dtype(mut self, fallback_dtype: FallbackDtype) -> Self {
+        self.conversion.fallback_dtype = fallback_dtype;
+        self
+    }
+}
+
+impl<R> SerReader<R> for ExtendedJsonReader<R>
+where
+    R: Read + Seek,
+{
+    fn new(reader: R) -> Self {
+        ExtendedJsonReader {
+            reader,
+            infer_schema_length: None,
+            schema: None,
+            n_rows: None,
+            conversion: ConversionOptions::default(),
+        }
+    }
+
+    fn finish(self) -> PolarsResult<DataFrame> {
+        let mut lines = BufReader::new(self.reader).lines();
+        let next_document = || -> PolarsResult<Option<Document>> {
+            for line in lines.by_ref() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let value = serde_json::from_str(&line)
+                    .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+                return match extjson_to_bson(value)? {
+                    Bson::Document(doc) => Ok(Some(doc)),
+                    other => Err(PolarsError::ComputeError(
+                        format!("expected a document on every line, got {}", other).into(),
+                    )),
+                };
+            }
+            Ok(None)
+        };
+        read_documents(
+            next_document,
+            self.schema,
+            self.infer_schema_length,
+            self.n_rows,
+            &self.conversion,
+        )
+    }
+}
+
+/// Convert an extended JSON value into bson.
+///
+/// The bson crate does not decode `$numberDecimal` yet, so those wrappers are decoded here
+/// and everything else is left to the bson crate.
+pub(crate) fn extjson_to_bson(value: Value) -> PolarsResult<Bson> {
+    let bson = match value {
+        Value::Object(mut map) => match map.get("$numberDecimal") {
+            Some(Value::String(s)) if map.len() == 1 => match parse_decimal128(s) {
+                Some(dec) => Bson::Decimal128(dec),
+                None => {
+                    return Err(PolarsError::ComputeError(
+                        format!("invalid $numberDecimal: {}", s).into(),
+                    ))
+                }
+            },
+            _ if map.keys().any(|key| key.starts_with('$')) => {
+                Bson::try_from(Value::Object(map))
+                    .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?
+            }
+            _ => {
+                let mut doc = Document::new();
+                for (key, value) in std::mem::take(&mut map) {
+                    doc.insert(key, extjson_to_bson(value)?);
+                }
+                Bson::Document(doc)
+            }
+        },
+        Value::Array(arr) => Bson::Array(
+            arr.into_iter()
+                .map(extjson_to_bson)
+                .collect::<PolarsResult<_>>()?,
+        ),
+        value => Bson::try_from(value)
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?,
+    };
+    Ok(bson)
+}
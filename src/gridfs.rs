@@ -0,0 +1,191 @@
+//! Files stored in a GridFS bucket.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use polars::prelude::*;
+
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::{FindOneOptions, FindOptions},
+    sync::Collection,
+};
+
+use crate::{read_documents, MongoScan, MongoScanOptions};
+
+/// Columns of the file listing, if present in the files collection.
+const FILE_COLUMNS: [&str; 5] = ["_id", "filename", "length", "uploadDate", "metadata"];
+
+/// Reads the files of a GridFS bucket.
+///
+/// The bucket is made of the `<bucket>.files` and `<bucket>.chunks` collections of
+/// `options.db`. `options.filter` selects the files to read, e.g. `{"metadata.kind": "report"}`.
+///
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::prelude::*;
+///
+/// # fn main() -> PolarsResult<()> {
+/// # let options = MongoScanOptions::default();
+/// let reader = GridFsReader::new(options).with_bucket("images");
+/// let files = reader.files()?;
+/// reader.download("logo.png", "/tmp/logo.png")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct GridFsReader {
+    options: MongoScanOptions,
+    bucket: String,
+    with_contents: bool,
+}
+
+impl GridFsReader {
+    pub fn new(options: MongoScanOptions) -> Self {
+        GridFsReader {
+            options,
+            bucket: "fs".into(),
+            with_contents: false,
+        }
+    }
+
+    /// Name of the bucket. Defaults to `"fs"`.
+    pub fn with_bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.bucket = bucket.into();
+        self
+    }
+
+    /// Add the contents of every file as a binary `data` column to [`GridFsReader::files`].
+    pub fn with_contents(mut self, with_contents: bool) -> Self {
+        self.with_contents = with_contents;
+        self
+    }
+
+    fn scan(&self, suffix: &str) -> PolarsResult<MongoScan> {
+        let options = MongoScanOptions {
+            collection: format!("{}.{}", self.bucket, suffix),
+            ..self.options.clone()
+        };
+        Ok(MongoScan::from_options(&options)?
+            .with_max_time(options.max_time_ms.map(Duration::from_millis))
+            .with_decimal128_as_float(options.decimal128_as_float)
+            .with_object_id_as(options.object_id_as)
+            .with_timestamp_as(options.timestamp_as)
+            .with_regex_as(options.regex_as)
+            .with_uuid_as(options.uuid_as)
+            .with_fallback_dtype(options.fallback_dtype)
+            .with_datetime_time_unit(options.datetime_time_unit)
+            .with_parse_mode(options.parse_mode)
+            .with_parse_report(options.parse_report.clone()))
+    }
+
+    /// Write the chunks of the file with `id` in order, and return the number of bytes written.
+    fn read_chunks(
+        chunks: &Collection<Document>,
+        id: &Bson,
+        mut write: impl FnMut(&[u8]) -> PolarsResult<()>,
+    ) -> PolarsResult<u64> {
+        let find_options = FindOptions::builder().sort(doc! {"n": 1}).build();
+        let cursor = chunks
+            .find(doc! {"files_id": id.clone()}, Some(find_options))
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+
+        let mut length = 0;
+        for chunk in cursor {
+            let chunk =
+                chunk.map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+            match chunk.get("data") {
+                Some(Bson::Binary(data)) => {
+                    write(&data.bytes)?;
+                    length += data.bytes.len() as u64;
+                }
+                _ => {
+                    return Err(PolarsError::ComputeError(
+                        format!("chunk of file {} without binary data", id).into(),
+                    ))
+                }
+            }
+        }
+        Ok(length)
+    }
+
+    /// List the files of the bucket, with their `_id`, `filename`, `length`, `uploadDate` and
+    /// `metadata`, and their contents if [`GridFsReader::with_contents`] is set.
+    pub fn files(&self) -> PolarsResult<DataFrame> {
+        let scan = self.scan("files")?;
+        let find_options = FindOptions::builder()
+            .sort(doc! {"filename": 1, "uploadDate": 1})
+            .max_time(scan.max_time)
+            .build();
+        let docs = scan
+            .get_collection()?
+            .find(self.options.filter.clone(), Some(find_options))
+            .and_then(|cursor| cursor.collect::<mongodb::error::Result<Vec<_>>>())
+            .map_err(|err| scan.driver_error(err))?;
+
+        let mut iter = docs.iter().cloned();
+        let df = read_documents(
+            || Ok(iter.next()),
+            self.options.schema.clone(),
+            Some(docs.len().max(1)),
+            None,
+            &scan.conversion,
+        )?;
+        let columns = FILE_COLUMNS
+            .iter()
+            .filter(|name| df.get_column_names().contains(name))
+            .collect::<Vec<_>>();
+        let mut df = df.select(columns)?;
+
+        if self.with_contents {
+            let chunks = self.scan("chunks")?.get_collection()?;
+            let total = docs
+                .iter()
+                .map(|doc| match doc.get("length") {
+                    Some(Bson::Int32(n)) => *n as usize,
+                    Some(Bson::Int64(n)) => *n as usize,
+                    _ => 0,
+                })
+                .sum();
+            let mut data = ListPrimitiveChunkedBuilder::<UInt8Type>::new(
+                "data",
+                docs.len(),
+                total,
+                DataType::UInt8,
+            );
+            for doc in &docs {
+                let mut bytes = vec![];
+                Self::read_chunks(&chunks, doc.get("_id").unwrap_or(&Bson::Null), |chunk| {
+                    bytes.extend_from_slice(chunk);
+                    Ok(())
+                })?;
+                data.append_slice(Some(&bytes));
+            }
+            df.with_column(data.finish().into_series())?;
+        }
+        Ok(df)
+    }
+
+    /// Stream the latest revision of the file named `filename` to `path`,
+    /// and return its length in bytes.
+    pub fn download(&self, filename: &str, path: impl AsRef<Path>) -> PolarsResult<u64> {
+        let find_options = FindOneOptions::builder()
+            .sort(doc! {"uploadDate": -1})
+            .projection(doc! {"_id": 1})
+            .build();
+        let file = self
+            .scan("files")?
+            .get_collection()?
+            .find_one(doc! {"filename": filename}, Some(find_options))
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?
+            .ok_or_else(|| PolarsError::NotFound(filename.to_string().into()))?;
+
+        let chunks = self.scan("chunks")?.get_collection()?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        let length = Self::read_chunks(&chunks, file.get("_id").unwrap_or(&Bson::Null), |chunk| {
+            Ok(writer.write_all(chunk)?)
+        })?;
+        writer.flush()?;
+        Ok(length)
+    }
+}
@@ -0,0 +1,313 @@
+//! Joins of two collections of the same database, executed by mongodb with `$lookup`.
+use polars::prelude::*;
+
+use mongodb::bson::{doc, Bson, Document};
+
+use crate::pushdown::{field_path, is_plain_field};
+use crate::{scan_mongo, scan_mongo_source, MongoScanOptions};
+
+/// Field the joined documents are stored in by the `$lookup` stage.
+const JOINED: &str = "__joined";
+
+/// Join two collections of the same database on equality keys in a single aggregation,
+/// instead of scanning both collections and joining them in polars.
+///
+/// The result has the columns of the `left` collection, followed by the columns of the
+/// `right` collection except its join keys, like [`LazyFrame::join`]. The `filter`, `predicate`
+/// and `sort` of `left` are applied as usual. The `filter` and `predicate` of `right` are
+/// evaluated in the `$lookup`, so the predicate must be fully translatable to a mongodb filter.
+///
+/// Keys are compared with mongodb equality, so e.g. an `Int32` key matches an equal `Double` key.
+/// Like `filter` and `predicate`, the keys name the mongodb fields, whatever the `rename` of
+/// their side. The renames are applied to the joined columns.
+///
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::prelude::*;
+///
+/// # fn main() -> PolarsResult<()> {
+/// let orders = MongoScanOptions {
+///     connection_str: "mongodb://localhost:27017".into(),
+///     db: "shop".into(),
+///     collection: "orders".into(),
+///     ..Default::default()
+/// };
+/// let users = MongoScanOptions {
+///     collection: "users".into(),
+///     ..orders.clone()
+/// };
+/// let df = MongoJoinBuilder::new(orders, users)
+///     .with_left_on(vec!["user_id".into()])
+///     .with_right_on(vec!["_id".into()])
+///     .finish()?
+///     .collect()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MongoJoinBuilder {
+    left: MongoScanOptions,
+    right: MongoScanOptions,
+    left_on: Vec<String>,
+    right_on: Vec<String>,
+    how: JoinType,
+    suffix: String,
+}
+
+impl MongoJoinBuilder {
+    pub fn new(left: MongoScanOptions, right: MongoScanOptions) -> Self {
+        MongoJoinBuilder {
+            left,
+            right,
+            left_on: vec![],
+            right_on: vec![],
+            how: JoinType::Inner,
+            suffix: "_right".into(),
+        }
+    }
+
+    pub fn with_left_on(mut self, left_on: Vec<String>) -> Self {
+        self.left_on = left_on;
+        self
+    }
+
+    pub fn with_right_on(mut self, right_on: Vec<String>) -> Self {
+        self.right_on = right_on;
+        self
+    }
+
+    /// Join on fields with the same name in both collections.
+    pub fn with_on(self, on: Vec<String>) -> Self {
+        self.with_left_on(on.clone()).with_right_on(on)
+    }
+
+    /// Only `JoinType::Inner` (the default) and `JoinType::Left` are supported.
+    pub fn with_how(mut self, how: JoinType) -> Self {
+        self.how = how;
+        self
+    }
+
+    /// Suffix of the `right` columns that have the same name as a `left` column.
+    /// Defaults to `"_right"`.
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    fn invalid(msg: impl Into<String>) -> PolarsError {
+        PolarsError::InvalidOperation(msg.into().into())
+    }
+
+    /// The `$lookup` stage, matching the `right` documents on the join keys
+    /// and `right_filter`.
+    fn lookup_stage(
+        &self,
+        right_fields: &[&str],
+        right_filter: Option<&Document>,
+    ) -> PolarsResult<Document> {
+        let mut variables = Document::new();
+        let mut conditions = vec![];
+        for (i, (left, right)) in self.left_on.iter().zip(&self.right_on).enumerate() {
+            let var = format!("k{}", i);
+            conditions.push(Bson::Document(
+                doc! {"$eq": [field_path(right).unwrap(), format!("$${}", var)]},
+            ));
+            variables.insert(var, field_path(left).unwrap());
+        }
+
+        let mut pipeline = vec![doc! {"$match": {"$expr": {"$and": conditions}}}];
+        if let Some(filter) = right_filter {
+            pipeline.push(doc! {"$match": filter.clone()});
+        }
+        let mut project = Document::new();
+        if !right_fields.contains(&"_id") {
+            project.insert("_id", 0);
+        }
+        for name in right_fields {
+            project.insert(*name, 1);
+        }
+        pipeline.push(doc! {"$project": project});
+
+        Ok(doc! {
+            "$lookup": {
+                "from": self.right.collection.clone(),
+                "let": variables,
+                "pipeline": pipeline,
+                "as": JOINED,
+            }
+        })
+    }
+
+    pub fn finish(self) -> PolarsResult<LazyFrame> {
+        if !matches!(self.how, JoinType::Inner | JoinType::Left) {
+            return Err(Self::invalid(format!(
+                "{:?} joins can't be executed by mongodb",
+                self.how
+            )));
+        }
+        if self.left.db != self.right.db || self.left.connection_str != self.right.connection_str {
+            return Err(Self::invalid(
+                "only collections of the same database can be joined by mongodb",
+            ));
+        }
+        if self.left_on.is_empty() || self.left_on.len() != self.right_on.len() {
+            return Err(Self::invalid(
+                "a mongodb join needs the same, non-zero number of left and right keys",
+            ));
+        }
+
+        let left = scan_mongo_source(self.left.clone(), None)?;
+        let right = scan_mongo_source(self.right.clone(), None)?;
+        if right.remaining.is_some() {
+            return Err(Self::invalid(
+                "the right predicate of a mongodb join must be translatable to a mongodb filter",
+            ));
+        }
+        // the pipeline reads the fields under their mongodb names, the renames are applied to
+        // the result.
+        let (left_schema, right_schema) = (left.schema, right.schema);
+        for (keys, schema) in [
+            (&self.left_on, &left_schema),
+            (&self.right_on, &right_schema),
+        ] {
+            for key in keys {
+                if !is_plain_field(key) {
+                    return Err(Self::invalid(format!(
+                        "{} can't be used as a join key",
+                        key
+                    )));
+                }
+                if schema.get(key).is_none() {
+                    return Err(PolarsError::NotFound(key.clone().into()));
+                }
+            }
+        }
+
+        let renamed = |options: &MongoScanOptions, name: &str| -> String {
+            match options.rename.as_ref().and_then(|rename| rename.get(name)) {
+                Some(renamed) => renamed.clone(),
+                None => name.to_string(),
+            }
+        };
+        let left_names = left_schema
+            .iter_names()
+            .flat_map(|name| [name.clone(), renamed(&self.left, name)])
+            .collect::<PlHashSet<_>>();
+
+        // the fields of the right collection, and their names in the output.
+        let right_fields = right_schema
+            .iter_names()
+            .filter(|name| !self.right_on.contains(name))
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+        let mut schema = left_schema.as_ref().clone();
+        let mut project = Document::new();
+        if left_schema.get("_id").is_none() {
+            project.insert("_id", 0);
+        }
+        for name in left_schema.iter_names() {
+            project.insert(name.as_str(), 1);
+        }
+        for name in &right_fields {
+            let mut output = renamed(&self.right, name);
+            if left_names.contains(&output) {
+                output.push_str(&self.suffix);
+            }
+            if !is_plain_field(name) || !is_plain_field(&output) || output == JOINED {
+                return Err(Self::invalid(format!(
+                    "{} can't be joined by mongodb",
+                    name
+                )));
+            }
+            project.insert(output.as_str(), format!("${}.{}", JOINED, name));
+            schema.with_column(output, right_schema.get(name).unwrap().clone());
+        }
+
+        let mut options = self.left.clone();
+        let mut pipeline = vec![];
+        // filter before the lookup, so only the matching documents are joined.
+        // the scans hold `filter` and the translated part of `predicate`.
+        options.filter = None;
+        if let Some(filter) = &left.scan.filter {
+            pipeline.push(doc! {"$match": filter.clone()});
+        }
+        pipeline.push(self.lookup_stage(&right_fields, right.scan.filter.as_ref())?);
+        pipeline.push(doc! {
+            "$unwind": {
+                "path": format!("${}", JOINED),
+                "preserveNullAndEmptyArrays": self.how == JoinType::Left,
+            }
+        });
+        pipeline.push(doc! {"$project": project});
+
+        options.predicate = left.remaining;
+        options.schema = Some(schema);
+        options.dtype_overrides = None;
+        // the right columns are already projected under their output names.
+        let rename = options.rename.take();
+        let lf = scan_mongo(options, Some(pipeline))?;
+        Ok(match rename {
+            Some(rename) => lf.rename(rename.keys(), rename.values()),
+            None => lf,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn options(collection: &str, fields: &[(&str, DataType)]) -> MongoScanOptions {
+        MongoScanOptions {
+            connection_str: "mongodb://localhost:27017".into(),
+            db: "shop".into(),
+            collection: collection.into(),
+            schema: Some(Schema::from(
+                fields
+                    .iter()
+                    .map(|(name, dtype)| Field::new(name, dtype.clone())),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn join_renames_the_result_once() {
+        let mut orders = options(
+            "orders",
+            &[
+                ("_id", DataType::Utf8),
+                ("user_id", DataType::Utf8),
+                ("name", DataType::Utf8),
+            ],
+        );
+        orders.rename = Some(HashMap::from([("name".into(), "item".into())]));
+        let mut users = options(
+            "users",
+            &[
+                ("_id", DataType::Utf8),
+                ("name", DataType::Utf8),
+                ("age", DataType::Int32),
+            ],
+        );
+        users.rename = Some(HashMap::from([("age".into(), "item".into())]));
+
+        let schema = MongoJoinBuilder::new(orders, users)
+            .with_left_on(vec!["user_id".into()])
+            .with_right_on(vec!["_id".into()])
+            .finish()
+            .unwrap()
+            .schema()
+            .unwrap();
+        let names = schema
+            .iter_names()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["_id", "user_id", "item", "name_right", "item_right"]
+        );
+        assert_eq!(schema.get("item_right"), Some(&DataType::Int32));
+    }
+}
@@ -1,6 +1,6 @@
 //! Polars mongo is a connector to read from a mongodb collection into a Polars dataframe.
 //! Usage:
-//! ```rust
+//! ```no_run
 //! use polars::prelude::*;
 //! use polars_mongo::prelude::*;
 //!
@@ -16,6 +16,7 @@
 //!         collection,
 //!         infer_schema_length: Some(1000),
 //!         n_rows: None,
+//!         ..Default::default()
 //!     })?
 //!     .collect()?;
 //!
@@ -26,34 +27,183 @@
 #![deny(clippy::all)]
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "sync")]
+pub mod admin;
+#[cfg(feature = "async")]
+mod async_scan;
+#[cfg(feature = "sync")]
+pub mod batched;
+pub mod bson_file;
 mod buffer;
+mod cancel;
+#[cfg(feature = "sync")]
+mod causal;
+#[cfg(feature = "sync")]
+pub mod change_stream;
+mod connection;
 mod conversion;
+mod error;
+#[cfg(all(feature = "sync", any(feature = "parquet", feature = "ipc")))]
+mod export;
+pub mod expr;
+pub mod extjson_file;
+#[cfg(feature = "sync")]
+pub mod gridfs;
+#[cfg(feature = "sync")]
+pub mod join;
+mod partition;
+mod predicate;
 pub mod prelude;
+#[cfg(feature = "sync")]
+pub mod profile;
+mod progress;
+#[cfg(feature = "sync")]
+mod pushdown;
+#[cfg(feature = "sync")]
+pub mod reader;
+mod retry;
+#[cfg(feature = "sync")]
+pub mod tailable;
+#[cfg(feature = "sync")]
+mod throttle;
+#[cfg(all(feature = "sync", feature = "serde"))]
+pub mod typed;
+#[cfg(feature = "sync")]
+pub mod vector_search;
+#[cfg(feature = "sync")]
+pub mod writer;
 
+#[cfg(feature = "async")]
+pub use crate::async_scan::*;
 use crate::buffer::*;
+pub use crate::cancel::CancellationToken;
+pub use crate::connection::{Compression, MongoConnection};
+pub use crate::conversion::{
+    any_value_to_bson, df_to_documents, FallbackDtype, ObjectIdAs, RawFormat, RegexAs, TimestampAs,
+    UuidAs,
+};
+pub use crate::error::PolarsMongoError;
+#[cfg(feature = "sync")]
+use crate::partition::*;
+pub use crate::partition::{CountMode, PartitionStrategy};
+#[cfg(feature = "sync")]
+use crate::progress::ProgressTracker;
+pub use crate::progress::{ProgressCallback, ScanProgress};
+#[cfg(feature = "sync")]
+pub use crate::pushdown::PipelineOutput;
+#[cfg(feature = "sync")]
+use crate::retry::ReadError;
+pub use crate::retry::RetryPolicy;
+#[cfg(feature = "sync")]
+use crate::throttle::Throttle;
+/// Used by [`mongo_schema!`], so callers don't need polars under that name.
+#[doc(hidden)]
+pub use polars;
 
-use conversion::Wrap;
-use polars::export::rayon::prelude::*;
-use polars::{frame::row::*, prelude::*};
-use polars_core::POOL;
+use conversion::{merge_dtypes, ConversionOptions, RAW_COLUMN};
+pub use conversion::{ParseMode, ParseReport};
+use polars::prelude::*;
+#[cfg(feature = "sync")]
+use predicate::FilterContext;
+use predicate::SourceTypes;
+#[cfg(feature = "sync")]
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "sync")]
+use std::time::{Duration, Instant};
 
+use mongodb::bson::Document;
+#[cfg(feature = "sync")]
 use mongodb::{
-    bson::{Bson, Document},
-    options::{ClientOptions, FindOptions},
-    sync::{Client, Collection, Cursor},
+    bson::{doc, Bson, Timestamp},
+    error::ErrorKind,
+    options::{
+        AggregateOptions, ClientOptions, CountOptions, DistinctOptions,
+        EstimatedDocumentCountOptions, FindOptions,
+    },
+    sync::{Client, Collection, Cursor, Database},
 };
-use polars_core::utils::accumulate_dataframes_vertical;
+#[cfg(feature = "sync")]
+use once_cell::sync::OnceCell;
+#[cfg(feature = "sync")]
+use polars::export::rayon::prelude::*;
+#[cfg(feature = "sync")]
+use polars_core::{utils::accumulate_dataframes_vertical, POOL};
+
+/// Scans of at most this many rows, e.g. of `fetch` or `head`, are read with a single
+/// cursor in a single batch, without collecting statistics of the collection.
+#[cfg(feature = "sync")]
+const SINGLE_CURSOR_MAX_ROWS: usize = 1_000;
+
+/// Cursor batches are sized to about this many bytes, if no batch size is given.
+#[cfg(feature = "sync")]
+const DEFAULT_TARGET_BATCH_BYTES: usize = 4 * 1024 * 1024;
+
+/// Scans without a fixed number of threads use a thread per this many bytes of the collection.
+#[cfg(feature = "sync")]
+const MIN_BYTES_PER_THREAD: usize = 16 * 1024 * 1024;
+
+/// Size of a scan, estimated from the statistics of the collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanEstimate {
+    /// Number of documents in the collection.
+    pub count: usize,
+    /// Average size of a document in bytes.
+    pub avg_document_size: usize,
+    /// Uncompressed size of all documents in bytes.
+    pub total_bytes: usize,
+    /// Approximate size in bytes of the collection once read with the schema of the scan.
+    pub projected_memory: usize,
+}
 
+#[cfg(feature = "sync")]
+#[derive(Clone)]
 pub struct MongoScan {
     client_options: ClientOptions,
+    /// Connected on first use, and shared by schema inference and all scan threads.
+    client: OnceCell<Client>,
     db: String,
     collection_name: String,
     pub collection: Option<Collection<Document>>,
+    pub filter: Option<Document>,
+    pub sort: Option<Document>,
+    pub ordered: bool,
+    pub pipeline: Option<Vec<Document>>,
+    pub schema: Option<Schema>,
+    pub dtype_overrides: Option<Vec<Field>>,
+    pub infer_schema_sample: bool,
+    pub columns: Option<Vec<String>>,
+    pub include_id: bool,
+    pub categorical_columns: Vec<String>,
+    pub unnest_columns: Vec<String>,
+    /// Schema of the collection before unnesting, resolved once.
+    nested_schema: OnceCell<Schema>,
+    /// Bson types of the fields sampled by the first schema inference, to translate predicates.
+    source_types: OnceCell<SourceTypes>,
+    /// `collStats` of the collection, fetched once.
+    stats: OnceCell<ScanEstimate>,
+    pub partition_strategy: Option<PartitionStrategy>,
+    pub count_mode: CountMode,
+    pub retry_policy: Option<RetryPolicy>,
+    pub snapshot: bool,
+    pub causal_consistency: bool,
+    pub on_progress: Option<ProgressCallback>,
+    pub cancellation: Option<CancellationToken>,
+    /// Rate and concurrency limits, shared by all workers.
+    throttle: Throttle,
+    conversion: ConversionOptions,
     pub n_threads: Option<usize>,
     pub batch_size: Option<usize>,
+    pub target_batch_bytes: Option<usize>,
+    pub max_time: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub allow_disk_use: bool,
+    pub no_cursor_timeout: bool,
     pub rechunk: bool,
 }
 
+#[cfg(feature = "sync")]
 impl MongoScan {
     pub fn with_rechunk(mut self, rechunk: bool) -> Self {
         self.rechunk = rechunk;
@@ -64,100 +214,1001 @@ impl MongoScan {
         self
     }
 
+    /// Size the cursor batches of every scan thread to about `target_batch_bytes`, based on
+    /// the average document size of the collection. Defaults to 4 MiB.
+    /// A fixed batch size takes precedence.
+    pub fn with_target_batch_bytes(mut self, target_batch_bytes: Option<usize>) -> Self {
+        self.target_batch_bytes = target_batch_bytes;
+        self
+    }
+
+    /// Use `schema` instead of inferring it from the collection.
+    ///
+    /// The numeric and `Boolean` columns of a given schema are trusted to be stored as numbers
+    /// and booleans, so predicates on them are evaluated by mongodb. Predicates on the other
+    /// columns need their bson types sampled by schema inference.
+    pub fn with_schema(mut self, schema: Option<Schema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Overwrite the dtypes of the given fields in the inferred schema.
+    pub fn with_dtype_overrides(mut self, dtype_overrides: Option<Vec<Field>>) -> Self {
+        self.dtype_overrides = dtype_overrides;
+        self
+    }
+
+    /// Only infer and fetch these fields, in this order, instead of every field of the documents.
+    pub fn with_columns(mut self, columns: Option<Vec<String>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Whether to read the `_id` field. Defaults to `true`.
+    pub fn with_include_id(mut self, include_id: bool) -> Self {
+        self.include_id = include_id;
+        self
+    }
+
+    /// Read these string fields as `Categorical` instead of `Utf8` columns.
+    ///
+    /// Only applies to fields inferred as `Utf8`, a given schema is used as is.
+    pub fn with_categorical_columns(mut self, columns: Vec<String>) -> Self {
+        self.categorical_columns = columns;
+        self
+    }
+
+    /// Infer the schema from a random `$sample` of the collection instead of the first documents.
+    ///
+    /// This picks up fields that only appear further into the collection,
+    /// at the cost of mongodb having to select the random sample.
+    pub fn with_infer_schema_sample(mut self, infer_schema_sample: bool) -> Self {
+        self.infer_schema_sample = infer_schema_sample;
+        self
+    }
+
+    /// Set how the scan is split between threads.
+    ///
+    /// Defaults to [`PartitionStrategy::ShardChunks`] for large sharded collections,
+    /// [`PartitionStrategy::IdRange`] for other large collections, and
+    /// [`PartitionStrategy::SkipLimit`] otherwise.
+    pub fn with_partition_strategy(mut self, strategy: Option<PartitionStrategy>) -> Self {
+        self.partition_strategy = strategy;
+        self
+    }
+
+    /// How the collection is counted when there is no filter. A filtered collection
+    /// is always counted exactly.
+    pub fn with_count_mode(mut self, count_mode: CountMode) -> Self {
+        self.count_mode = count_mode;
+        self
+    }
+
+    /// Read a partition again after transient errors, such as unreachable hosts or a primary
+    /// stepdown, instead of failing the whole scan. Defaults to no retries.
+    pub fn with_retry_policy(mut self, retry_policy: Option<RetryPolicy>) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Call `on_progress` with the number of documents read by every worker of the scan,
+    /// every 10,000 documents and once its partition is read.
+    pub fn with_on_progress(mut self, on_progress: Option<ProgressCallback>) -> Self {
+        self.on_progress = on_progress;
+        self
+    }
+
+    /// Stop the scan once `cancellation` is cancelled, and fail it with
+    /// [`PolarsMongoError::Cancelled`].
+    pub fn with_cancellation(mut self, cancellation: Option<CancellationToken>) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Read at most `max_docs_per_second` documents per second, summed over all workers,
+    /// so analytical scans don't starve the other workload of the cluster.
+    pub fn with_max_docs_per_second(mut self, max_docs_per_second: Option<u32>) -> Self {
+        self.throttle.max_docs_per_second = max_docs_per_second;
+        self
+    }
+
+    /// Read at most `max_batches_in_flight` partitions at the same time, independent of
+    /// the number of threads. The other workers wait until a partition is read.
+    pub fn with_max_batches_in_flight(mut self, max_batches_in_flight: Option<usize>) -> Self {
+        self.throttle.max_batches_in_flight = max_batches_in_flight;
+        self
+    }
+
+    /// Read all partitions of the scan at the same cluster time, with `readConcern: snapshot`,
+    /// so they observe a consistent view of the collection even under concurrent writes.
+    ///
+    /// Needs a replica set or sharded cluster, and the scan has to finish within the snapshot
+    /// history window of the server, 5 minutes by default.
+    pub fn with_snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /// Read the partitions of the scan after the latest write of a
+    /// [`MongoCollectionWriter`](crate::writer::MongoCollectionWriter) with causal consistency to
+    /// the same cluster in this program, with `readConcern: majority` and `afterClusterTime`.
+    ///
+    /// Needs a replica set or sharded cluster. Without such a write, the scan reads as usual.
+    pub fn with_causal_consistency(mut self, causal_consistency: bool) -> Self {
+        self.causal_consistency = causal_consistency;
+        self
+    }
+
+    /// Read `Decimal128` values as `Float64` instead of exact decimal strings.
+    ///
+    /// This is lossy, as a `Float64` can't represent all 34 significant digits of a `Decimal128`.
+    pub fn with_decimal128_as_float(mut self, decimal128_as_float: bool) -> Self {
+        self.conversion.decimal128_as_float = decimal128_as_float;
+        self
+    }
+
+    /// Set how `ObjectId` values are represented. Defaults to [`ObjectIdAs::Hex`].
+    pub fn with_object_id_as(mut self, object_id_as: ObjectIdAs) -> Self {
+        self.conversion.object_id_as = object_id_as;
+        self
+    }
+
+    /// Set how `Timestamp` values are represented. Defaults to [`TimestampAs::String`].
+    pub fn with_timestamp_as(mut self, timestamp_as: TimestampAs) -> Self {
+        self.conversion.timestamp_as = timestamp_as;
+        self
+    }
+
+    /// Set how regular expressions are represented. Defaults to [`RegexAs::String`].
+    pub fn with_regex_as(mut self, regex_as: RegexAs) -> Self {
+        self.conversion.regex_as = regex_as;
+        self
+    }
+
+    /// Set how UUIDs stored as `Binary` values are represented. Defaults to [`UuidAs::Binary`].
+    pub fn with_uuid_as(mut self, uuid_as: UuidAs) -> Self {
+        self.conversion.uuid_as = uuid_as;
+        self
+    }
+
+    /// Set how bson types without a polars counterpart are read.
+    /// Defaults to [`FallbackDtype::Utf8Json`].
+    pub fn with_fallback_dtype(mut self, fallback_dtype: FallbackDtype) -> Self {
+        self.conversion.fallback_dtype = fallback_dtype;
+        self
+    }
+
+    /// Add a `_raw` column with every document serialized as `raw_format`, alongside the
+    /// parsed columns. The whole documents are fetched, regardless of the selected columns.
+    pub fn with_include_raw(mut self, include_raw: bool) -> Self {
+        self.conversion.include_raw = include_raw;
+        self
+    }
+
+    /// Set how the documents of the `_raw` column are serialized.
+    /// Defaults to [`RawFormat::ExtendedJson`].
+    pub fn with_raw_format(mut self, raw_format: RawFormat) -> Self {
+        self.conversion.raw_format = raw_format;
+        self
+    }
+
+    /// Flatten nested documents into top-level columns named by their path, joined by
+    /// `separator`, e.g. `address.city`, instead of reading them as `Struct` columns.
+    pub fn with_flatten(mut self, separator: Option<String>) -> Self {
+        self.conversion.flatten = separator;
+        self
+    }
+
+    /// Time unit of the `Datetime` columns. Defaults to milliseconds, the precision of bson datetimes.
+    pub fn with_datetime_time_unit(mut self, time_unit: Option<TimeUnit>) -> Self {
+        self.conversion.time_unit = time_unit;
+        self
+    }
+
+    /// Set what happens with values that don't match the dtype of their column.
+    /// Defaults to [`ParseMode::Permissive`], which reads them as nulls.
+    pub fn with_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.conversion.parse_mode = parse_mode;
+        self
+    }
+
+    /// Add the counts of invalid values and skipped documents to `report` while scanning.
+    pub fn with_parse_report(mut self, report: Option<Arc<Mutex<ParseReport>>>) -> Self {
+        self.conversion.parse_report = report;
+        self
+    }
+
+    /// Only read the documents matching `filter`.
+    ///
+    /// When scanning an aggregation pipeline, the filter is applied to the pipeline output.
+    pub fn with_filter(mut self, filter: Option<Document>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sort the documents server side, e.g. `doc! {"ts": 1}`, so mongodb can use an index.
+    ///
+    /// A sorted scan is always partitioned with `skip`/`limit`, which keeps the order across threads.
+    pub fn with_sort(mut self, sort: Option<Document>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Return the rows in the same order on every scan, by sorting every partition on `_id`
+    /// after the `sort`, if any. The partitions are concatenated in the order they were split in.
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Let the server abort queries that run longer than `max_time` (`maxTimeMS`).
+    ///
+    /// The limit applies to every query on its own, so it bounds the time the server spends on
+    /// a single partition, not the whole scan.
+    pub fn with_max_time(mut self, max_time: Option<Duration>) -> Self {
+        self.max_time = max_time;
+        self
+    }
+
+    /// Abort the scan with an error once it has been running for longer than `timeout`.
+    ///
+    /// This bounds the whole scan, and is checked between documents. Combine it with
+    /// [`MongoScan::with_max_time`] to also bound a single slow query.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Let aggregations and sorted `find`s write temporary files on the server, instead of
+    /// failing once they exceed the memory limit of a stage.
+    pub fn with_allow_disk_use(mut self, allow_disk_use: bool) -> Self {
+        self.allow_disk_use = allow_disk_use;
+        self
+    }
+
+    /// Keep the cursors of the scan open while they are idle, instead of letting the server
+    /// close them after 10 minutes. The cursors are still closed once the scan finishes or fails.
+    pub fn with_no_cursor_timeout(mut self, no_cursor_timeout: bool) -> Self {
+        self.no_cursor_timeout = no_cursor_timeout;
+        self
+    }
+
+    /// Read the output of an aggregation `pipeline` instead of the raw collection.
+    pub fn with_pipeline(mut self, pipeline: Option<Vec<Document>>) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Replace these struct columns by their fields, as if `unnest` was called on the scan.
+    ///
+    /// The fields are regular columns of the scan, so only the selected ones are fetched.
+    pub fn with_unnest_columns(mut self, columns: Vec<String>) -> Self {
+        self.unnest_columns = columns;
+        self
+    }
+
+    /// Translate `predicate` into a mongodb filter so it is evaluated server side.
+    ///
+    /// `schema` is the schema of the scan, see [`AnonymousScan::schema`]. Comparisons with
+    /// columns that are not read from a single sampled bson type of the same kind, e.g. `Utf8`
+    /// columns of `Decimal128`s, are not translated.
+    ///
+    /// Returns the part of the predicate that could not be translated and still
+    /// needs to be applied by polars.
+    pub fn with_predicate(mut self, predicate: &Expr, schema: &Schema) -> (Self, Option<Expr>) {
+        let (filter, remaining) = self.split_predicate(predicate, schema);
+        self.filter = predicate::and_filters(self.filter.take(), filter);
+        (self, remaining)
+    }
+
+    /// [`MongoScan::with_predicate`] with `options.predicate`, inferring the schema first
+    /// if there is anything to translate.
+    pub(crate) fn with_options_predicate(
+        self,
+        options: &MongoScanOptions,
+    ) -> PolarsResult<(Self, Option<Expr>)> {
+        match &options.predicate {
+            Some(predicate) if self.translates_predicates() => {
+                let schema = self.schema(options.infer_schema_length)?;
+                Ok(self.with_predicate(predicate, &schema))
+            }
+            predicate => Ok((self, predicate.clone())),
+        }
+    }
+
+    /// Whether predicates on the columns can be evaluated as mongodb filters.
+    fn translates_predicates(&self) -> bool {
+        // the unnested columns don't name the fields they are read from.
+        self.conversion.filterable() && self.unnest_columns.is_empty()
+    }
+
+    /// Split `predicate` into a mongodb filter and the part polars has to evaluate.
+    pub(crate) fn split_predicate(
+        &self,
+        predicate: &Expr,
+        schema: &Schema,
+    ) -> (Option<Document>, Option<Expr>) {
+        if !self.translates_predicates() {
+            return (None, Some(predicate.clone()));
+        }
+        let source_types = self.source_types(schema);
+        let ctx = FilterContext {
+            schema,
+            source_types: &source_types,
+            conversion: &self.conversion,
+        };
+        predicate::split_predicate(predicate, &ctx)
+    }
+
+    /// Bson types of the fields sampled by schema inference, or those of the given `schema`
+    /// if it was not inferred, see [`predicate::given_source_types`].
+    pub(crate) fn source_types(&self, schema: &Schema) -> SourceTypes {
+        match self.source_types.get() {
+            Some(source_types) => source_types.clone(),
+            None => predicate::given_source_types(schema),
+        }
+    }
+
     pub fn new(connection_str: String, db: String, collection: String) -> PolarsResult<Self> {
-        let client_options = ClientOptions::parse(connection_str).map_err(|e| {
-            PolarsError::InvalidOperation(format!("unable to connect to mongodb: {}", e).into())
-        })?;
+        let client_options = ClientOptions::parse(connection_str)
+            .map_err(|err| PolarsMongoError::from_driver(err, format!("{}.{}", db, collection)))?;
+        Ok(Self::from_client_options(client_options, db, collection))
+    }
 
-        Ok(MongoScan {
+    /// Scan a collection with driver options built elsewhere, e.g. by [`MongoConnection`].
+    pub fn from_client_options(
+        client_options: ClientOptions,
+        db: String,
+        collection: String,
+    ) -> Self {
+        MongoScan {
             client_options,
+            client: OnceCell::new(),
             db,
             collection_name: collection,
             collection: None,
+            filter: None,
+            sort: None,
+            ordered: false,
+            pipeline: None,
+            schema: None,
+            dtype_overrides: None,
+            infer_schema_sample: false,
+            columns: None,
+            include_id: true,
+            categorical_columns: vec![],
+            unnest_columns: vec![],
+            nested_schema: OnceCell::new(),
+            source_types: OnceCell::new(),
+            stats: OnceCell::new(),
+            partition_strategy: None,
+            count_mode: CountMode::default(),
+            retry_policy: None,
+            snapshot: false,
+            causal_consistency: false,
+            on_progress: None,
+            cancellation: None,
+            throttle: Throttle::default(),
+            conversion: ConversionOptions::default(),
             n_threads: None,
             rechunk: false,
             batch_size: None,
+            target_batch_bytes: None,
+            max_time: None,
+            timeout: None,
+            allow_disk_use: false,
+            no_cursor_timeout: false,
+        }
+    }
+
+    /// Connect with `options.connection` if set, otherwise with `options.connection_str`.
+    pub(crate) fn from_options(options: &MongoScanOptions) -> PolarsResult<Self> {
+        match &options.connection {
+            Some(connection) => Ok(Self::from_client_options(
+                connection.client_options()?,
+                options.db.clone(),
+                options.collection.clone(),
+            )),
+            None => Self::new(
+                options.connection_str.clone(),
+                options.db.clone(),
+                options.collection.clone(),
+            ),
+        }
+    }
+
+    fn get_client(&self) -> PolarsResult<&Client> {
+        self.client.get_or_try_init(|| {
+            Client::with_options(self.client_options.clone()).map_err(|err| self.driver_error(err))
         })
     }
 
-    fn get_collection(&self) -> Collection<Document> {
-        let client = Client::with_options(self.client_options.clone()).unwrap();
+    fn get_database(&self) -> PolarsResult<Database> {
+        Ok(self.get_client()?.database(&self.db))
+    }
+
+    /// Partitions along the chunks of the collection, if it is sharded.
+    fn shard_chunk_partitions(&self, n_threads: usize) -> PolarsResult<Option<Vec<Partition>>> {
+        let config = self.get_client()?.database("config");
+        shard_chunk_partitions(&config, &self.namespace(), self.filter.as_ref(), n_threads)
+            .map_err(|err| self.driver_error(err))
+    }
+
+    fn get_collection(&self) -> PolarsResult<Collection<Document>> {
+        Ok(self
+            .get_database()?
+            .collection::<Document>(&self.collection_name))
+    }
+
+    /// The `db.collection` the scan reads from.
+    fn namespace(&self) -> String {
+        format!("{}.{}", self.db, self.collection_name)
+    }
+
+    /// Convert an error of the driver, with the collection it occurred on.
+    fn driver_error(&self, err: mongodb::error::Error) -> PolarsError {
+        PolarsMongoError::from_driver(err, self.namespace()).into()
+    }
+
+    /// Statistics of the whole collection from `collStats`, without `projected_memory`.
+    fn collection_stats(&self) -> PolarsResult<ScanEstimate> {
+        self.stats
+            .get_or_try_init(|| {
+                let stats = self
+                    .get_database()?
+                    .run_command(doc! {"collStats": &self.collection_name}, None)
+                    .map_err(|err| self.driver_error(err))?;
+                let number = |key: &str| match stats.get(key) {
+                    Some(Bson::Int32(n)) => *n as usize,
+                    Some(Bson::Int64(n)) => *n as usize,
+                    Some(Bson::Double(n)) => *n as usize,
+                    _ => 0,
+                };
+                Ok(ScanEstimate {
+                    count: number("count"),
+                    // empty collections don't report an average size at all.
+                    avg_document_size: number("avgObjSize"),
+                    total_bytes: number("size"),
+                    projected_memory: 0,
+                })
+            })
+            .copied()
+    }
 
-        let database = client.database(&self.db);
-        database.collection::<Document>(&self.collection_name)
+    /// Estimate the size of the scan from the `collStats` of the collection.
+    ///
+    /// The counts and sizes are those of the whole collection, regardless of the filter.
+    /// The projected memory is based on the schema of the scan, which is inferred if not
+    /// provided. Fails for views, and without the `collStats` privilege.
+    pub fn estimate(&self) -> PolarsResult<ScanEstimate> {
+        let mut estimate = self.collection_stats()?;
+        let schema = AnonymousScan::schema(self, Some(100))?;
+        estimate.projected_memory = estimate.count * row_size(&schema, estimate.avg_document_size);
+        Ok(estimate)
+    }
+
+    /// Number of threads of a scan without a fixed `n_threads`: one per 16 MiB of
+    /// the collection, up to the size of the thread pool.
+    fn default_n_threads(&self) -> usize {
+        let max_threads = POOL.current_num_threads();
+        match self.collection_stats() {
+            Ok(stats) => (stats.total_bytes / MIN_BYTES_PER_THREAD).clamp(1, max_threads),
+            Err(_) => max_threads,
+        }
+    }
+
+    /// Number of documents per cursor batch.
+    ///
+    /// Without a fixed `batch_size`, the batch size is derived from `target_batch_bytes` and the
+    /// average document size reported by `collStats`. This falls back to the server default if
+    /// the stats are unavailable, e.g. for views or without the `collStats` privilege.
+    fn effective_batch_size(&self) -> Option<u32> {
+        if let Some(batch_size) = self.batch_size {
+            return Some(batch_size as u32);
+        }
+        let target = self
+            .target_batch_bytes
+            .unwrap_or(DEFAULT_TARGET_BATCH_BYTES);
+        let avg_size = self.collection_stats().ok()?.avg_document_size;
+        if avg_size == 0 {
+            return None;
+        }
+        Some((target / avg_size).clamp(1, u32::MAX as usize) as u32)
     }
 
     fn parse_lines<'a>(
         &self,
-        mut cursor: Cursor<Document>,
+        cursor: Cursor<Document>,
         buffers: &mut PlIndexMap<String, Buffer<'a>>,
-    ) -> mongodb::error::Result<()> {
-        while let Some(Ok(doc)) = cursor.next() {
-            buffers.iter_mut().for_each(|(s, inner)| match doc.get(s) {
-                Some(v) => inner.add(v).expect("was not able to add to buffer."),
-                None => inner.add_null(),
-            });
+        deadline: Option<Instant>,
+        progress: &mut ProgressTracker,
+    ) -> Result<(), ReadError> {
+        let mut report = ParseReport::default();
+        for doc in cursor {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(self.timeout_error());
+            }
+            // dropping the cursor kills it on the server.
+            self.check_cancelled()?;
+            match doc {
+                Ok(doc) => {
+                    add_document(&doc, buffers, &self.conversion, &mut report)?;
+                    progress.add_document();
+                    self.throttle.document();
+                }
+                Err(err)
+                    if matches!(*err.kind, ErrorKind::BsonDeserialization(_))
+                        && self.conversion.parse_mode == ParseMode::SkipDocument =>
+                {
+                    report.skipped_documents += 1
+                }
+                Err(err) => return Err(ReadError::Driver(err)),
+            }
         }
+        self.conversion.report(report);
         Ok(())
     }
-}
 
-impl AnonymousScan for MongoScan {
-    fn scan(&self, scan_opts: AnonymousScanOptions) -> PolarsResult<DataFrame> {
-        let collection = &self.get_collection();
+    /// The current cluster time, that the partitions of a snapshot scan are all read at.
+    fn cluster_time(&self) -> PolarsResult<Timestamp> {
+        let response = self
+            .get_database()?
+            .run_command(doc! {"ping": 1}, None)
+            .map_err(|err| self.driver_error(err))?;
+        let cluster_time = response
+            .get_document("$clusterTime")
+            .and_then(|cluster_time| cluster_time.get_timestamp("clusterTime"));
+        match response.get_timestamp("operationTime").or(cluster_time) {
+            Ok(ts) => Ok(ts),
+            Err(_) => Err(PolarsError::InvalidOperation(
+                "snapshot scans need a replica set or a sharded cluster".into(),
+            )),
+        }
+    }
 
-        let projection = scan_opts.output_schema.clone().map(|schema| {
-            let prj = schema
-                .iter_names()
-                .map(|name| (name.clone(), Bson::Int64(1)));
+    /// The read concern the partitions are read with, if it pins them to a cluster time.
+    fn partition_read_concern(&self) -> PolarsResult<Option<Document>> {
+        if self.snapshot {
+            let cluster_time = self.cluster_time()?;
+            return Ok(Some(
+                doc! {"level": "snapshot", "atClusterTime": cluster_time},
+            ));
+        }
+        let last_write = self
+            .causal_consistency
+            .then(|| causal::last_write(&causal::cluster_key(&self.client_options)))
+            .flatten();
+        Ok(last_write.map(|operation_time| {
+            doc! {"level": "majority", "afterClusterTime": operation_time}
+        }))
+    }
 
-            Document::from_iter(prj)
-        });
+    /// Read a partition with a `read_concern` that pins it to a cluster time.
+    ///
+    /// The driver can only pin the reads of a session to a cluster time, and sessions can't be
+    /// shared between threads, so the partition is read with raw `find` and `getMore` commands.
+    fn parse_pinned<'a>(
+        &self,
+        filter: Option<Document>,
+        find_options: &FindOptions,
+        read_concern: &Document,
+        buffers: &mut PlIndexMap<String, Buffer<'a>>,
+        deadline: Option<Instant>,
+        progress: &mut ProgressTracker,
+    ) -> Result<(), ReadError> {
+        let db = self.get_database()?;
+        let mut find = doc! {
+            "find": &self.collection_name,
+            "filter": filter.unwrap_or_default(),
+            "readConcern": read_concern.clone(),
+        };
+        if let Some(projection) = &find_options.projection {
+            find.insert("projection", projection.clone());
+        }
+        if let Some(sort) = &find_options.sort {
+            find.insert("sort", sort.clone());
+        }
+        if let Some(skip) = find_options.skip {
+            find.insert("skip", skip as i64);
+        }
+        if let Some(limit) = find_options.limit {
+            find.insert("limit", limit);
+        }
+        if let Some(batch_size) = find_options.batch_size {
+            find.insert("batchSize", batch_size as i32);
+        }
+        if let Some(max_time) = find_options.max_time {
+            find.insert("maxTimeMS", max_time.as_millis() as i64);
+        }
+        if let Some(allow_disk_use) = find_options.allow_disk_use {
+            find.insert("allowDiskUse", allow_disk_use);
+        }
+
+        let invalid_response =
+            |err| PolarsError::ComputeError(format!("invalid cursor response: {}", err).into());
+        let mut report = ParseReport::default();
+        let mut response = db.run_command(find, None)?;
+        let mut batch = "firstBatch";
+        loop {
+            let cursor = response.get_document("cursor").map_err(invalid_response)?;
+            for doc in cursor.get_array(batch).map_err(invalid_response)? {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Err(self.timeout_error().into());
+                }
+                if let Bson::Document(doc) = doc {
+                    add_document(doc, buffers, &self.conversion, &mut report)?;
+                    progress.add_document();
+                    self.throttle.document();
+                }
+            }
+            let id = cursor.get_i64("id").map_err(invalid_response)?;
+            if id == 0 {
+                break;
+            }
+            if let Err(err) = self.check_cancelled() {
+                // raw cursors are not killed on drop.
+                let kill = doc! {"killCursors": &self.collection_name, "cursors": [id]};
+                let _ = db.run_command(kill, None);
+                return Err(err.into());
+            }
+            let mut get_more = doc! {"getMore": id, "collection": &self.collection_name};
+            if let Some(batch_size) = find_options.batch_size {
+                get_more.insert("batchSize", batch_size as i32);
+            }
+            response = db.run_command(get_more, None)?;
+            batch = "nextBatch";
+        }
+        self.conversion.report(report);
+        Ok(())
+    }
+
+    /// Run `read`, and run it again after transient errors if there is a retry policy.
+    fn with_retries<T>(&self, mut read: impl FnMut() -> Result<T, ReadError>) -> PolarsResult<T> {
+        let res = match &self.retry_policy {
+            Some(policy) => policy.run(read),
+            None => read(),
+        };
+        res.map_err(|err| match err {
+            ReadError::Driver(err) => self.driver_error(err),
+            ReadError::Polars(err) => err,
+        })
+    }
+
+    fn aggregate_options(&self) -> AggregateOptions {
+        AggregateOptions::builder()
+            .max_time(self.max_time)
+            .allow_disk_use(self.allow_disk_use.then_some(true))
+            .build()
+    }
+
+    /// Average document size the bytes of the progress updates are estimated from.
+    fn progress_document_size(&self) -> usize {
+        match &self.on_progress {
+            Some(_) => self
+                .collection_stats()
+                .map(|stats| stats.avg_document_size)
+                .unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    /// Fail with [`PolarsMongoError::Cancelled`] if the cancellation token is cancelled.
+    pub(crate) fn check_cancelled(&self) -> PolarsResult<()> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(PolarsMongoError::Cancelled {
+                namespace: self.namespace(),
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn timeout_error(&self) -> PolarsError {
+        PolarsError::ComputeError(
+            format!(
+                "scan of {} did not finish within {:?}",
+                self.namespace(),
+                self.timeout.unwrap_or_default()
+            )
+            .into(),
+        )
+    }
+
+    /// Leave `_id` out of the projection `prj`, if it is not read.
+    pub(crate) fn project_id(&self, prj: &mut Document) {
+        if !self.include_id {
+            prj.insert("_id", Bson::Int32(0));
+        }
+    }
+
+    /// Projection of the fields in `columns`, if only those are read.
+    fn columns_projection(&self) -> Option<Document> {
+        let mut prj = Document::new();
+        for name in self.columns.iter().flatten() {
+            for path in self.conversion.flattened_paths(name) {
+                prj.insert(path, Bson::Int64(1));
+            }
+        }
+        self.project_id(&mut prj);
+        (!prj.is_empty()).then_some(prj)
+    }
+
+    /// The columns of `schema` that are read: those in `columns`, in the order of `columns`,
+    /// and `_id` only if it is included.
+    fn select_columns(&self, schema: Schema) -> Schema {
+        let schema = match &self.columns {
+            Some(columns) => columns
+                .iter()
+                .filter_map(|name| schema.get_field(name))
+                .collect(),
+            None => schema,
+        };
+        let mut schema: Schema = match self.include_id {
+            true => schema,
+            false => schema
+                .iter_fields()
+                .filter(|fld| fld.name() != "_id")
+                .collect(),
+        };
+        if self.conversion.include_raw {
+            schema.with_column(RAW_COLUMN.to_string(), self.conversion.raw_dtype());
+        }
+        schema
+    }
+
+    /// Sort of every query of the scan. Ordered scans break ties of the `sort` on `_id`,
+    /// which is unique, so the order of the documents is fully determined.
+    pub(crate) fn scan_sort(&self) -> Option<Document> {
+        let mut sort = self.sort.clone();
+        if self.ordered {
+            let sort = sort.get_or_insert_with(Document::new);
+            if !sort.contains_key("_id") {
+                sort.insert("_id", 1);
+            }
+        }
+        sort
+    }
+
+    /// Projection of a scan with the given options, or `None` to fetch whole documents.
+    fn scan_projection(&self, scan_opts: &AnonymousScanOptions) -> Option<Document> {
+        let mut prj = match (&scan_opts.output_schema, &self.columns) {
+            (Some(schema), _) => projection(schema, &self.conversion),
+            (None, Some(_)) => projection(&scan_opts.schema, &self.conversion),
+            (None, None) if !self.include_id => Document::new(),
+            (None, None) => return None,
+        };
+        self.project_id(&mut prj);
+        Some(prj)
+    }
+
+    /// Schema of the documents, before any columns are unnested.
+    fn collection_schema(&self, infer_schema_length: Option<usize>) -> PolarsResult<Schema> {
+        if let Some(schema) = &self.schema {
+            return Ok(self.select_columns(schema.clone()));
+        }
+        let collection = self.get_collection()?;
+
+        let infer_options = FindOptions::builder()
+            .limit(infer_schema_length.map(|i| i as i64))
+            .projection(self.columns_projection())
+            .build();
+        let project_stage = self.columns_projection().map(|prj| doc! {"$project": prj});
+
+        let res = match &self.pipeline {
+            _ if self.infer_schema_sample => {
+                let mut pipeline = self.pipeline.clone().unwrap_or_default();
+                if let Some(filter) = &self.filter {
+                    pipeline.push(doc! {"$match": filter.clone()});
+                }
+                let size = infer_schema_length.unwrap_or(100) as i64;
+                pipeline.push(doc! {"$sample": {"size": size}});
+                pipeline.extend(project_stage);
+                collection.aggregate(pipeline, self.aggregate_options())
+            }
+            Some(pipeline) => {
+                let mut pipeline = pipeline.clone();
+                if let Some(n) = infer_schema_length {
+                    pipeline.push(doc! {"$limit": n as i64});
+                }
+                pipeline.extend(project_stage);
+                collection.aggregate(pipeline, self.aggregate_options())
+            }
+            None => collection.find(self.filter.clone(), Some(infer_options)),
+        }
+        .and_then(|cursor| cursor.collect::<mongodb::error::Result<Vec<_>>>())
+        .map_err(|err| self.driver_error(err))?;
+
+        let infer_schema_length = infer_schema_length.unwrap_or(100);
+        let _ = self.source_types.set(infer_source_types(
+            res.iter(),
+            infer_schema_length,
+            &self.conversion,
+        ));
+        let mut schema = self.select_columns(infer_documents_schema(
+            res.iter(),
+            infer_schema_length,
+            &self.conversion,
+        ));
+        apply_dtype_overrides(&mut schema, self.dtype_overrides.as_deref());
+        apply_categorical_columns(&mut schema, &self.categorical_columns);
+        Ok(schema)
+    }
+
+    /// Scan the documents of the collection, or the output of the pipeline.
+    fn scan_documents(&self, scan_opts: AnonymousScanOptions) -> PolarsResult<DataFrame> {
+        if let Some(pipeline) = &self.pipeline {
+            return self.scan_pipeline(pipeline, scan_opts);
+        }
+        let deadline = self.deadline();
+        let start = Instant::now();
+        let collection = &self.get_collection()?;
+
+        let single_cursor = scan_opts
+            .n_rows
+            .filter(|n_rows| *n_rows <= SINGLE_CURSOR_MAX_ROWS);
 
         let mut find_options = FindOptions::default();
-        find_options.projection = projection;
-        find_options.batch_size = self.batch_size.map(|b| b as u32);
+        find_options.projection = self.scan_projection(&scan_opts);
+        find_options.batch_size = match single_cursor {
+            Some(n_rows) if self.batch_size.is_none() => Some(n_rows.max(1) as u32),
+            _ => self.effective_batch_size(),
+        };
+        find_options.sort = self.scan_sort();
+        find_options.max_time = self.max_time;
+        find_options.allow_disk_use = self.allow_disk_use.then_some(true);
+        find_options.no_cursor_timeout = self.no_cursor_timeout.then_some(true);
 
         let schema = scan_opts.output_schema.unwrap_or(scan_opts.schema);
+        let offset = scan_opts.skip_rows.unwrap_or(0);
 
         // if no n_rows we need to get the count from mongo.
-        let n_rows = scan_opts
-            .n_rows
-            .unwrap_or_else(|| collection.estimated_document_count(None).unwrap() as usize);
+        let n_rows = match scan_opts.n_rows {
+            // mongodb treats a limit of 0 as no limit at all.
+            Some(0) => {
+                let buffers = init_buffers(schema.as_ref(), 0)?;
+                return DataFrame::new(
+                    buffers
+                        .into_values()
+                        .map(|buf| buf.into_series())
+                        .collect::<PolarsResult<_>>()?,
+                );
+            }
+            Some(n_rows) => n_rows,
+            None => self.count(None)?.saturating_sub(offset),
+        };
 
-        let mut n_threads = self.n_threads.unwrap_or_else(|| POOL.current_num_threads());
+        let n_threads = match single_cursor {
+            Some(_) => 1,
+            None if n_rows < 128 => 1,
+            None => self.n_threads.unwrap_or_else(|| self.default_n_threads()),
+        };
 
-        if n_rows < 128 {
-            n_threads = 1
+        // a range partition can't be limited to `n_rows`, so slices always use skip/limit.
+        // so are sorted scans, as `_id` ranges don't follow the sort order.
+        let strategy = match (self.partition_strategy, scan_opts.n_rows) {
+            _ if self.sort.is_some() => PartitionStrategy::SkipLimit,
+            (_, Some(_)) => PartitionStrategy::SkipLimit,
+            (Some(strategy), None) => strategy,
+            (None, None) if n_threads > 1 && n_rows >= ID_RANGE_MIN_ROWS => {
+                PartitionStrategy::IdRange
+            }
+            (None, None) => PartitionStrategy::SkipLimit,
+        };
+        // chunk ranges can't skip a number of documents, so scans with an offset are split
+        // by `_id` range or with skip/limit.
+        let partitions = match strategy {
+            PartitionStrategy::ShardChunks if n_threads > 1 && offset == 0 => {
+                self.shard_chunk_partitions(n_threads)?
+            }
+            // sharded collections are split on their chunks, if the cluster metadata is readable.
+            PartitionStrategy::IdRange
+                if n_threads > 1 && offset == 0 && self.partition_strategy.is_none() =>
+            {
+                match self.shard_chunk_partitions(n_threads).ok().flatten() {
+                    Some(partitions) => Some(partitions),
+                    None => id_range_partitions(collection, self.filter.as_ref(), 0, n_threads)?,
+                }
+            }
+            PartitionStrategy::IdRange if n_threads > 1 => {
+                id_range_partitions(collection, self.filter.as_ref(), offset, n_threads)?
+            }
+            _ => None,
         }
+        .unwrap_or_else(|| {
+            skip_limit_partitions(
+                self.filter.as_ref(),
+                offset,
+                n_rows,
+                n_threads,
+                scan_opts.n_rows.is_some(),
+            )
+        });
 
-        let rows_per_thread = n_rows / n_threads;
+        let rows_per_partition = n_rows / partitions.len();
+        let read_concern = self.partition_read_concern()?;
 
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            partitions = partitions.len(),
+            n_threads,
+            ?strategy,
+            "partitioned scan"
+        );
+        // the worker threads don't inherit the span of the scan.
+        #[cfg(feature = "tracing")]
+        let scan_span = tracing::Span::current();
+
+        let avg_document_size = self.progress_document_size();
         let dfs = POOL.install(|| {
-            (0..n_threads)
+            partitions
                 .into_par_iter()
-                .map(|idx| {
+                .enumerate()
+                .map(|(worker, partition)| {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::debug_span!(
+                        parent: &scan_span,
+                        "mongo_partition",
+                        skip = ?partition.skip,
+                        limit = ?partition.limit
+                    )
+                    .entered();
+                    #[cfg(feature = "tracing")]
+                    let partition_start = Instant::now();
                     let mut find_options = find_options.clone();
+                    find_options.skip = partition.skip;
+                    find_options.limit = partition.limit;
 
-                    let start = idx * rows_per_thread;
+                    // a failed partition is read again from the start.
+                    let df = self.with_retries(|| {
+                        let _in_flight = self.throttle.enter();
+                        let mut buffers = init_buffers(schema.as_ref(), rows_per_partition)?;
+                        let mut progress = ProgressTracker::new(
+                            self.on_progress.as_ref(),
+                            worker,
+                            start,
+                            avg_document_size,
+                        );
+                        let filter = partition.filter.clone();
+                        match &read_concern {
+                            Some(read_concern) => self.parse_pinned(
+                                filter,
+                                &find_options,
+                                read_concern,
+                                &mut buffers,
+                                deadline,
+                                &mut progress,
+                            )?,
+                            None => {
+                                let cursor = collection.find(filter, Some(find_options.clone()))?;
+                                self.parse_lines(cursor, &mut buffers, deadline, &mut progress)?;
+                            }
+                        }
+                        progress.finish();
 
-                    find_options.skip = Some(start as u64);
-                    find_options.limit = Some(rows_per_thread as i64);
-                    let cursor = collection.find(None, Some(find_options));
-                    let mut buffers = init_buffers(schema.as_ref(), rows_per_thread)?;
-
-                    self.parse_lines(cursor.unwrap(), &mut buffers)
-                        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
-
-                    DataFrame::new(
-                        buffers
-                            .into_values()
-                            .map(|buf| buf.into_series())
-                            .collect::<PolarsResult<_>>()?,
-                    )
+                        #[cfg(feature = "tracing")]
+                        let _span = tracing::trace_span!("build_columns").entered();
+                        Ok(DataFrame::new(
+                            buffers
+                                .into_values()
+                                .map(|buf| buf.into_series())
+                                .collect::<PolarsResult<_>>()?,
+                        )?)
+                    })?;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        documents = df.height(),
+                        elapsed_ms = partition_start.elapsed().as_millis() as u64,
+                        "partition read"
+                    );
+                    Ok(df)
                 })
                 .collect::<PolarsResult<Vec<_>>>()
         })?;
+        // the partitions are read in parallel, but collected in the order they were split in.
         let mut df = accumulate_dataframes_vertical(dfs)?;
 
         if self.rechunk {
@@ -166,27 +1217,789 @@ impl AnonymousScan for MongoScan {
         Ok(df)
     }
 
-    fn schema(&self, infer_schema_length: Option<usize>) -> PolarsResult<Schema> {
-        let collection = self.get_collection();
+    /// Scan the struct columns the selected unnested columns are read from, and unnest them.
+    fn scan_unnested(&self, mut scan_opts: AnonymousScanOptions) -> PolarsResult<DataFrame> {
+        let nested = self
+            .nested_schema
+            .get_or_try_init(|| self.collection_schema(None))?;
+        let output = match scan_opts.output_schema.take() {
+            Some(output) => output,
+            None => scan_opts.schema.clone(),
+        };
+        let read = nest_schema(nested, &output, &self.unnest_columns);
+        let structs = read
+            .iter()
+            .filter(|(name, dtype)| {
+                matches!(dtype, DataType::Struct(_)) && self.unnest_columns.contains(name)
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        scan_opts.output_schema = Some(Arc::new(read));
 
-        let infer_options = FindOptions::builder()
-            .limit(infer_schema_length.map(|i| i as i64))
-            .build();
+        let df = self.scan_documents(scan_opts)?;
+        df.unnest(structs)?.select(output.iter_names())
+    }
 
-        let res = collection
-            .find(None, Some(infer_options))
-            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
-        let iter = res.map(|doc| {
-            let val = doc.unwrap();
-            val.into_iter()
-                .map(|(key, value)| {
-                    let dtype = Wrap::<DataType>::from(&value);
-                    (key, dtype.0)
-                })
-                .collect()
+    /// Run the aggregation pipeline on a single cursor.
+    ///
+    /// The pipeline output can't be partitioned up front, so the projection,
+    /// filter and row limit are appended as extra stages instead.
+    fn scan_pipeline(
+        &self,
+        pipeline: &[Document],
+        scan_opts: AnonymousScanOptions,
+    ) -> PolarsResult<DataFrame> {
+        let deadline = self.deadline();
+        let collection = self.get_collection()?;
+
+        let mut pipeline = pipeline.to_vec();
+        if let Some(filter) = &self.filter {
+            pipeline.push(doc! {"$match": filter.clone()});
+        }
+        if let Some(sort) = self.scan_sort() {
+            pipeline.push(doc! {"$sort": sort});
+        }
+        if let Some(skip) = scan_opts.skip_rows {
+            pipeline.push(doc! {"$skip": skip as i64});
+        }
+        if let Some(n_rows) = scan_opts.n_rows {
+            pipeline.push(doc! {"$limit": n_rows as i64});
+        }
+        if let Some(prj) = self.scan_projection(&scan_opts) {
+            pipeline.push(doc! {"$project": prj});
+        }
+
+        let mut options = self.aggregate_options();
+        options.batch_size = self.batch_size.map(|b| b as u32);
+
+        let schema = scan_opts.output_schema.unwrap_or(scan_opts.schema);
+        let start = Instant::now();
+        let mut df = self.with_retries(|| {
+            let cursor = collection.aggregate(pipeline.clone(), Some(options.clone()))?;
+            let mut buffers = init_buffers(schema.as_ref(), scan_opts.n_rows.unwrap_or(1024))?;
+            // the size of the pipeline output is unknown, so no bytes are reported.
+            let mut progress = ProgressTracker::new(self.on_progress.as_ref(), 0, start, 0);
+            self.parse_lines(cursor, &mut buffers, deadline, &mut progress)?;
+            progress.finish();
+
+            Ok(DataFrame::new(
+                buffers
+                    .into_values()
+                    .map(|buf| buf.into_series())
+                    .collect::<PolarsResult<_>>()?,
+            )?)
+        })?;
+        if self.rechunk {
+            df.rechunk();
+        }
+        Ok(df)
+    }
+
+    /// Count the documents matching the filter without fetching them.
+    ///
+    /// Without a filter and with [`CountMode::Estimated`], the count is taken from the
+    /// collection metadata with `estimated_document_count`, otherwise `count_documents`
+    /// is used. The count of an
+    /// aggregation pipeline is computed server side with an extra `$count` stage.
+    /// Counting stops at `n_rows` if provided.
+    pub fn count(&self, n_rows: Option<usize>) -> PolarsResult<usize> {
+        let collection = self.get_collection()?;
+
+        let count = match (&self.pipeline, &self.filter) {
+            (Some(pipeline), filter) => {
+                let mut pipeline = pipeline.clone();
+                if let Some(filter) = filter {
+                    pipeline.push(doc! {"$match": filter.clone()});
+                }
+                if let Some(n_rows) = n_rows {
+                    pipeline.push(doc! {"$limit": n_rows as i64});
+                }
+                pipeline.push(doc! {"$count": "count"});
+                let options = self.aggregate_options();
+                collection
+                    .aggregate(pipeline, Some(options))
+                    .and_then(|mut cursor| cursor.next().transpose())
+                    // `$count` returns no document at all for an empty input.
+                    .map(|doc| match doc.as_ref().and_then(|doc| doc.get("count")) {
+                        Some(Bson::Int32(n)) => *n as u64,
+                        Some(Bson::Int64(n)) => *n as u64,
+                        _ => 0,
+                    })
+            }
+            (None, filter) if filter.is_some() || self.count_mode == CountMode::Exact => {
+                let options = CountOptions::builder()
+                    .limit(n_rows.map(|n| n as u64))
+                    .max_time(self.max_time)
+                    .build();
+                collection.count_documents(filter.clone(), Some(options))
+            }
+            (None, _) => collection
+                .estimated_document_count(
+                    EstimatedDocumentCountOptions::builder()
+                        .max_time(self.max_time)
+                        .build(),
+                )
+                .map(|count| n_rows.map_or(count, |n| count.min(n as u64))),
+        }
+        .map_err(|err| self.driver_error(err))?;
+        Ok(count as usize)
+    }
+
+    /// Read the distinct values of `field` matching the filter into a [`Series`].
+    ///
+    /// The values are deduplicated server side with the `distinct` command, or with a
+    /// `$group` stage when scanning an aggregation pipeline, so only the unique values are
+    /// sent over the network. Like the `distinct` command, the elements of array fields are
+    /// returned as separate values. The dtype is inferred from the values if not provided.
+    pub fn distinct(&self, field: &str, dtype: Option<DataType>) -> PolarsResult<Series> {
+        let collection = self.get_collection()?;
+
+        let values = match &self.pipeline {
+            Some(pipeline) => {
+                let mut pipeline = pipeline.clone();
+                if let Some(filter) = &self.filter {
+                    pipeline.push(doc! {"$match": filter.clone()});
+                }
+                pipeline.push(doc! {"$group": {"_id": format!("${}", field)}});
+                let options = self.aggregate_options();
+                collection
+                    .aggregate(pipeline, Some(options))
+                    .and_then(|cursor| {
+                        cursor
+                            .map(|doc| doc.map(|mut doc| doc.remove("_id").unwrap_or(Bson::Null)))
+                            .collect()
+                    })
+            }
+            None => {
+                let options = DistinctOptions::builder().max_time(self.max_time).build();
+                collection.distinct(field, self.filter.clone(), Some(options))
+            }
+        }
+        .map_err(|err| self.driver_error(err))?;
+
+        let n_values = values.len();
+        let schema = dtype.map(|dtype| Schema::from(vec![Field::new(field, dtype)].into_iter()));
+        let mut docs = values.into_iter().map(|value| doc! {field: value});
+        let df = read_documents(
+            || Ok(docs.next()),
+            schema,
+            Some(n_values),
+            None,
+            &self.conversion,
+        )?;
+        match df.column(field) {
+            Ok(s) => Ok(s.clone()),
+            // nothing to infer a dtype from.
+            Err(_) => Ok(Series::new_empty(field, &DataType::Null)),
+        }
+    }
+
+    /// The greatest stored value of `field` among the documents matching the filter, sorted by
+    /// `field` and sliced by `skip_rows` and `n_rows`, or `None` if there are none.
+    fn max_value(
+        &self,
+        field: &str,
+        skip_rows: Option<usize>,
+        n_rows: Option<usize>,
+    ) -> PolarsResult<Option<Bson>> {
+        let collection = self.get_collection()?;
+        let pipeline = self.max_value_pipeline(field, skip_rows, n_rows);
+        let docs: Vec<Document> = collection
+            .aggregate(pipeline, Some(self.aggregate_options()))
+            .and_then(|cursor| cursor.collect())
+            .map_err(|err| self.driver_error(err))?;
+        Ok(docs
+            .into_iter()
+            .next()
+            .and_then(|mut doc| doc.remove("max"))
+            .filter(|max| *max != Bson::Null))
+    }
+
+    /// Pipeline of [`MongoScan::max_value`], grouping the sliced documents into their `max`.
+    fn max_value_pipeline(
+        &self,
+        field: &str,
+        skip_rows: Option<usize>,
+        n_rows: Option<usize>,
+    ) -> Vec<Document> {
+        let mut pipeline = vec![];
+        if let Some(filter) = &self.filter {
+            pipeline.push(doc! {"$match": filter.clone()});
+        }
+        pipeline.push(doc! {"$sort": {field: 1}});
+        if let Some(skip_rows) = skip_rows {
+            pipeline.push(doc! {"$skip": skip_rows as i64});
+        }
+        if let Some(n_rows) = n_rows {
+            pipeline.push(doc! {"$limit": n_rows as i64});
+        }
+        pipeline.push(doc! {"$group": {"_id": Bson::Null, "max": {"$max": format!("${}", field)}}});
+        pipeline
+    }
+
+    /// Count the documents matching the filter per value of `field`, into a frame of `field`
+    /// and `counts` sorted by descending count, like [`Series::value_counts`].
+    ///
+    /// The values are counted server side with a `$sortByCount` stage, so only the frequency
+    /// table is sent over the network. Missing fields are counted as nulls, and arrays are
+    /// counted as a whole. The dtype of `field` is inferred from the values if not provided.
+    pub fn value_counts(&self, field: &str, dtype: Option<DataType>) -> PolarsResult<DataFrame> {
+        let collection = self.get_collection()?;
+
+        let mut pipeline = self.pipeline.clone().unwrap_or_default();
+        if let Some(filter) = &self.filter {
+            pipeline.push(doc! {"$match": filter.clone()});
+        }
+        pipeline.push(doc! {"$sortByCount": {"$ifNull": [format!("${}", field), Bson::Null]}});
+        pipeline.push(doc! {"$project": {"_id": 0, field: "$_id", "counts": "$count"}});
+        let docs: Vec<Document> = collection
+            .aggregate(pipeline, Some(self.aggregate_options()))
+            .and_then(|cursor| cursor.collect())
+            .map_err(|err| self.driver_error(err))?;
+
+        let n_values = docs.len();
+        let schema = dtype.map(|dtype| {
+            Schema::from(
+                vec![Field::new(field, dtype), Field::new("counts", IDX_DTYPE)].into_iter(),
+            )
         });
-        let schema = infer_schema(iter, infer_schema_length.unwrap_or(100));
-        Ok(schema)
+        let mut docs = docs.into_iter();
+        let df = read_documents(
+            || Ok(docs.next()),
+            schema,
+            Some(n_values),
+            None,
+            &self.conversion,
+        )?;
+        match (df.column(field), df.column("counts")) {
+            (Ok(values), Ok(counts)) => {
+                DataFrame::new(vec![values.clone(), counts.cast(&IDX_DTYPE)?])
+            }
+            // nothing to infer a dtype from.
+            _ => DataFrame::new(vec![
+                Series::new_empty(field, &DataType::Null),
+                Series::new_empty("counts", &IDX_DTYPE),
+            ]),
+        }
+    }
+
+    /// Fetch the first `n` documents of the collection with a single `find`.
+    ///
+    /// The schema is inferred from the returned documents themselves, so no
+    /// count or partitioning is done. This is meant for quick interactive exploration.
+    /// If no document matches and no schema is given, the frame has no columns.
+    pub fn head(&self, n: usize) -> PolarsResult<DataFrame> {
+        // mongodb treats a limit of 0 as no limit at all.
+        if n == 0 {
+            let schema = self.collection_schema(None)?;
+            return DataFrame::new(
+                init_buffers(&schema, 0)?
+                    .into_values()
+                    .map(|buf| buf.into_series())
+                    .collect::<PolarsResult<_>>()?,
+            );
+        }
+        let collection = self.get_collection()?;
+
+        let find_options = FindOptions::builder()
+            .limit(Some(n as i64))
+            .projection(self.columns_projection())
+            .batch_size(self.batch_size.map(|b| b as u32))
+            .sort(self.scan_sort())
+            .max_time(self.max_time)
+            .allow_disk_use(self.allow_disk_use.then_some(true))
+            .build();
+
+        let docs = collection
+            .find(self.filter.clone(), Some(find_options))
+            .and_then(|cursor| cursor.collect::<mongodb::error::Result<Vec<_>>>())
+            .map_err(|err| self.driver_error(err))?;
+
+        let schema = match &self.schema {
+            Some(schema) => self.select_columns(schema.clone()),
+            None => {
+                let mut schema =
+                    self.select_columns(infer_documents_schema(docs.iter(), n, &self.conversion));
+                apply_dtype_overrides(&mut schema, self.dtype_overrides.as_deref());
+                apply_categorical_columns(&mut schema, &self.categorical_columns);
+                schema
+            }
+        };
+        let mut buffers = init_buffers(&schema, docs.len())?;
+        let mut report = ParseReport::default();
+        for doc in &docs {
+            add_document(doc, &mut buffers, &self.conversion, &mut report)?;
+        }
+        self.conversion.report(report);
+
+        DataFrame::new(
+            buffers
+                .into_values()
+                .map(|buf| buf.into_series())
+                .collect::<PolarsResult<_>>()?,
+        )
+    }
+}
+
+/// Convert bson documents into a [`DataFrame`], with the same dtypes as a scan of a
+/// collection containing them.
+///
+/// The schema is inferred from all documents if it is not given.
+pub fn documents_to_df(docs: &[Document], schema: Option<Schema>) -> PolarsResult<DataFrame> {
+    let conversion = ConversionOptions::default();
+    let schema = match schema {
+        Some(schema) => schema,
+        None => infer_documents_schema(docs.iter(), docs.len(), &conversion),
+    };
+    let mut buffers = init_buffers(&schema, docs.len())?;
+    let mut report = ParseReport::default();
+    for doc in docs {
+        add_document(doc, &mut buffers, &conversion, &mut report)?;
+    }
+
+    DataFrame::new(
+        buffers
+            .into_values()
+            .map(|buf| buf.into_series())
+            .collect::<PolarsResult<_>>()?,
+    )
+}
+
+/// Construct dataframes from bson documents, e.g. `DataFrame::from_documents(&docs, None)`.
+pub trait MongoDataFrame: Sized {
+    /// See [`documents_to_df`].
+    fn from_documents(docs: &[Document], schema: Option<Schema>) -> PolarsResult<Self>;
+}
+
+impl MongoDataFrame for DataFrame {
+    fn from_documents(docs: &[Document], schema: Option<Schema>) -> PolarsResult<Self> {
+        documents_to_df(docs, schema)
+    }
+}
+
+/// Read the documents returned by `next_document` into a [`DataFrame`].
+///
+/// Only the first `infer_schema_length` documents are kept in memory to infer the schema,
+/// the remaining ones are added to the buffers as they are read.
+fn read_documents(
+    mut next_document: impl FnMut() -> PolarsResult<Option<Document>>,
+    schema: Option<Schema>,
+    infer_schema_length: Option<usize>,
+    n_rows: Option<usize>,
+    conversion: &ConversionOptions,
+) -> PolarsResult<DataFrame> {
+    let n_rows = n_rows.unwrap_or(usize::MAX);
+    let infer_schema_length = infer_schema_length.unwrap_or(100).min(n_rows);
+
+    let mut docs = Vec::with_capacity(infer_schema_length);
+    while docs.len() < infer_schema_length {
+        match next_document()? {
+            Some(doc) => docs.push(doc),
+            None => break,
+        }
+    }
+    let schema = match schema {
+        Some(schema) => schema,
+        None => infer_documents_schema(docs.iter(), infer_schema_length, conversion),
+    };
+
+    let mut buffers = init_buffers(&schema, docs.len())?;
+    let mut n_read = docs.len();
+    let mut report = ParseReport::default();
+    for doc in &docs {
+        add_document(doc, &mut buffers, conversion, &mut report)?;
+    }
+    while n_read < n_rows {
+        match next_document()? {
+            Some(doc) => add_document(&doc, &mut buffers, conversion, &mut report)?,
+            None => break,
+        }
+        n_read += 1;
+    }
+    conversion.report(report);
+
+    DataFrame::new(
+        buffers
+            .into_values()
+            .map(|buf| buf.into_series())
+            .collect::<PolarsResult<_>>()?,
+    )
+}
+
+fn add_document(
+    doc: &Document,
+    buffers: &mut PlIndexMap<String, Buffer>,
+    conversion: &ConversionOptions,
+    report: &mut ParseReport,
+) -> PolarsResult<()> {
+    let raw = doc;
+    let doc = conversion.resolve_fallbacks(conversion.flatten(doc))?;
+    if conversion.validate() {
+        let mut valid = true;
+        for (name, buf) in buffers.iter() {
+            match doc.get(name) {
+                Some(value) if !buf.accepts(value) => {
+                    if conversion.parse_mode == ParseMode::Strict {
+                        let id = doc.get("_id").map(|id| id.to_string());
+                        return Err(PolarsMongoError::SchemaMismatch(format!(
+                            "field '{}' has an unexpected {:?} value {} in the document with _id {}",
+                            name,
+                            value.element_type(),
+                            value,
+                            id.as_deref().unwrap_or("<missing>"),
+                        ))
+                        .into());
+                    }
+                    *report.invalid_values.entry(name.clone()).or_default() += 1;
+                    valid = false;
+                }
+                _ => {}
+            }
+        }
+        if !valid && conversion.parse_mode == ParseMode::SkipDocument {
+            report.skipped_documents += 1;
+            return Ok(());
+        }
+    }
+    for (name, buf) in buffers.iter_mut() {
+        if conversion.include_raw && name == RAW_COLUMN {
+            buf.add(&conversion.raw_value(raw)?)?;
+            continue;
+        }
+        match doc.get(name) {
+            Some(v) => buf.add(v)?,
+            None => buf.add_null(),
+        }
+    }
+    Ok(())
+}
+
+fn infer_documents_schema<'a>(
+    docs: impl Iterator<Item = &'a Document>,
+    infer_schema_length: usize,
+    conversion: &ConversionOptions,
+) -> Schema {
+    let mut schema = Schema::new();
+    for doc in docs.take(infer_schema_length) {
+        for (key, value) in conversion.flatten(doc).iter() {
+            let dtype = conversion.dtype(value);
+            let dtype = match schema.get(key) {
+                Some(existing) => merge_dtypes(existing, &dtype),
+                None => dtype,
+            };
+            schema.with_column(key.clone(), dtype);
+        }
+    }
+    // fields that were only ever null can't be inferred
+    Schema::from(
+        schema
+            .iter_fields()
+            .filter(|fld| fld.data_type() != &DataType::Null),
+    )
+}
+
+/// The bson types of the fields of the first `infer_schema_length` documents, `None` for the
+/// fields with values of several types, see [`predicate::source_type`].
+fn infer_source_types<'a>(
+    docs: impl Iterator<Item = &'a Document>,
+    infer_schema_length: usize,
+    conversion: &ConversionOptions,
+) -> SourceTypes {
+    let mut types = SourceTypes::default();
+    for doc in docs.take(infer_schema_length) {
+        for (key, value) in conversion.flatten(doc).iter() {
+            let source = match predicate::source_type(value) {
+                Some(source) => source,
+                None => continue,
+            };
+            let existing = types.entry(key.clone()).or_insert(Some(source));
+            if *existing != Some(source) {
+                *existing = None;
+            }
+        }
+    }
+    types
+}
+
+/// Infer the schema of in-memory documents, e.g. from a dump or a change stream, with the
+/// same dtypes as a scan of a collection containing them.
+///
+/// Only the first `length` documents are used, all of them if `None`. Documents nested
+/// more than `depth` levels deep are read as `Utf8` strings instead of `Struct` columns.
+pub fn infer_bson_schema(
+    docs: impl Iterator<Item = Document>,
+    depth: Option<usize>,
+    length: Option<usize>,
+) -> Schema {
+    let docs = docs.take(length.unwrap_or(usize::MAX)).collect::<Vec<_>>();
+    let schema = infer_documents_schema(docs.iter(), docs.len(), &ConversionOptions::default());
+    match depth {
+        Some(depth) => Schema::from(
+            schema
+                .iter_fields()
+                .map(|fld| Field::new(fld.name(), limit_depth(fld.data_type(), depth))),
+        ),
+        None => schema,
+    }
+}
+
+/// `dtype` with the structs nested more than `depth` levels deep replaced by `Utf8`.
+fn limit_depth(dtype: &DataType, depth: usize) -> DataType {
+    match dtype {
+        DataType::Struct(_) if depth == 0 => DataType::Utf8,
+        DataType::Struct(fields) => DataType::Struct(
+            fields
+                .iter()
+                .map(|fld| Field::new(fld.name(), limit_depth(fld.data_type(), depth - 1)))
+                .collect(),
+        ),
+        DataType::List(inner) => DataType::List(Box::new(limit_depth(inner, depth))),
+        dtype => dtype.clone(),
+    }
+}
+
+/// Add the dot notation paths of all fields in `dtype` to the projection.
+///
+/// Structs are projected field by field, so only the sub-fields in the schema
+/// are sent over the network. This also works for lists of structs, as mongodb
+/// applies the path to every document in the array.
+#[cfg(feature = "sync")]
+fn add_projection_paths(
+    path: String,
+    dtype: &DataType,
+    conversion: &ConversionOptions,
+    prj: &mut Document,
+) {
+    match dtype {
+        // an `ObjectId`, `Timestamp` or regex read as a struct is a single bson value.
+        DataType::Struct(_) if conversion.is_value_struct(dtype) => {
+            prj.insert(path, Bson::Int64(1));
+        }
+        DataType::Struct(fields) if !fields.is_empty() => {
+            for fld in fields {
+                add_projection_paths(
+                    format!("{}.{}", path, fld.name()),
+                    fld.data_type(),
+                    conversion,
+                    prj,
+                );
+            }
+        }
+        DataType::List(inner) if matches!(inner.as_ref(), DataType::Struct(_)) => {
+            add_projection_paths(path, inner, conversion, prj)
+        }
+        _ => {
+            prj.insert(path, Bson::Int64(1));
+        }
+    }
+}
+
+/// Projection document that only includes the (nested) fields in `schema`.
+#[cfg(feature = "sync")]
+fn projection(schema: &Schema, conversion: &ConversionOptions) -> Document {
+    // an empty projection fetches the whole documents, that the raw column is serialized from.
+    if conversion.include_raw && schema.get(RAW_COLUMN).is_some() {
+        return Document::new();
+    }
+    let mut prj = Document::new();
+    for (name, dtype) in schema.iter() {
+        for path in conversion.flattened_paths(name) {
+            add_projection_paths(path, dtype, conversion, &mut prj);
+        }
+    }
+    prj
+}
+
+/// Replace the struct columns in `columns` by their fields.
+fn unnest_schema(nested: &Schema, columns: &[String]) -> PolarsResult<Schema> {
+    let mut schema = Schema::with_capacity(nested.len());
+    for (name, dtype) in nested.iter() {
+        let fields = match dtype {
+            DataType::Struct(fields) if columns.contains(name) => fields.clone(),
+            _ => vec![Field::new(name, dtype.clone())],
+        };
+        for fld in fields {
+            if schema.get(fld.name()).is_some() {
+                return Err(PolarsError::Duplicate(
+                    format!(
+                        "column '{}' appears more than once after unnesting",
+                        fld.name()
+                    )
+                    .into(),
+                ));
+            }
+            schema.with_column(fld.name().clone(), fld.data_type().clone());
+        }
+    }
+    Ok(schema)
+}
+
+/// The columns of `nested` needed to read the unnested `output` columns,
+/// with structs limited to the selected fields.
+fn nest_schema(nested: &Schema, output: &Schema, columns: &[String]) -> Schema {
+    let mut schema = Schema::new();
+    for (name, dtype) in nested.iter() {
+        match dtype {
+            DataType::Struct(fields) if columns.contains(name) => {
+                let selected = fields
+                    .iter()
+                    .filter(|fld| output.get(fld.name()).is_some())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !selected.is_empty() {
+                    schema.with_column(name.clone(), DataType::Struct(selected));
+                }
+            }
+            _ if output.get(name).is_some() => schema.with_column(name.clone(), dtype.clone()),
+            _ => {}
+        }
+    }
+    schema
+}
+
+#[cfg(feature = "sync")]
+/// Approximate size in bytes of a row read with `schema`, from documents of `document_size` bytes.
+///
+/// Fixed width values take up the size of their dtype, variable width values an even share
+/// of the document.
+fn row_size(schema: &Schema, document_size: usize) -> usize {
+    let share = document_size / schema.len().max(1);
+    schema
+        .iter()
+        .map(|(_, dtype)| value_size(dtype, share))
+        .sum()
+}
+
+#[cfg(feature = "sync")]
+fn value_size(dtype: &DataType, share: usize) -> usize {
+    match dtype {
+        DataType::Boolean | DataType::Int8 | DataType::UInt8 => 1,
+        DataType::Int16 | DataType::UInt16 => 2,
+        DataType::Int32 | DataType::UInt32 | DataType::Float32 | DataType::Date => 4,
+        DataType::Categorical(_) => 4,
+        DataType::Int64
+        | DataType::UInt64
+        | DataType::Float64
+        | DataType::Datetime(_, _)
+        | DataType::Duration(_)
+        | DataType::Time => 8,
+        DataType::Struct(fields) => {
+            let share = share / fields.len().max(1);
+            fields
+                .iter()
+                .map(|fld| value_size(fld.data_type(), share))
+                .sum()
+        }
+        // the values plus an offset.
+        _ => share + 8,
+    }
+}
+
+#[cfg(feature = "sync")]
+/// `output` with the columns of `schema` that `predicate` reads and are not part of it yet.
+fn with_predicate_columns(output: &Schema, schema: &Schema, predicate: &Expr) -> SchemaRef {
+    let mut output = output.clone();
+    for expr in predicate {
+        if let Expr::Column(name) = expr {
+            if let (None, Some(dtype)) = (output.get(name), schema.get(name)) {
+                output.with_column(name.to_string(), dtype.clone());
+            }
+        }
+    }
+    Arc::new(output)
+}
+
+fn apply_dtype_overrides(schema: &mut Schema, dtype_overrides: Option<&[Field]>) {
+    for fld in dtype_overrides.unwrap_or_default() {
+        schema.with_column(fld.name().clone(), fld.data_type().clone());
+    }
+}
+
+fn apply_categorical_columns(schema: &mut Schema, columns: &[String]) {
+    for name in columns {
+        if let Some(DataType::Utf8) = schema.get(name) {
+            schema.with_column(name.clone(), DataType::Categorical(None));
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+impl AnonymousScan for MongoScan {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "mongo_scan",
+            skip_all,
+            fields(namespace = %self.namespace(), n_rows = ?scan_opts.n_rows)
+        )
+    )]
+    fn scan(&self, mut scan_opts: AnonymousScanOptions) -> PolarsResult<DataFrame> {
+        #[cfg(feature = "tracing")]
+        let start = Instant::now();
+        // the partitions are encoded separately, and can only be combined with a shared cache.
+        let _string_cache = (!self.categorical_columns.is_empty()).then(IUseStringCache::new);
+
+        // the filter of the query plan, pushed down by polars.
+        let (filter, remaining) = match scan_opts.predicate.take() {
+            Some(predicate) => self.split_predicate(&predicate, &scan_opts.schema),
+            None => (None, None),
+        };
+        let scan = match filter {
+            Some(filter) => Cow::Owned(MongoScan {
+                filter: predicate::and_filters(self.filter.clone(), Some(filter)),
+                ..self.clone()
+            }),
+            None => Cow::Borrowed(self),
+        };
+        // the rest of the filter is applied to the scanned rows, before they are sliced.
+        let output = scan_opts.output_schema.clone();
+        let slice = match &remaining {
+            Some(remaining) => {
+                scan_opts.output_schema = output
+                    .as_ref()
+                    .map(|output| with_predicate_columns(output, &scan_opts.schema, remaining));
+                Some((scan_opts.skip_rows.take(), scan_opts.n_rows.take()))
+            }
+            None => None,
+        };
+
+        let mut df = if scan.unnest_columns.is_empty() {
+            scan.scan_documents(scan_opts)?
+        } else {
+            scan.scan_unnested(scan_opts)?
+        };
+        if let (Some(remaining), Some((skip_rows, n_rows))) = (remaining, slice) {
+            df = df.lazy().filter(remaining).collect()?;
+            if let Some(output) = output {
+                df = df.select(output.iter_names())?;
+            }
+            df = df.slice(skip_rows.unwrap_or(0) as i64, n_rows.unwrap_or(usize::MAX));
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            rows = df.height(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "scan finished"
+        );
+        Ok(df)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "mongo_schema",
+            skip_all,
+            fields(namespace = %self.namespace(), infer_schema_length = ?infer_schema_length)
+        )
+    )]
+    fn schema(&self, infer_schema_length: Option<usize>) -> PolarsResult<Schema> {
+        if self.unnest_columns.is_empty() {
+            return self.collection_schema(infer_schema_length);
+        }
+        let nested = self
+            .nested_schema
+            .get_or_try_init(|| self.collection_schema(infer_schema_length))?;
+        unnest_schema(nested, &self.unnest_columns)
     }
 
     fn allows_predicate_pushdown(&self) -> bool {
@@ -205,31 +2018,852 @@ impl AnonymousScan for MongoScan {
 pub struct MongoScanOptions {
     /// mongodb style connection string. `mongodb://<user>:<password>@host.domain`
     pub connection_str: String,
+    /// Typed connection settings. Takes precedence over `connection_str` when provided.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub connection: Option<MongoConnection>,
     /// the name of the mongodb database
     pub db: String,
     /// the name of the mongodb collection
     pub collection: String,
     // Number of rows used to infer the schema. Defaults to `100` if not provided.
     pub infer_schema_length: Option<usize>,
+    /// Infer the schema from a random `$sample` of `infer_schema_length` documents
+    /// instead of the first documents in the collection.
+    pub infer_schema_sample: bool,
+    /// Only infer and fetch these fields, in this order. Unlike selecting columns on the
+    /// [`LazyFrame`], this also leaves the other fields out of schema inference.
+    pub columns: Option<Vec<String>>,
+    /// Read the `_id` field. Defaults to `true`, if `false` it is left out of the projection
+    /// and of the inferred schema.
+    pub include_id: bool,
+    /// Read these string fields as `Categorical` instead of `Utf8` columns, which stores
+    /// every distinct value once. Useful for status or enum-like fields.
+    pub categorical_columns: Vec<String>,
+    /// Schema of the collection. If provided, the schema is not inferred from the collection,
+    /// and predicates are only pushed down on its numeric and `Boolean` columns, see
+    /// [`MongoScan::with_schema`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub schema: Option<Schema>,
+    /// Overwrite the dtypes of these fields in the inferred schema, e.g. to read a field as
+    /// `Float64` that only contains integers in the documents used for inference.
+    /// Overriding a struct field with a subset of its fields only fetches those sub-fields,
+    /// e.g. `meta: Struct[ip]` projects `{"meta.ip": 1}`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub dtype_overrides: Option<Vec<Field>>,
     /// Number of rows to return from mongodb collection. If not provided, it will fetch all rows from collection.
     pub n_rows: Option<usize>,
+    /// Skip this many documents before reading `n_rows`, with `skip` on the server.
+    /// Unordered scans without `n_rows` may skip the first documents in `_id` order instead.
+    pub skip_rows: Option<usize>,
     /// determines the number of records to return from a single request to mongodb
     pub batch_size: Option<usize>,
+    /// Without a `batch_size`, size the batches to about this many bytes based on the
+    /// average document size of the collection. Defaults to 4 MiB.
+    pub target_batch_bytes: Option<usize>,
+    /// How the scan is split between threads. If not provided, large collections are split
+    /// by shard chunk if sharded and by `_id` range otherwise, and smaller ones with `skip`/`limit`.
+    pub partition_strategy: Option<PartitionStrategy>,
+    /// How the collection is counted before it is partitioned, if there is no filter.
+    /// Defaults to the fast but possibly inaccurate `estimated_document_count`.
+    pub count_mode: CountMode,
+    /// Retry partitions that fail with transient server errors. Defaults to no retries.
+    pub retry_policy: Option<RetryPolicy>,
+    /// Called with the progress of every worker of the scan, every 10,000 documents and
+    /// once its partition is read.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub on_progress: Option<ProgressCallback>,
+    /// Limit the documents read per second by all workers of the scan together.
+    pub max_docs_per_second: Option<u32>,
+    /// Limit the number of partitions that are read at the same time.
+    pub max_batches_in_flight: Option<usize>,
+    /// Stop the scan once this token is cancelled, closing its cursors.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancellation: Option<CancellationToken>,
+    /// Read all partitions at the same cluster time, so concurrent inserts and deletes can't
+    /// lead to duplicated or missing rows. Needs a replica set or sharded cluster.
+    pub snapshot: bool,
+    /// Read the documents written with causal consistency earlier in this program to the
+    /// same cluster. Needs a replica set or sharded cluster.
+    pub causal_consistency: bool,
+    /// Read `Decimal128` values as a lossy `Float64`.
+    /// By default they are read as `Utf8` decimal strings, which keep the exact value.
+    pub decimal128_as_float: bool,
+    /// How `ObjectId` values are represented. Defaults to a hex `Utf8` string.
+    pub object_id_as: ObjectIdAs,
+    /// How `Timestamp` values are represented. Defaults to a debug formatted `Utf8` string.
+    pub timestamp_as: TimestampAs,
+    /// How regular expressions are represented. Defaults to a `/pattern/options` `Utf8` string.
+    pub regex_as: RegexAs,
+    /// How UUIDs stored as `Binary` values are represented. Defaults to a `List(UInt8)` of the bytes.
+    pub uuid_as: UuidAs,
+    /// How bson types without a polars counterpart, like `MinKey`, are read.
+    /// Defaults to relaxed extended JSON `Utf8` strings.
+    pub fallback_dtype: FallbackDtype,
+    /// Add a `_raw` column with every document serialized as extended JSON or bson, e.g. to
+    /// audit the conversion or to reprocess fields that inference missed.
+    pub include_raw: bool,
+    /// How the documents of the `_raw` column are serialized. Defaults to extended JSON.
+    pub raw_format: RawFormat,
+    /// Time unit of the `Datetime` columns. Defaults to milliseconds, the precision of bson datetimes.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub datetime_time_unit: Option<TimeUnit>,
+    /// Flatten nested documents into top-level columns like `address.city` instead of `Struct` columns.
+    pub flatten: bool,
+    /// Separator of the flattened column names. Defaults to `.`.
+    /// With any other separator, predicates are evaluated by polars instead of mongodb.
+    pub flatten_separator: Option<String>,
+    /// Struct columns replaced by their fields after the scan, as if `unnest` was called on it.
+    /// Unlike `flatten`, the fields keep their own names and only the selected ones are fetched.
+    pub unnest_columns: Vec<String>,
+    /// Rename columns after the scan, e.g. to expose fields named `$type` as `type`.
+    /// The projection, `filter` and `predicate` still use the mongodb field names.
+    pub rename: Option<HashMap<String, String>>,
+    /// Error on values that don't match the dtype of their column, instead of reading them as nulls.
+    pub parse_mode: ParseMode,
+    /// Filled with the counts of invalid values per field and skipped documents during the scan.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub parse_report: Option<Arc<Mutex<ParseReport>>>,
+    /// mongodb filter document passed to `find`, so only matching documents are sent over the network.
+    pub filter: Option<Document>,
+    /// mongodb sort document, e.g. `{"ts": 1}`. The documents are sorted server side,
+    /// which can use an index instead of sorting the whole collection in polars.
+    pub sort: Option<Document>,
+    /// Return the rows in the same order on every scan. Every partition is sorted on `_id`
+    /// after `sort`, and the partitions are concatenated in order.
+    pub ordered: bool,
+    /// Add a column with the index of every row, as [`LazyFrame::with_row_count`] would.
+    /// Implies `ordered`, so the same document gets the same index on every scan.
+    /// Documents that don't match `filter` are not counted.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub row_count: Option<RowCount>,
+    /// Let the server abort every query of the scan that runs longer than this (`maxTimeMS`).
+    pub max_time_ms: Option<u64>,
+    /// Abort the whole scan with an error once it has been running for this long.
+    pub timeout_ms: Option<u64>,
+    /// Let aggregations and sorted scans write temporary files on the server (`allowDiskUse`).
+    pub allow_disk_use: bool,
+    /// Keep idle cursors open instead of letting the server close them after 10 minutes.
+    /// Useful for long scans that are slowed down by the consumer.
+    pub no_cursor_timeout: bool,
+    /// Predicate to filter the collection by. Supported expressions (comparisons, `is_null`,
+    /// `is_in`, `and`/`or`) are translated into a mongodb filter and evaluated server side.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub predicate: Option<Expr>,
 }
 
+impl Default for MongoScanOptions {
+    fn default() -> Self {
+        MongoScanOptions {
+            connection_str: String::new(),
+            connection: None,
+            db: String::new(),
+            collection: String::new(),
+            infer_schema_length: None,
+            infer_schema_sample: false,
+            columns: None,
+            include_id: true,
+            categorical_columns: vec![],
+            schema: None,
+            dtype_overrides: None,
+            n_rows: None,
+            skip_rows: None,
+            batch_size: None,
+            target_batch_bytes: None,
+            partition_strategy: None,
+            count_mode: CountMode::default(),
+            retry_policy: None,
+            on_progress: None,
+            max_docs_per_second: None,
+            max_batches_in_flight: None,
+            cancellation: None,
+            snapshot: false,
+            causal_consistency: false,
+            decimal128_as_float: false,
+            object_id_as: ObjectIdAs::default(),
+            timestamp_as: TimestampAs::default(),
+            regex_as: RegexAs::default(),
+            uuid_as: UuidAs::default(),
+            fallback_dtype: FallbackDtype::default(),
+            include_raw: false,
+            raw_format: RawFormat::default(),
+            datetime_time_unit: None,
+            flatten: false,
+            flatten_separator: None,
+            unnest_columns: vec![],
+            rename: None,
+            parse_mode: ParseMode::default(),
+            parse_report: None,
+            filter: None,
+            sort: None,
+            ordered: false,
+            row_count: None,
+            max_time_ms: None,
+            timeout_ms: None,
+            allow_disk_use: false,
+            no_cursor_timeout: false,
+            predicate: None,
+        }
+    }
+}
+
+impl MongoScanOptions {
+    /// Apply `rename` to the columns of a scan.
+    pub(crate) fn rename_columns(&self, lf: LazyFrame) -> LazyFrame {
+        match &self.rename {
+            Some(rename) => lf.rename(rename.keys(), rename.values()),
+            None => lf,
+        }
+    }
+
+    /// Separator of the flattened column names, if nested documents are flattened.
+    pub(crate) fn flattening(&self) -> Option<String> {
+        self.flatten
+            .then(|| self.flatten_separator.clone().unwrap_or_else(|| ".".into()))
+    }
+}
+
+#[cfg(feature = "sync")]
+fn scan_mongo(
+    options: MongoScanOptions,
+    pipeline: Option<Vec<Document>>,
+) -> PolarsResult<LazyFrame> {
+    Ok(scan_mongo_source(options, pipeline)?.lf)
+}
+
+#[cfg(feature = "sync")]
+/// A scan of a collection, with the [`MongoScan`] it reads from.
+pub(crate) struct MongoSource {
+    pub(crate) lf: LazyFrame,
+    pub(crate) scan: Arc<MongoScan>,
+    /// Schema of the scan, before the columns are renamed.
+    pub(crate) schema: SchemaRef,
+    /// The part of `options.predicate` that is evaluated by polars.
+    pub(crate) remaining: Option<Expr>,
+}
+
+#[cfg(feature = "sync")]
+pub(crate) fn scan_mongo_source(
+    options: MongoScanOptions,
+    pipeline: Option<Vec<Document>>,
+) -> PolarsResult<MongoSource> {
+    let f = MongoScan::from_options(&options)?
+        .with_batch_size(options.batch_size)
+        .with_target_batch_bytes(options.target_batch_bytes)
+        .with_filter(options.filter.clone())
+        .with_sort(options.sort.clone())
+        .with_ordered(options.ordered || options.row_count.is_some())
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_timeout(options.timeout_ms.map(Duration::from_millis))
+        .with_allow_disk_use(options.allow_disk_use)
+        .with_no_cursor_timeout(options.no_cursor_timeout)
+        .with_schema(options.schema.clone())
+        .with_dtype_overrides(options.dtype_overrides.clone())
+        .with_infer_schema_sample(options.infer_schema_sample)
+        .with_columns(options.columns.clone())
+        .with_include_id(options.include_id)
+        .with_categorical_columns(options.categorical_columns.clone())
+        .with_partition_strategy(options.partition_strategy)
+        .with_count_mode(options.count_mode)
+        .with_retry_policy(options.retry_policy.clone())
+        .with_on_progress(options.on_progress.clone())
+        .with_cancellation(options.cancellation.clone())
+        .with_max_docs_per_second(options.max_docs_per_second)
+        .with_max_batches_in_flight(options.max_batches_in_flight)
+        .with_snapshot(options.snapshot)
+        .with_causal_consistency(options.causal_consistency)
+        .with_decimal128_as_float(options.decimal128_as_float)
+        .with_object_id_as(options.object_id_as)
+        .with_timestamp_as(options.timestamp_as)
+        .with_regex_as(options.regex_as)
+        .with_uuid_as(options.uuid_as)
+        .with_fallback_dtype(options.fallback_dtype)
+        .with_include_raw(options.include_raw)
+        .with_raw_format(options.raw_format)
+        .with_flatten(options.flattening())
+        .with_datetime_time_unit(options.datetime_time_unit)
+        .with_parse_mode(options.parse_mode)
+        .with_parse_report(options.parse_report.clone())
+        .with_unnest_columns(options.unnest_columns.clone())
+        .with_pipeline(pipeline);
+
+    // a given schema describes the documents, not the unnested or selected columns.
+    let schema = options.schema.clone().filter(|_| {
+        options.unnest_columns.is_empty()
+            && options.columns.is_none()
+            && options.include_id
+            && !options.include_raw
+    });
+    // the predicate is translated against the schema of the scan, which is then passed on
+    // so it is not inferred twice.
+    let (f, remaining, schema) = match &options.predicate {
+        Some(predicate) if f.translates_predicates() => {
+            let schema = f.schema(options.infer_schema_length)?;
+            let (f, remaining) = f.with_predicate(predicate, &schema);
+            (f, remaining, Some(schema))
+        }
+        predicate => (f, predicate.clone(), schema),
+    };
+
+    let args = ScanArgsAnonymous {
+        name: "MONGO SCAN",
+        infer_schema_length: options.infer_schema_length,
+        schema,
+        // rows can only be limited at the source if all filtering happens server side.
+        n_rows: options.n_rows.filter(|_| remaining.is_none()),
+        skip_rows: options.skip_rows.filter(|_| remaining.is_none()),
+        row_count: options.row_count.clone(),
+        ..ScanArgsAnonymous::default()
+    };
+
+    let scan = Arc::new(f);
+    let mut lf = LazyFrame::anonymous_scan(scan.clone(), args)?;
+    let schema = lf.schema()?;
+    if let Some(remaining) = remaining.clone() {
+        lf = lf.filter(remaining);
+        match (options.skip_rows, options.n_rows) {
+            (None, None) => {}
+            (skip_rows, n_rows) => {
+                let len = n_rows.map_or(IdxSize::MAX, |n| n as IdxSize);
+                lf = lf.slice(skip_rows.unwrap_or(0) as i64, len);
+            }
+        }
+    }
+    Ok(MongoSource {
+        lf: options.rename_columns(lf),
+        scan,
+        schema,
+        remaining,
+    })
+}
+
+#[cfg(feature = "sync")]
 pub trait MongoLazyReader {
     fn scan_mongo_collection(options: MongoScanOptions) -> PolarsResult<LazyFrame> {
-        let f = MongoScan::new(options.connection_str, options.db, options.collection)?;
+        scan_mongo(options, None)
+    }
 
-        let args = ScanArgsAnonymous {
-            name: "MONGO SCAN",
-            infer_schema_length: options.infer_schema_length,
-            n_rows: options.n_rows,
-            ..ScanArgsAnonymous::default()
-        };
+    /// Scan the collection and run `query` on it, executing as much of the query as possible
+    /// in mongodb.
+    ///
+    /// Filters, column selections and renames, sorts, limits, `unique`, `explode` of a single
+    /// column and group-bys with `sum`, `mean`, `min`, `max`, `first`, `last` and `count`
+    /// aggregations at the start of the query are translated into a single aggregation
+    /// pipeline, so only its output is sent over the network. The query continues in polars
+    /// from the first operation without a pipeline equivalent.
+    ///
+    /// ```no_run
+    /// # use polars::prelude::*;
+    /// # use polars_mongo::prelude::*;
+    /// # fn main() -> PolarsResult<()> {
+    /// # let options = MongoScanOptions::default();
+    /// let df = LazyFrame::scan_mongo_query(options, |lf| {
+    ///     lf.filter(col("age").gt(lit(30)))
+    ///         .select([col("name"), col("age")])
+    ///         .sort("age", Default::default())
+    ///         .limit(10)
+    /// })?
+    /// .collect()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn scan_mongo_query(
+        options: MongoScanOptions,
+        query: impl FnOnce(LazyFrame) -> LazyFrame,
+    ) -> PolarsResult<LazyFrame> {
+        pushdown::scan_mongo_query(options, query)
+    }
 
-        LazyFrame::anonymous_scan(Arc::new(f), args)
+    /// Run `query` on the collection entirely in mongodb, and write its output into another
+    /// collection of the same database with `$out` or `$merge`, so no data leaves the database.
+    ///
+    /// Supports the same operations as [`scan_mongo_query`](MongoLazyReader::scan_mongo_query),
+    /// and fails if any part of the query has no pipeline equivalent.
+    fn merge_mongo_query(
+        options: MongoScanOptions,
+        query: impl FnOnce(LazyFrame) -> LazyFrame,
+        output: PipelineOutput,
+    ) -> PolarsResult<()> {
+        pushdown::merge_mongo_query(options, query, &output)
+    }
+
+    /// Downsample a time series collection in mongodb, by grouping the documents into windows
+    /// of `every` of the `index_column`, per group of the `by` columns.
+    ///
+    /// `every` is a single duration like `15m`, `1h`, `1d`, `1w` (starting on mondays), `1mo` or
+    /// `1y`, and the windows are truncated with `$dateTrunc`. The `aggs` are the `sum`, `mean`,
+    /// `min`, `max`, `first`, `last` and `count` aggregations of single columns supported by
+    /// [`scan_mongo_query`](MongoLazyReader::scan_mongo_query). Like `groupby_dynamic`, the rows
+    /// are sorted by the groups and the start of the windows. Fails if any part of it has no
+    /// pipeline equivalent.
+    ///
+    /// ```no_run
+    /// # use polars::prelude::*;
+    /// # use polars_mongo::prelude::*;
+    /// # fn main() -> PolarsResult<()> {
+    /// # let options = MongoScanOptions::default();
+    /// let df = LazyFrame::scan_mongo_downsample(
+    ///     options,
+    ///     "timestamp",
+    ///     "1h",
+    ///     &["sensor".to_string()],
+    ///     &[col("temperature").mean(), col("temperature").max().alias("max_temperature")],
+    /// )?
+    /// .collect()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn scan_mongo_downsample(
+        options: MongoScanOptions,
+        index_column: &str,
+        every: &str,
+        by: &[String],
+        aggs: &[Expr],
+    ) -> PolarsResult<LazyFrame> {
+        pushdown::scan_mongo_downsample(options, index_column, every, by, aggs)
+    }
+
+    /// Scan the output of an aggregation `pipeline` on the collection.
+    ///
+    /// The schema is inferred from the pipeline output, by running it with an extra `$limit` stage.
+    fn scan_mongo_aggregation(
+        options: MongoScanOptions,
+        pipeline: Vec<Document>,
+    ) -> PolarsResult<LazyFrame> {
+        scan_mongo(options, Some(pipeline))
     }
 }
 
+#[cfg(feature = "sync")]
 impl MongoLazyReader for LazyFrame {}
+
+#[cfg(feature = "sync")]
+/// Count the documents of a collection matching `options.filter` and `options.predicate`,
+/// capped at `options.n_rows`.
+///
+/// Unlike `scan_mongo_collection(options)?.select([count()])`, no documents are sent over
+/// the network, unless the predicate can't be translated into a mongodb filter.
+pub fn count_collection(options: MongoScanOptions) -> PolarsResult<usize> {
+    let f = MongoScan::from_options(&options)?
+        .with_filter(options.filter.clone())
+        .with_decimal128_as_float(options.decimal128_as_float)
+        .with_object_id_as(options.object_id_as)
+        .with_flatten(options.flattening())
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_count_mode(options.count_mode);
+    let (f, remaining) = f.with_options_predicate(&options)?;
+    match remaining {
+        None => f.count(options.n_rows),
+        Some(_) => Ok(scan_mongo(options, None)?.collect()?.height()),
+    }
+}
+
+#[cfg(feature = "sync")]
+/// Read the distinct values of `field` in the documents matching `options.filter` and
+/// `options.predicate`.
+///
+/// Unlike `scan_mongo_collection(options)?.select([col(field)]).unique(..)`, only the unique
+/// values are sent over the network, unless the predicate can't be translated into a mongodb filter.
+/// The dtype is taken from `options.schema` if provided.
+pub fn distinct_collection(options: MongoScanOptions, field: &str) -> PolarsResult<Series> {
+    let f = MongoScan::from_options(&options)?
+        .with_filter(options.filter.clone())
+        .with_decimal128_as_float(options.decimal128_as_float)
+        .with_object_id_as(options.object_id_as)
+        .with_timestamp_as(options.timestamp_as)
+        .with_regex_as(options.regex_as)
+        .with_uuid_as(options.uuid_as)
+        .with_fallback_dtype(options.fallback_dtype)
+        .with_datetime_time_unit(options.datetime_time_unit)
+        .with_max_time(options.max_time_ms.map(Duration::from_millis));
+    let (f, remaining) = f.with_options_predicate(&options)?;
+    match remaining {
+        None => {
+            let dtype = options
+                .schema
+                .as_ref()
+                .and_then(|schema| schema.get(field).cloned());
+            f.distinct(field, dtype)
+        }
+        Some(_) => {
+            let df = scan_mongo(options, None)?
+                .select([col(field)])
+                .unique(None, UniqueKeepStrategy::First)
+                .collect()?;
+            Ok(df.column(field)?.clone())
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+/// Count the documents matching `options.filter` and `options.predicate` per value of `field`,
+/// into a frame of `field` and `counts` sorted by descending count.
+///
+/// Unlike `scan_mongo_collection(options)?.select([col(field)]).collect()?[field].value_counts(..)`,
+/// only the frequency table is sent over the network, unless the predicate can't be translated
+/// into a mongodb filter. The dtype is taken from `options.schema` if provided.
+pub fn value_counts_collection(options: MongoScanOptions, field: &str) -> PolarsResult<DataFrame> {
+    let f = MongoScan::from_options(&options)?
+        .with_filter(options.filter.clone())
+        .with_decimal128_as_float(options.decimal128_as_float)
+        .with_object_id_as(options.object_id_as)
+        .with_timestamp_as(options.timestamp_as)
+        .with_regex_as(options.regex_as)
+        .with_uuid_as(options.uuid_as)
+        .with_fallback_dtype(options.fallback_dtype)
+        .with_datetime_time_unit(options.datetime_time_unit)
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_allow_disk_use(options.allow_disk_use);
+    let (f, remaining) = f.with_options_predicate(&options)?;
+    match remaining {
+        None => {
+            let dtype = options
+                .schema
+                .as_ref()
+                .and_then(|schema| schema.get(field).cloned());
+            f.value_counts(field, dtype)
+        }
+        Some(_) => {
+            let df = scan_mongo(options, None)?.select([col(field)]).collect()?;
+            df.column(field)?.value_counts(true, true)
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+/// Scan several collections of the same database and union them vertically.
+///
+/// `collections` are names, globs with `*` and `?` like `events_2022_*`, or regular
+/// expressions enclosed in slashes like `/^events_\d+$/`, matched against the collections
+/// of `options.db`. Every collection is scanned with `options`, so `n_rows` applies per
+/// collection. Columns missing from some of the collections are filled with nulls, and
+/// columns with different dtypes are read with a common dtype like in schema inference.
+///
+/// If `collection_column` is given, a column of that name holds the collection
+/// every row was read from.
+pub fn scan_mongo_collections(
+    options: MongoScanOptions,
+    collections: Vec<String>,
+    collection_column: Option<&str>,
+) -> PolarsResult<LazyFrame> {
+    let names = resolve_collections(&options, &collections)?;
+    if names.is_empty() {
+        return Err(PolarsError::NotFound(
+            format!("no collection in {} matches {:?}", options.db, collections).into(),
+        ));
+    }
+    let scans = names
+        .into_iter()
+        .map(|name| {
+            // the rows are counted across all collections, once they are concatenated.
+            let lf = scan_mongo(
+                MongoScanOptions {
+                    collection: name.clone(),
+                    ordered: options.ordered || options.row_count.is_some(),
+                    row_count: None,
+                    ..options.clone()
+                },
+                None,
+            )?;
+            let schema = lf.schema()?;
+            Ok((name, lf, schema))
+        })
+        .collect::<PolarsResult<Vec<_>>>()?;
+
+    let mut schema = Schema::new();
+    for (_, _, scan_schema) in &scans {
+        for (name, dtype) in scan_schema.iter() {
+            let dtype = match schema.get(name) {
+                Some(existing) => merge_dtypes(existing, dtype),
+                None => dtype.clone(),
+            };
+            schema.with_column(name.clone(), dtype);
+        }
+    }
+
+    let lfs: Vec<_> = scans
+        .into_iter()
+        .map(|(collection, lf, scan_schema)| {
+            let mut exprs: Vec<_> = schema
+                .iter()
+                .map(|(name, dtype)| match scan_schema.get(name) {
+                    Some(scan_dtype) if scan_dtype == dtype => col(name),
+                    Some(_) => col(name).cast(dtype.clone()),
+                    None => lit(Null {}).cast(dtype.clone()).alias(name),
+                })
+                .collect();
+            if let Some(column) = collection_column {
+                exprs.push(lit(collection).alias(column));
+            }
+            lf.select(exprs)
+        })
+        .collect();
+    let lf = concat(&lfs, false, true)?;
+    Ok(match &options.row_count {
+        Some(row_count) => lf.with_row_count(&row_count.name, Some(row_count.offset)),
+        None => lf,
+    })
+}
+
+/// Names of the collections matching `patterns`, in the order of `patterns`
+/// and sorted by name per pattern.
+#[cfg(feature = "sync")]
+fn resolve_collections(
+    options: &MongoScanOptions,
+    patterns: &[String],
+) -> PolarsResult<Vec<String>> {
+    let scan = MongoScan::from_options(options)?;
+    let db = scan.get_database()?;
+    let mut names = vec![];
+    for pattern in patterns {
+        let regex = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) => regex.to_string(),
+            None if pattern.contains(['*', '?']) => glob_to_regex(pattern),
+            None => {
+                names.push(pattern.clone());
+                continue;
+            }
+        };
+        let mut matched = db
+            .list_collection_names(doc! {"name": {"$regex": regex}})
+            .map_err(|err| scan.driver_error(err))?;
+        matched.sort();
+        names.extend(matched);
+    }
+    let mut seen = PlHashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+    Ok(names)
+}
+
+/// Anchored regular expression matching the same names as `glob`.
+#[cfg(feature = "sync")]
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c if "\\.+()[]{}^$|".contains(c) => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(c),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Size of a random sample of a collection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    /// A fixed number of documents.
+    N(usize),
+    /// A fraction between `0` and `1` of the documents matching the filter.
+    Fraction(f64),
+}
+
+#[cfg(feature = "sync")]
+/// Read a random sample of a collection with the `$sample` stage, instead of scanning it.
+///
+/// `options.filter` and the parts of `options.predicate` that can be evaluated server side are
+/// applied before sampling, the rest of the predicate to the sample. A fraction is converted
+/// into a number of documents with a count of the matching documents, see `options.count_mode`.
+/// Like `$sample` itself, the sample may contain a document more than once.
+pub fn scan_mongo_sample(
+    mut options: MongoScanOptions,
+    size: SampleSize,
+) -> PolarsResult<LazyFrame> {
+    let scan = MongoScan::from_options(&options)?
+        .with_flatten(options.flattening())
+        .with_unnest_columns(options.unnest_columns.clone())
+        .with_count_mode(options.count_mode)
+        .with_filter(options.filter.take());
+    let (scan, remaining) = scan.with_options_predicate(&options)?;
+    options.predicate = remaining;
+
+    let n = match size {
+        SampleSize::N(n) => n,
+        SampleSize::Fraction(fraction) => {
+            let count = scan.count(None)?;
+            (count as f64 * fraction.clamp(0.0, 1.0)).ceil() as usize
+        }
+    };
+    let mut pipeline = vec![];
+    if let Some(filter) = &scan.filter {
+        pipeline.push(doc! {"$match": filter.clone()});
+    }
+    pipeline.push(doc! {"$sample": {"size": n as i64}});
+    scan_mongo(options, Some(pipeline))
+}
+
+#[cfg(feature = "sync")]
+/// Read the documents added since the last run of a scheduled job, i.e. those whose
+/// `watermark` field is greater than `last_value`, sorted by it.
+///
+/// Returns the documents and the new high-watermark, to pass as `last_value` to the next run.
+/// Without new documents, the watermark stays at `last_value`. The watermark is the greatest
+/// stored value of the field, read on the server before the documents, so it keeps its bson
+/// type, e.g. `ObjectId` for `_id`, whatever dtype the column is read as. Documents added
+/// in between are left to the next run.
+///
+/// `n_rows` caps the watermark rather than the rows: all documents up to the watermark of the
+/// first `n_rows` are read, including those tied with the last one, so none are skipped by the
+/// next run. The rows are sorted by `watermark`, and then by `sort`.
+pub fn scan_mongo_incremental(
+    mut options: MongoScanOptions,
+    watermark: &str,
+    last_value: Option<Bson>,
+) -> PolarsResult<(DataFrame, Option<Bson>)> {
+    if let Some(last_value) = &last_value {
+        let after = doc! {watermark: {"$gt": last_value.clone()}};
+        options.filter = predicate::and_filters(options.filter.take(), Some(after));
+    }
+    let high = MongoScan::from_options(&options)?
+        .with_filter(options.filter.clone())
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_allow_disk_use(options.allow_disk_use)
+        .max_value(watermark, options.skip_rows, options.n_rows)?;
+    match &high {
+        Some(high) => {
+            let until = doc! {watermark: {"$lte": high.clone()}};
+            options.filter = predicate::and_filters(options.filter.take(), Some(until));
+            options.n_rows = None;
+        }
+        // nothing new, the scan only reads the schema.
+        None => options.n_rows = Some(0),
+    }
+    options.sort = Some(watermark_sort(watermark, options.sort.take()));
+    let df = scan_mongo(options, None)?.collect()?;
+    Ok((df, high.or(last_value)))
+}
+
+#[cfg(feature = "sync")]
+/// Sort by `watermark`, and then by the other keys of `sort`.
+fn watermark_sort(watermark: &str, sort: Option<Document>) -> Document {
+    let mut watermark_sort = doc! {watermark: 1};
+    for (key, order) in sort.into_iter().flatten() {
+        if key != watermark {
+            watermark_sort.insert(key, order);
+        }
+    }
+    watermark_sort
+}
+
+#[cfg(feature = "sync")]
+/// Read the first `n` documents of a collection into a [`DataFrame`].
+///
+/// Unlike [`MongoLazyReader::scan_mongo_collection`], this issues a single limited `find`
+/// and skips counting and partitioning entirely. Scans with `skip_rows`, `unnest_columns` or a
+/// predicate that can't be translated into a mongodb filter are read with a limited
+/// [`MongoLazyReader::scan_mongo_collection`] instead.
+pub fn head_collection(options: MongoScanOptions, n: usize) -> PolarsResult<DataFrame> {
+    let scan_head = |options: MongoScanOptions| -> PolarsResult<DataFrame> {
+        scan_mongo(options, None)?.limit(n as IdxSize).collect()
+    };
+    if options.skip_rows.is_some() || !options.unnest_columns.is_empty() {
+        return scan_head(options);
+    }
+    let f = MongoScan::from_options(&options)?
+        .with_batch_size(options.batch_size)
+        .with_flatten(options.flattening())
+        .with_filter(options.filter.clone())
+        .with_sort(options.sort.clone())
+        .with_ordered(options.ordered)
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_schema(options.schema.clone())
+        .with_dtype_overrides(options.dtype_overrides.clone())
+        .with_columns(options.columns.clone())
+        .with_include_id(options.include_id)
+        .with_categorical_columns(options.categorical_columns.clone())
+        .with_decimal128_as_float(options.decimal128_as_float)
+        .with_object_id_as(options.object_id_as)
+        .with_timestamp_as(options.timestamp_as)
+        .with_regex_as(options.regex_as)
+        .with_uuid_as(options.uuid_as)
+        .with_fallback_dtype(options.fallback_dtype)
+        .with_include_raw(options.include_raw)
+        .with_raw_format(options.raw_format)
+        .with_datetime_time_unit(options.datetime_time_unit)
+        .with_parse_mode(options.parse_mode)
+        .with_parse_report(options.parse_report.clone());
+    let (f, remaining) = f.with_options_predicate(&options)?;
+    if remaining.is_some() {
+        return scan_head(options);
+    }
+    let mut df = f.head(n)?;
+    for (existing, new) in options.rename.iter().flatten() {
+        df.rename(existing, new)?;
+    }
+    Ok(df)
+}
+
+#[cfg(all(test, feature = "sync"))]
+mod tests {
+    use super::*;
+
+    fn scan() -> MongoScan {
+        MongoScan::new(
+            "mongodb://localhost:27017".into(),
+            "db".into(),
+            "coll".into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn watermark_sort_keeps_the_other_keys() {
+        assert_eq!(watermark_sort("ts", None), doc! {"ts": 1});
+        assert_eq!(
+            watermark_sort("ts", Some(doc! {"ts": -1, "name": -1, "_id": 1})),
+            doc! {"ts": 1, "name": -1, "_id": 1}
+        );
+    }
+
+    #[test]
+    fn head_of_zero_rows_has_the_schema() {
+        let schema = Schema::from(
+            vec![
+                Field::new("_id", DataType::Utf8),
+                Field::new("a", DataType::Int64),
+            ]
+            .into_iter(),
+        );
+        let df = scan().with_schema(Some(schema.clone())).head(0).unwrap();
+        assert_eq!(df.height(), 0);
+        assert_eq!(df.schema(), schema);
+    }
+
+    #[test]
+    fn max_value_of_the_sliced_documents() {
+        let f = scan().with_filter(Some(doc! {"ts": {"$gt": 10}}));
+        assert_eq!(
+            f.max_value_pipeline("ts", Some(5), Some(100)),
+            vec![
+                doc! {"$match": {"ts": {"$gt": 10}}},
+                doc! {"$sort": {"ts": 1}},
+                doc! {"$skip": 5_i64},
+                doc! {"$limit": 100_i64},
+                doc! {"$group": {"_id": Bson::Null, "max": {"$max": "$ts"}}},
+            ]
+        );
+    }
+
+    #[test]
+    fn head_of_zero_rows_selects_the_columns() {
+        let schema = Schema::from(
+            vec![
+                Field::new("_id", DataType::Utf8),
+                Field::new("a", DataType::Int64),
+                Field::new("b", DataType::Utf8),
+            ]
+            .into_iter(),
+        );
+        let df = scan()
+            .with_schema(Some(schema))
+            .with_columns(Some(vec!["b".into(), "_id".into()]))
+            .with_include_id(false)
+            .head(0)
+            .unwrap();
+        assert_eq!(df.get_column_names(), ["b"]);
+    }
+}
@@ -0,0 +1,436 @@
+//! Splitting a collection scan into partitions that can be read in parallel.
+#[cfg(feature = "sync")]
+use polars::prelude::*;
+
+#[cfg(feature = "sync")]
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document},
+    options::FindOptions,
+    sync::{Collection, Database},
+};
+
+#[cfg(feature = "sync")]
+use crate::predicate::and_filters;
+
+/// How a collection scan is split up between the threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionStrategy {
+    /// Split the collection on sampled `_id` boundaries.
+    ///
+    /// Every thread filters on its own `_id` range, so mongodb can use the `_id` index
+    /// instead of walking past the skipped documents.
+    IdRange,
+    /// Split the collection with `skip` and `limit`.
+    SkipLimit,
+    /// Split a sharded collection along the chunk boundaries of its shard key,
+    /// read from the `config` database.
+    ///
+    /// Every thread reads a range of consecutive chunks, which mongos routes to the shards
+    /// that own them. Collections that are not sharded, or sharded on a hashed key,
+    /// are split with `skip` and `limit` instead.
+    ShardChunks,
+}
+
+/// How the documents of a collection without a filter are counted before partitioning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CountMode {
+    /// Read the count from the collection metadata with `estimated_document_count`.
+    ///
+    /// This is instant, but can be off after an unclean shutdown or on sharded clusters
+    /// with orphaned documents.
+    #[default]
+    Estimated,
+    /// Count the documents with `count_documents`, which scans the `_id` index.
+    Exact,
+}
+
+/// Collections with at least this many documents are split by `_id` range by default.
+#[cfg(feature = "sync")]
+pub(crate) const ID_RANGE_MIN_ROWS: usize = 100_000;
+
+/// Number of `_id`s sampled per thread to determine the range boundaries.
+#[cfg(feature = "sync")]
+const SAMPLES_PER_PARTITION: usize = 32;
+
+/// A part of the collection that is read by a single cursor.
+#[cfg(feature = "sync")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Partition {
+    pub(crate) filter: Option<Document>,
+    pub(crate) skip: Option<u64>,
+    pub(crate) limit: Option<i64>,
+}
+
+/// Split `n_rows` rows after the first `offset` evenly between `n_threads` partitions,
+/// the last one taking the remainder.
+///
+/// If `n_rows` is the count of the collection rather than a limit, the last partition is
+/// left unbounded, so documents that were not counted yet are still read.
+#[cfg(feature = "sync")]
+pub(crate) fn skip_limit_partitions(
+    filter: Option<&Document>,
+    offset: usize,
+    n_rows: usize,
+    n_threads: usize,
+    limited: bool,
+) -> Vec<Partition> {
+    let n_threads = n_threads.clamp(1, n_rows.max(1));
+    let rows_per_thread = n_rows / n_threads;
+    (0..n_threads)
+        .map(|idx| {
+            let skip = idx * rows_per_thread;
+            let limit = match idx == n_threads - 1 {
+                true if !limited => None,
+                true => Some(n_rows - skip),
+                false => Some(rows_per_thread),
+            };
+            Partition {
+                filter: filter.cloned(),
+                skip: Some((offset + skip) as u64),
+                limit: limit.map(|limit| limit as i64),
+            }
+        })
+        .collect()
+}
+
+/// Partition the collection on `_id` boundaries taken from a `$sample` of the collection.
+///
+/// The first `offset` documents in `_id` order are left out, by starting the first range
+/// at the `_id` of the document after them.
+///
+/// Returns `None` if the sampled `_id`s are not all `ObjectId`s, or if there are
+/// not enough distinct `_id`s to split on.
+#[cfg(feature = "sync")]
+pub(crate) fn id_range_partitions(
+    collection: &Collection<Document>,
+    filter: Option<&Document>,
+    offset: usize,
+    n_threads: usize,
+) -> PolarsResult<Option<Vec<Partition>>> {
+    let offset_filter;
+    let filter = match offset {
+        0 => filter,
+        _ => match id_at_offset(collection, filter, offset)? {
+            Some(id) => {
+                offset_filter = and_filters(filter.cloned(), Some(doc! {"_id": {"$gte": id}}));
+                offset_filter.as_ref()
+            }
+            None => return Ok(None),
+        },
+    };
+
+    let mut pipeline = vec![];
+    if let Some(filter) = filter {
+        pipeline.push(doc! {"$match": filter.clone()});
+    }
+    pipeline.push(doc! {"$sample": {"size": (n_threads * SAMPLES_PER_PARTITION) as i64}});
+    pipeline.push(doc! {"$project": {"_id": 1}});
+
+    let sample = collection
+        .aggregate(pipeline, None)
+        .and_then(|cursor| cursor.collect::<mongodb::error::Result<Vec<_>>>())
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+    Ok(sampled_id_partitions(&sample, filter, n_threads))
+}
+
+/// Split the `_id` range of the `sample` documents into `n_threads` partitions, and add a
+/// partition for the documents whose `_id` is not an `ObjectId`.
+#[cfg(feature = "sync")]
+fn sampled_id_partitions(
+    sample: &[Document],
+    filter: Option<&Document>,
+    n_threads: usize,
+) -> Option<Vec<Partition>> {
+    let mut ids = sample
+        .iter()
+        .map(|doc| match doc.get("_id") {
+            Some(Bson::ObjectId(oid)) => Some(*oid),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+    ids.sort();
+    ids.dedup();
+
+    if ids.len() < n_threads {
+        return None;
+    }
+
+    // the `_id` every partition starts at, the first partition is unbounded below.
+    let bounds: Vec<_> = (1..n_threads)
+        .map(|idx| ids[idx * ids.len() / n_threads])
+        .collect();
+
+    let mut partitions: Vec<_> = (0..n_threads)
+        .map(|idx| {
+            let range = match (idx.checked_sub(1).map(|i| bounds[i]), bounds.get(idx)) {
+                (Some(lower), Some(upper)) => doc! {"_id": {"$gte": lower, "$lt": upper}},
+                (Some(lower), None) => doc! {"_id": {"$gte": lower}},
+                (None, Some(upper)) => doc! {"_id": {"$lt": upper}},
+                (None, None) => doc! {"_id": {"$type": "objectId"}},
+            };
+            Partition {
+                filter: and_filters(filter.cloned(), Some(range)),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    // range queries only match `ObjectId`s, so any other `_id` gets its own partition.
+    partitions.push(Partition {
+        filter: and_filters(
+            filter.cloned(),
+            Some(doc! {"_id": {"$not": {"$type": "objectId"}}}),
+        ),
+        ..Default::default()
+    });
+    Some(partitions)
+}
+
+/// The `ObjectId` of the document at `offset` in `_id` order, found on the `_id` index.
+#[cfg(feature = "sync")]
+fn id_at_offset(
+    collection: &Collection<Document>,
+    filter: Option<&Document>,
+    offset: usize,
+) -> PolarsResult<Option<ObjectId>> {
+    let find_options = FindOptions::builder()
+        .sort(doc! {"_id": 1})
+        .skip(offset as u64)
+        .projection(doc! {"_id": 1})
+        .build();
+    let doc = collection
+        .find_one(filter.cloned(), find_options)
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+    Ok(doc.and_then(|doc| doc.get_object_id("_id").ok()))
+}
+
+/// Partition a sharded collection along the chunk boundaries of its shard key.
+///
+/// Consecutive chunks are grouped into `n_threads` ranges of about the same number of chunks.
+/// Returns `None` if the collection is not sharded, or if it is sharded on a hashed key,
+/// whose chunk boundaries are hashes instead of values of the key.
+#[cfg(feature = "sync")]
+pub(crate) fn shard_chunk_partitions(
+    config: &Database,
+    namespace: &str,
+    filter: Option<&Document>,
+    n_threads: usize,
+) -> mongodb::error::Result<Option<Vec<Partition>>> {
+    let sharded = config
+        .collection::<Document>("collections")
+        .find_one(doc! {"_id": namespace, "dropped": {"$ne": true}}, None)?;
+    let sharded = match sharded {
+        Some(sharded) => sharded,
+        None => return Ok(None),
+    };
+    let key = match sharded.get_document("key") {
+        Ok(key) => key.clone(),
+        Err(_) => return Ok(None),
+    };
+    if key.values().any(|v| v.as_str() == Some("hashed")) {
+        return Ok(None);
+    }
+    let fields: Vec<_> = key.keys().cloned().collect();
+
+    // chunks refer to their collection by uuid since mongodb 5.0, and by namespace before.
+    let chunk_filter = match sharded.get("uuid") {
+        Some(uuid) => doc! {"$or": [{"uuid": uuid.clone()}, {"ns": namespace}]},
+        None => doc! {"ns": namespace},
+    };
+    let find_options = FindOptions::builder()
+        .sort(doc! {"min": 1})
+        .projection(doc! {"min": 1, "max": 1})
+        .build();
+    let chunks = config
+        .collection::<Document>("chunks")
+        .find(chunk_filter, find_options)?
+        .map(|chunk| {
+            let chunk = chunk?;
+            Ok(
+                match (chunk.get_document("min"), chunk.get_document("max")) {
+                    (Ok(min), Ok(max)) => Some((min.clone(), max.clone())),
+                    _ => None,
+                },
+            )
+        })
+        .collect::<mongodb::error::Result<Option<Vec<_>>>>()?;
+    let chunks = match chunks {
+        Some(chunks) if chunks.len() > 1 => chunks,
+        _ => return Ok(None),
+    };
+
+    let n_partitions = n_threads.min(chunks.len());
+    let partitions = (0..n_partitions)
+        .map(|idx| {
+            let (min, _) = &chunks[idx * chunks.len() / n_partitions];
+            let (_, max) = &chunks[(idx + 1) * chunks.len() / n_partitions - 1];
+            let range = doc! {"$and": [
+                key_bound(&fields, min, "$gt", true),
+                key_bound(&fields, max, "$lt", false),
+            ]};
+            Partition {
+                filter: and_filters(filter.cloned(), Some(range)),
+                ..Default::default()
+            }
+        })
+        .collect();
+    Ok(Some(partitions))
+}
+
+/// Filter on the shard key `fields` being before or after `bound`, in the lexicographic
+/// order chunks are split in. `op` is `$lt` or `$gt`, and `inclusive` also matches `bound`.
+#[cfg(feature = "sync")]
+fn key_bound(fields: &[String], bound: &Document, op: &str, inclusive: bool) -> Document {
+    let value = |field: &String| bound.get(field).cloned().unwrap_or(Bson::Null);
+    if let [field] = fields {
+        let op = match inclusive {
+            true => format!("{}e", op),
+            false => op.to_string(),
+        };
+        return doc! {field: {op: value(field)}};
+    }
+    // the first field that differs from `bound` decides, the ones before it are equal.
+    let mut branches: Vec<Bson> = (0..fields.len())
+        .map(|idx| {
+            let mut branch: Document = fields[..idx]
+                .iter()
+                .map(|field| (field.clone(), value(field)))
+                .collect();
+            branch.insert(fields[idx].clone(), doc! {op: value(&fields[idx])});
+            Bson::Document(branch)
+        })
+        .collect();
+    if inclusive {
+        let equal: Document = fields
+            .iter()
+            .map(|field| (field.clone(), value(field)))
+            .collect();
+        branches.push(Bson::Document(equal));
+    }
+    doc! {"$or": branches}
+}
+
+#[cfg(all(test, feature = "sync"))]
+mod tests {
+    use super::*;
+
+    fn bounds(partitions: &[Partition]) -> Vec<(Option<u64>, Option<i64>)> {
+        partitions.iter().map(|p| (p.skip, p.limit)).collect()
+    }
+
+    #[test]
+    fn skip_limit_partitions_cover_uneven_splits() {
+        let partitions = skip_limit_partitions(None, 0, 1001, 4, true);
+        assert_eq!(
+            bounds(&partitions),
+            [
+                (Some(0), Some(250)),
+                (Some(250), Some(250)),
+                (Some(500), Some(250)),
+                (Some(750), Some(251)),
+            ]
+        );
+        let total: i64 = partitions.iter().filter_map(|p| p.limit).sum();
+        assert_eq!(total, 1001);
+    }
+
+    #[test]
+    fn skip_limit_partitions_start_at_offset() {
+        let filter = doc! {"a": 1};
+        let partitions = skip_limit_partitions(Some(&filter), 10, 20, 2, true);
+        assert_eq!(
+            bounds(&partitions),
+            [(Some(10), Some(10)), (Some(20), Some(10))]
+        );
+        assert!(partitions
+            .iter()
+            .all(|p| p.filter.as_ref() == Some(&filter)));
+    }
+
+    #[test]
+    fn unlimited_skip_limit_partitions_leave_the_last_unbounded() {
+        let partitions = skip_limit_partitions(None, 0, 1001, 4, false);
+        assert_eq!(
+            bounds(&partitions),
+            [
+                (Some(0), Some(250)),
+                (Some(250), Some(250)),
+                (Some(500), Some(250)),
+                (Some(750), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_limit_partitions_with_fewer_rows_than_threads() {
+        let partitions = skip_limit_partitions(None, 0, 3, 8, true);
+        assert_eq!(
+            bounds(&partitions),
+            [(Some(0), Some(1)), (Some(1), Some(1)), (Some(2), Some(1))]
+        );
+    }
+
+    #[test]
+    fn skip_limit_partitions_without_rows() {
+        let partitions = skip_limit_partitions(None, 0, 0, 4, false);
+        assert_eq!(bounds(&partitions), [(Some(0), None)]);
+    }
+
+    fn ids(hex: &[&str]) -> Vec<Document> {
+        hex.iter()
+            .map(|hex| doc! {"_id": ObjectId::parse_str(hex).unwrap()})
+            .collect()
+    }
+
+    #[test]
+    fn id_range_partitions_split_on_sampled_ids() {
+        let sample = ids(&[
+            "62d0b0f1a1b2c3d4e5f60703",
+            "62d0b0f1a1b2c3d4e5f60701",
+            "62d0b0f1a1b2c3d4e5f60704",
+            "62d0b0f1a1b2c3d4e5f60702",
+        ]);
+        let bound = ObjectId::parse_str("62d0b0f1a1b2c3d4e5f60703").unwrap();
+        let filters = sampled_id_partitions(&sample, None, 2)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.filter)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            filters,
+            [
+                Some(doc! {"_id": {"$lt": bound}}),
+                Some(doc! {"_id": {"$gte": bound}}),
+                Some(doc! {"_id": {"$not": {"$type": "objectId"}}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn id_range_partitions_keep_the_filter() {
+        let sample = ids(&["62d0b0f1a1b2c3d4e5f60701"]);
+        let partitions = sampled_id_partitions(&sample, Some(&doc! {"a": 1}), 1).unwrap();
+        assert_eq!(
+            partitions[0].filter,
+            Some(doc! {"$and": [{"a": 1}, {"_id": {"$type": "objectId"}}]})
+        );
+    }
+
+    #[test]
+    fn id_range_partitions_need_object_ids() {
+        let mut sample = ids(&["62d0b0f1a1b2c3d4e5f60701", "62d0b0f1a1b2c3d4e5f60702"]);
+        sample.push(doc! {"_id": "a string"});
+        assert!(sampled_id_partitions(&sample, None, 2).is_none());
+        assert!(sampled_id_partitions(&[doc! {"_id": 1}, doc! {"_id": 2}], None, 2).is_none());
+    }
+
+    #[test]
+    fn id_range_partitions_need_enough_distinct_ids() {
+        let sample = ids(&["62d0b0f1a1b2c3d4e5f60701", "62d0b0f1a1b2c3d4e5f60701"]);
+        assert!(sampled_id_partitions(&sample, None, 2).is_none());
+        assert!(sampled_id_partitions(&[], None, 1).is_none());
+    }
+}
@@ -0,0 +1,445 @@
+//! Translation of polars predicates into mongodb find filters.
+use polars::prelude::*;
+
+use mongodb::bson::{doc, oid::ObjectId, spec::ElementType, Bson, Document};
+
+use crate::conversion::{ConversionOptions, ObjectIdAs, Wrap};
+
+/// The bson type of the sampled values of every field, see [`source_type`], or `None` for the
+/// fields sampled with several types. Fields that were not sampled, or only as nulls, are left
+/// out.
+pub(crate) type SourceTypes = PlHashMap<String, Option<ElementType>>;
+
+/// The bson type `value` is recorded as in [`SourceTypes`], `None` for nulls.
+///
+/// Numbers compare by value whatever their type, so they are all recorded as `Double`,
+/// and symbols compare like strings.
+pub(crate) fn source_type(value: &Bson) -> Option<ElementType> {
+    match value.element_type() {
+        ElementType::Null | ElementType::Undefined => None,
+        ElementType::Int32 | ElementType::Int64 | ElementType::Double => Some(ElementType::Double),
+        ElementType::Symbol => Some(ElementType::String),
+        element_type => Some(element_type),
+    }
+}
+
+/// The bson types the columns of a schema given instead of inferred are trusted to be stored as,
+/// i.e. numbers for the numeric and booleans for the `Boolean` columns.
+pub(crate) fn given_source_types(schema: &Schema) -> SourceTypes {
+    schema
+        .iter()
+        .filter_map(|(name, dtype)| {
+            let source = match dtype {
+                DataType::Boolean => ElementType::Boolean,
+                dtype if dtype.is_numeric() => ElementType::Double,
+                _ => return None,
+            };
+            Some((name.clone(), Some(source)))
+        })
+        .collect()
+}
+
+/// What a predicate is translated against.
+///
+/// Literals are compared with the stored bson values, so they are converted back into the
+/// type the column is read from, e.g. a hex string into an `ObjectId`. Columns whose values
+/// don't map one-to-one onto their dtype are left to polars.
+pub(crate) struct FilterContext<'a> {
+    pub(crate) schema: &'a Schema,
+    pub(crate) source_types: &'a SourceTypes,
+    pub(crate) conversion: &'a ConversionOptions,
+}
+
+fn literal_to_bson(lit: &LiteralValue) -> Option<Bson> {
+    let bson = match lit {
+        LiteralValue::Null => Bson::Null,
+        LiteralValue::Boolean(v) => Bson::Boolean(*v),
+        LiteralValue::Utf8(v) => Bson::String(v.clone()),
+        LiteralValue::UInt8(v) => Bson::Int32(*v as i32),
+        LiteralValue::UInt16(v) => Bson::Int32(*v as i32),
+        LiteralValue::UInt32(v) => Bson::Int64(*v as i64),
+        LiteralValue::UInt64(v) => Bson::Int64(i64::try_from(*v).ok()?),
+        LiteralValue::Int8(v) => Bson::Int32(*v as i32),
+        LiteralValue::Int16(v) => Bson::Int32(*v as i32),
+        LiteralValue::Int32(v) => Bson::Int32(*v),
+        LiteralValue::Int64(v) => Bson::Int64(*v),
+        LiteralValue::Float32(v) => Bson::Double(*v as f64),
+        LiteralValue::Float64(v) => Bson::Double(*v),
+        LiteralValue::Series(s) => {
+            let arr = s.iter().map(|av| Wrap::<Bson>::from(&av).0).collect();
+            Bson::Array(arr)
+        }
+        _ => return None,
+    };
+    Some(bson)
+}
+
+fn column_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Column(name) => Some(name.as_ref()),
+        _ => None,
+    }
+}
+
+/// The stored bson value a literal compared with the column `name` is equal to, or `None` if
+/// comparing the stored values doesn't give the same result as comparing the column.
+fn column_value(name: &str, value: Bson, ctx: &FilterContext) -> Option<Bson> {
+    let dtype = ctx.schema.get(name)?;
+    if value == Bson::Null {
+        return Some(Bson::Null);
+    }
+    // fields that were not sampled, or sampled with several bson types, are left to polars.
+    let source = (*ctx.source_types.get(name)?)?;
+    match (dtype, source, value) {
+        // numbers of different bson types are compared by their value. `Decimal128`s are not,
+        // as mongodb compares them exactly and polars after rounding them to a float.
+        (
+            dtype,
+            ElementType::Double,
+            value @ (Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_)),
+        ) if dtype.is_numeric() => Some(value),
+        (DataType::Boolean, ElementType::Boolean, value @ Bson::Boolean(_)) => Some(value),
+        // a `Utf8` column can be read from many bson types, so only the ones sampled as
+        // strings or `ObjectId`s are compared server side.
+        (
+            DataType::Utf8 | DataType::Categorical(_),
+            ElementType::String,
+            value @ Bson::String(_),
+        ) => Some(value),
+        // hex strings of the same length sort like the bytes of the `ObjectId`s.
+        (DataType::Utf8, ElementType::ObjectId, Bson::String(hex))
+            if ctx.conversion.object_id_as == ObjectIdAs::Hex =>
+        {
+            let oid = ObjectId::parse_str(&hex).ok()?;
+            (oid.to_hex() == hex).then(|| Bson::ObjectId(oid))
+        }
+        _ => None,
+    }
+}
+
+/// Whether mongodb sorts the stored values of the column `name` in the same order as polars
+/// sorts the column.
+///
+/// mongodb orders values of different bson types by their type first, so only columns sampled
+/// with a single type that maps one-to-one onto their dtype are sorted server side.
+pub(crate) fn sorts_like_column(name: &str, ctx: &FilterContext) -> bool {
+    let (dtype, source) = match (ctx.schema.get(name), ctx.source_types.get(name)) {
+        (Some(dtype), Some(Some(source))) => (dtype, *source),
+        _ => return false,
+    };
+    match (dtype, source) {
+        (dtype, ElementType::Double) => dtype.is_numeric(),
+        (DataType::Boolean, ElementType::Boolean) => true,
+        // categoricals are sorted by their physical value.
+        (DataType::Utf8, ElementType::String) => true,
+        (DataType::Utf8, ElementType::ObjectId) => ctx.conversion.object_id_as == ObjectIdAs::Hex,
+        (DataType::Datetime(_, _) | DataType::Date, ElementType::DateTime) => true,
+        _ => false,
+    }
+}
+
+fn comparison_to_filter(
+    left: &Expr,
+    op: Operator,
+    right: &Expr,
+    ctx: &FilterContext,
+) -> Option<Document> {
+    // normalize `lit(1).lt(col("a"))` into `col("a").gt(lit(1))`
+    let (name, value, op) = match (left, right) {
+        (Expr::Column(_), Expr::Literal(lit)) => (column_name(left)?, literal_to_bson(lit)?, op),
+        (Expr::Literal(lit), Expr::Column(_)) => {
+            let op = match op {
+                Operator::Lt => Operator::Gt,
+                Operator::LtEq => Operator::GtEq,
+                Operator::Gt => Operator::Lt,
+                Operator::GtEq => Operator::LtEq,
+                op => op,
+            };
+            (column_name(right)?, literal_to_bson(lit)?, op)
+        }
+        _ => return None,
+    };
+
+    // comparisons with null are null in polars, and are filtered out.
+    if value == Bson::Null {
+        return None;
+    }
+    let value = column_value(name, value, ctx)?;
+
+    let filter = match op {
+        Operator::Eq => doc! {name: {"$eq": value}},
+        // mongodb's `$ne` matches missing fields, whereas polars filters out the nulls.
+        Operator::NotEq => doc! {name: {"$nin": [value, Bson::Null]}},
+        Operator::Lt => doc! {name: {"$lt": value}},
+        Operator::LtEq => doc! {name: {"$lte": value}},
+        Operator::Gt => doc! {name: {"$gt": value}},
+        Operator::GtEq => doc! {name: {"$gte": value}},
+        _ => return None,
+    };
+    Some(filter)
+}
+
+/// Translate a polars predicate into an equivalent mongodb filter document.
+///
+/// Returns `None` if (part of) the expression has no mongodb equivalent.
+pub(crate) fn expr_to_filter(expr: &Expr, ctx: &FilterContext) -> Option<Document> {
+    match expr {
+        Expr::BinaryExpr { left, op, right } => match op {
+            Operator::And => {
+                let left = expr_to_filter(left, ctx)?;
+                let right = expr_to_filter(right, ctx)?;
+                Some(doc! {"$and": [left, right]})
+            }
+            Operator::Or => {
+                let left = expr_to_filter(left, ctx)?;
+                let right = expr_to_filter(right, ctx)?;
+                Some(doc! {"$or": [left, right]})
+            }
+            op => comparison_to_filter(left, *op, right, ctx),
+        },
+        Expr::Function {
+            input, function, ..
+        } => match (function.to_string().as_str(), input.as_slice()) {
+            ("is_null", [e]) => Some(doc! {column_name(e)?: Bson::Null}),
+            ("is_not_null", [e]) => Some(doc! {column_name(e)?: {"$ne": Bson::Null}}),
+            ("is_in", [e, Expr::Literal(lit @ LiteralValue::Series(_))]) => {
+                let name = column_name(e)?;
+                let values = match literal_to_bson(lit)? {
+                    Bson::Array(values) => values
+                        .into_iter()
+                        .map(|value| column_value(name, value, ctx))
+                        .collect::<Option<Vec<_>>>()?,
+                    _ => return None,
+                };
+                Some(doc! {name: {"$in": values}})
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn split_conjunction(expr: &Expr, out: &mut Vec<Expr>) {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::And,
+            right,
+        } => {
+            split_conjunction(left, out);
+            split_conjunction(right, out);
+        }
+        e => out.push(e.clone()),
+    }
+}
+
+/// Split a predicate into a mongodb filter and the remaining predicate
+/// that could not be translated and needs to be evaluated by polars.
+pub(crate) fn split_predicate(
+    predicate: &Expr,
+    ctx: &FilterContext,
+) -> (Option<Document>, Option<Expr>) {
+    let mut conjunction = vec![];
+    split_conjunction(predicate, &mut conjunction);
+
+    let mut filters = vec![];
+    let mut remaining: Option<Expr> = None;
+    for expr in conjunction {
+        match expr_to_filter(&expr, ctx) {
+            Some(filter) => filters.push(filter),
+            None => {
+                remaining = Some(match remaining {
+                    Some(r) => r.and(expr),
+                    None => expr,
+                })
+            }
+        }
+    }
+
+    let filter = match filters.len() {
+        0 => None,
+        1 => filters.pop(),
+        _ => Some(doc! {"$and": filters}),
+    };
+    (filter, remaining)
+}
+
+/// Combine two optional filters, so documents have to match both.
+pub(crate) fn and_filters(left: Option<Document>, right: Option<Document>) -> Option<Document> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(doc! {"$and": [left, right]}),
+        (left, right) => left.or(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID: &str = "62d0b0f1a1b2c3d4e5f60718";
+
+    fn schema() -> Schema {
+        Schema::from(
+            vec![
+                Field::new("_id", DataType::Utf8),
+                Field::new("a", DataType::Int64),
+                Field::new("b", DataType::Int64),
+                Field::new("price", DataType::Float64),
+                Field::new("mixed", DataType::Float64),
+                Field::new("name", DataType::Utf8),
+            ]
+            .into_iter(),
+        )
+    }
+
+    fn source_types() -> SourceTypes {
+        [
+            ("_id", Some(ElementType::ObjectId)),
+            ("a", Some(ElementType::Double)),
+            ("b", Some(ElementType::Double)),
+            ("price", Some(ElementType::Decimal128)),
+            ("mixed", None),
+            ("name", Some(ElementType::String)),
+        ]
+        .into_iter()
+        .map(|(name, source)| (name.to_string(), source))
+        .collect()
+    }
+
+    /// Translate `expr` against [`schema`] and [`source_types`].
+    fn filter(expr: Expr) -> Option<Document> {
+        let (schema, source_types) = (schema(), source_types());
+        let conversion = ConversionOptions::default();
+        let ctx = FilterContext {
+            schema: &schema,
+            source_types: &source_types,
+            conversion: &conversion,
+        };
+        expr_to_filter(&expr, &ctx)
+    }
+
+    #[test]
+    fn literal_to_bson_overflow() {
+        assert_eq!(literal_to_bson(&LiteralValue::UInt64(u64::MAX)), None);
+        assert_eq!(
+            literal_to_bson(&LiteralValue::UInt64(1)),
+            Some(Bson::Int64(1))
+        );
+        assert_eq!(
+            literal_to_bson(&LiteralValue::UInt32(u32::MAX)),
+            Some(Bson::Int64(u32::MAX as i64))
+        );
+    }
+
+    #[test]
+    fn literal_first_comparisons_are_flipped() {
+        assert_eq!(
+            filter(lit(1_i64).lt(col("a"))),
+            Some(doc! {"a": {"$gt": 1_i64}})
+        );
+        assert_eq!(
+            filter(lit(1_i64).gt_eq(col("a"))),
+            Some(doc! {"a": {"$lte": 1_i64}})
+        );
+    }
+
+    #[test]
+    fn not_equal_filters_out_nulls() {
+        assert_eq!(
+            filter(col("a").neq(lit(2_i64))),
+            Some(doc! {"a": {"$nin": [2_i64, Bson::Null]}})
+        );
+    }
+
+    #[test]
+    fn object_id_hex() {
+        let oid = ObjectId::parse_str(OID).unwrap();
+        assert_eq!(
+            filter(col("_id").eq(lit(OID))),
+            Some(doc! {"_id": {"$eq": oid}})
+        );
+        // polars compares the lower case hex strings the `ObjectId`s are read as.
+        assert_eq!(filter(col("_id").eq(lit(OID.to_uppercase()))), None);
+        assert_eq!(filter(col("_id").eq(lit("not an id"))), None);
+    }
+
+    #[test]
+    fn decimal128_and_mixed_sources_stay_in_polars() {
+        assert_eq!(filter(col("price").gt(lit(1.5))), None);
+        assert_eq!(filter(col("mixed").gt(lit(1.5))), None);
+        assert_eq!(
+            filter(col("name").eq(lit("x"))),
+            Some(doc! {"name": {"$eq": "x"}})
+        );
+        // a number compared with a string column.
+        assert_eq!(filter(col("name").eq(lit(1_i64))), None);
+    }
+
+    #[test]
+    fn split_predicate_keeps_the_untranslated_conjuncts() {
+        let (schema, source_types) = (schema(), source_types());
+        let conversion = ConversionOptions::default();
+        let ctx = FilterContext {
+            schema: &schema,
+            source_types: &source_types,
+            conversion: &conversion,
+        };
+        let predicate = col("a")
+            .gt(lit(1_i64))
+            .and(col("a").eq(col("b")))
+            .and(col("name").eq(lit("x")))
+            .and(col("price").lt(lit(2.0)));
+        let (filter, remaining) = split_predicate(&predicate, &ctx);
+        assert_eq!(
+            filter,
+            Some(doc! {"$and": [
+                {"a": {"$gt": 1_i64}},
+                {"name": {"$eq": "x"}},
+            ]})
+        );
+        assert_eq!(
+            remaining,
+            Some(col("a").eq(col("b")).and(col("price").lt(lit(2.0))))
+        );
+
+        let (filter, remaining) = split_predicate(&col("a").eq(col("b")), &ctx);
+        assert_eq!(filter, None);
+        assert_eq!(remaining, Some(col("a").eq(col("b"))));
+    }
+
+    #[test]
+    fn and_filters_of_optional_filters() {
+        let (a, b) = (doc! {"a": 1}, doc! {"b": 2});
+        assert_eq!(and_filters(None, None), None);
+        assert_eq!(and_filters(Some(a.clone()), None), Some(a.clone()));
+        assert_eq!(and_filters(None, Some(b.clone())), Some(b.clone()));
+        assert_eq!(
+            and_filters(Some(a.clone()), Some(b.clone())),
+            Some(doc! {"$and": [a, b]})
+        );
+    }
+
+    #[test]
+    fn sorts_like_column_by_source_type() {
+        let (schema, source_types) = (schema(), source_types());
+        let conversion = ConversionOptions::default();
+        let ctx = FilterContext {
+            schema: &schema,
+            source_types: &source_types,
+            conversion: &conversion,
+        };
+        assert!(sorts_like_column("a", &ctx));
+        assert!(sorts_like_column("name", &ctx));
+        assert!(sorts_like_column("_id", &ctx));
+        assert!(!sorts_like_column("price", &ctx));
+        assert!(!sorts_like_column("mixed", &ctx));
+        assert!(!sorts_like_column("missing", &ctx));
+    }
+
+    #[test]
+    fn given_schemas_trust_numbers_and_booleans() {
+        let source_types = given_source_types(&schema());
+        assert_eq!(source_types.get("a"), Some(&Some(ElementType::Double)));
+        assert_eq!(source_types.get("name"), None);
+    }
+}
@@ -1 +1,25 @@
-pub use crate::{MongoLazyReader, MongoScan, MongoScanOptions};
+#[cfg(feature = "sync")]
+pub use crate::{
+    batched::MongoBatchedReader,
+    change_stream::MongoChangeStreamReader,
+    count_collection, distinct_collection,
+    gridfs::GridFsReader,
+    head_collection,
+    join::MongoJoinBuilder,
+    reader::MongoReader,
+    scan_mongo_collections, scan_mongo_incremental, scan_mongo_sample,
+    tailable::MongoTailableReader,
+    value_counts_collection,
+    vector_search::MongoVectorSearch,
+    writer::{IdMode, MongoCollectionWriter, MongoLazyWriter, MongoWriteOptions, WriteMode},
+    MongoLazyReader, MongoScan, PipelineOutput,
+};
+pub use crate::{
+    bson_file::BsonFileReader, df_to_documents, documents_to_df, extjson_file::ExtendedJsonReader,
+    infer_bson_schema, CancellationToken, Compression, CountMode, FallbackDtype, MongoConnection,
+    MongoDataFrame, MongoScanOptions, ObjectIdAs, ParseMode, ParseReport, PartitionStrategy,
+    PolarsMongoError, ProgressCallback, RawFormat, RegexAs, RetryPolicy, SampleSize, ScanEstimate,
+    ScanProgress, TimestampAs, UuidAs,
+};
+#[cfg(feature = "async")]
+pub use crate::{scan_mongo_collection_async, MongoScanAsync};
@@ -0,0 +1,291 @@
+//! Helpers to analyze the shape and size of a collection before scanning it.
+use polars::prelude::*;
+
+use mongodb::bson::{doc, Bson, Document};
+
+use crate::{scan_mongo_source, MongoScan, MongoScanOptions};
+
+/// Server side summaries of the fields of a collection.
+pub struct MongoProfiler;
+
+impl MongoProfiler {
+    /// Summarize the top level fields of the documents matching the `filter` and the translated
+    /// `predicate` of `options`, with a row per field:
+    ///
+    /// - `field`
+    /// - `count`: number of documents where the field is present and not null
+    /// - `null_ratio`: fraction of the documents where the field is missing or null
+    /// - `cardinality`: number of distinct values, arrays and documents are compared as a whole
+    /// - `min`, `max`: smallest and largest value in the bson sort order, as extended JSON
+    /// - `types`: number of values per bson type, as a JSON object
+    ///
+    /// The documents are grouped server side with a single `$facet` aggregation,
+    /// so only the summary is downloaded.
+    pub fn describe(options: MongoScanOptions) -> PolarsResult<DataFrame> {
+        let filter = scan_filter(&options)?;
+        let scan = MongoScan::from_options(&options)?
+            .with_count_mode(options.count_mode)
+            .with_allow_disk_use(options.allow_disk_use)
+            .with_filter(filter.clone());
+        let n_documents = scan.count(None)?.max(1) as f64;
+
+        let mut pipeline = vec![];
+        if let Some(filter) = filter {
+            pipeline.push(doc! {"$match": filter});
+        }
+        pipeline.push(doc! {"$project": {"_id": 0, "kv": {"$objectToArray": "$$ROOT"}}});
+        pipeline.push(doc! {"$unwind": "$kv"});
+        pipeline.push(doc! {"$facet": {
+            "fields": [{"$group": {
+                "_id": "$kv.k",
+                "count": {"$sum": {"$cond": [{"$eq": ["$kv.v", Bson::Null]}, 0, 1]}},
+                "min": {"$min": "$kv.v"},
+                "max": {"$max": "$kv.v"},
+            }}],
+            "types": [{"$group": {
+                "_id": {"field": "$kv.k", "type": {"$type": "$kv.v"}},
+                "count": {"$sum": 1},
+            }}],
+            "cardinality": [
+                {"$group": {"_id": {"field": "$kv.k", "value": "$kv.v"}}},
+                {"$group": {"_id": "$_id.field", "count": {"$sum": 1}}},
+            ],
+        }});
+
+        let mut cursor = scan
+            .get_collection()?
+            .aggregate(pipeline, scan.aggregate_options())
+            .map_err(|err| scan.driver_error(err))?;
+        let facets = match cursor.next() {
+            Some(facets) => facets.map_err(|err| scan.driver_error(err))?,
+            None => Document::new(),
+        };
+        let facet = |name: &str| -> Vec<Document> {
+            facets
+                .get_array(name)
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_document().cloned())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut types: PlHashMap<String, serde_json::Map<String, serde_json::Value>> =
+            PlHashMap::default();
+        for group in facet("types") {
+            let id = group.get_document("_id").ok();
+            let field = id.and_then(|id| id.get_str("field").ok());
+            let type_name = id.and_then(|id| id.get_str("type").ok());
+            if let (Some(field), Some(type_name)) = (field, type_name) {
+                types
+                    .entry(field.to_string())
+                    .or_default()
+                    .insert(type_name.to_string(), get_count(&group).into());
+            }
+        }
+        let cardinality: PlHashMap<String, u64> = facet("cardinality")
+            .iter()
+            .filter_map(|group| Some((group.get_str("_id").ok()?.to_string(), get_count(group))))
+            .collect();
+
+        let mut fields = facet("fields");
+        fields.sort_by(|l, r| l.get_str("_id").ok().cmp(&r.get_str("_id").ok()));
+        let extjson = |group: &Document, key: &str| {
+            group
+                .get(key)
+                .filter(|v| v != &&Bson::Null)
+                .map(|v| v.clone().into_relaxed_extjson().to_string())
+        };
+
+        let field: Vec<&str> = fields
+            .iter()
+            .map(|group| group.get_str("_id").unwrap_or_default())
+            .collect();
+        let count: Vec<u64> = fields.iter().map(get_count).collect();
+        let null_ratio: Vec<f64> = count
+            .iter()
+            .map(|count| 1.0 - *count as f64 / n_documents)
+            .collect();
+        let distinct: Vec<Option<u64>> = field
+            .iter()
+            .map(|name| cardinality.get(*name).copied())
+            .collect();
+        let min: Vec<Option<String>> = fields.iter().map(|group| extjson(group, "min")).collect();
+        let max: Vec<Option<String>> = fields.iter().map(|group| extjson(group, "max")).collect();
+        let type_counts: Vec<Option<String>> = field
+            .iter()
+            .map(|name| {
+                types
+                    .get(*name)
+                    .map(|types| serde_json::Value::from(types.clone()).to_string())
+            })
+            .collect();
+
+        DataFrame::new(vec![
+            Series::new("field", field),
+            Series::new("count", count),
+            Series::new("null_ratio", null_ratio),
+            Series::new("cardinality", distinct),
+            Series::new("min", min),
+            Series::new("max", max),
+            Series::new("types", type_counts),
+        ])
+    }
+}
+
+/// The `count` of a `$group` output, which is an `Int32` or an `Int64` depending on its size.
+fn get_count(group: &Document) -> u64 {
+    match group.get("count") {
+        Some(Bson::Int32(n)) => *n as u64,
+        Some(Bson::Int64(n)) => *n as u64,
+        _ => 0,
+    }
+}
+
+#[derive(Default)]
+struct FieldStats {
+    count: u32,
+    bytes: usize,
+    distinct: PlHashSet<String>,
+}
+
+/// Size in bytes a single `key: value` element takes up in a bson document.
+fn element_size(key: &str, value: &mongodb::bson::Bson) -> usize {
+    // a document with a single element is the element itself plus
+    // 4 bytes for the length and a trailing null byte.
+    mongodb::bson::to_vec(&doc! {key: value.clone()})
+        .map(|bytes| bytes.len() - 5)
+        .unwrap_or_default()
+}
+
+/// Sample `sample_size` documents with `$sample` and report per top level field:
+///
+/// - `count`: number of sampled documents containing the field
+/// - `cardinality`: number of distinct values seen in the sample
+/// - `avg_size`: average size of the field in bytes, including its key
+/// - `storage_share`: fraction of the sampled bytes taken up by the field
+///
+/// Fields with a large storage share are good candidates to project away,
+/// while low cardinality string fields benefit from a `Categorical` dtype.
+pub fn field_storage_report(
+    options: MongoScanOptions,
+    sample_size: usize,
+) -> PolarsResult<DataFrame> {
+    let collection = MongoScan::from_options(&options)?.get_collection()?;
+
+    let pipeline = vec![doc! {"$sample": {"size": sample_size as i64}}];
+    let cursor = collection
+        .aggregate(pipeline, None)
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+
+    let mut stats: PlIndexMap<String, FieldStats> = PlIndexMap::default();
+    let mut total_bytes = 0;
+    for doc in cursor {
+        let doc: Document =
+            doc.map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+        for (key, value) in doc.iter() {
+            let size = element_size(key, value);
+            total_bytes += size;
+
+            let field = stats.entry(key.clone()).or_default();
+            field.count += 1;
+            field.bytes += size;
+            field.distinct.insert(value.to_string());
+        }
+    }
+
+    let total_bytes = total_bytes.max(1) as f64;
+    let field: Vec<&str> = stats.keys().map(|k| k.as_str()).collect();
+    let count: Vec<u32> = stats.values().map(|s| s.count).collect();
+    let cardinality: Vec<u32> = stats.values().map(|s| s.distinct.len() as u32).collect();
+    let avg_size: Vec<f64> = stats
+        .values()
+        .map(|s| s.bytes as f64 / s.count as f64)
+        .collect();
+    let storage_share: Vec<f64> = stats
+        .values()
+        .map(|s| s.bytes as f64 / total_bytes)
+        .collect();
+
+    DataFrame::new(vec![
+        Series::new("field", field),
+        Series::new("count", count),
+        Series::new("cardinality", cardinality),
+        Series::new("avg_size", avg_size),
+        Series::new("storage_share", storage_share),
+    ])?
+    .sort(["storage_share"], true)
+}
+
+/// Check whether the `filter`, the translated `predicate` and the `sort` of `options` are
+/// supported by an index, using the query plan from `explain`.
+///
+/// Returns a warning for a filter that needs a full collection scan, and for a sort that
+/// is done in memory. An empty result means every pushed operation uses an index.
+pub fn index_warnings(options: MongoScanOptions) -> PolarsResult<Vec<String>> {
+    let scan = MongoScan::from_options(&options)?;
+    let filter = scan_filter(&options)?;
+
+    let mut find = doc! {"find": options.collection.as_str()};
+    if let Some(filter) = &filter {
+        find.insert("filter", filter.clone());
+    }
+    if let Some(sort) = &options.sort {
+        find.insert("sort", sort.clone());
+    }
+    let explain = scan
+        .get_database()?
+        .run_command(doc! {"explain": find, "verbosity": "queryPlanner"}, None)
+        .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+
+    let mut stages = vec![];
+    if let Some(planner) = explain.get("queryPlanner") {
+        plan_stages(planner, &mut stages);
+    }
+
+    let namespace = format!("{}.{}", options.db, options.collection);
+    let mut warnings = vec![];
+    if let Some(filter) = filter.filter(|_| stages.contains(&"COLLSCAN")) {
+        warnings.push(format!(
+            "filter {} on {} is not supported by an index and scans the whole collection",
+            filter, namespace
+        ));
+    }
+    if let Some(sort) = options.sort.filter(|_| stages.contains(&"SORT")) {
+        warnings.push(format!(
+            "sort {} on {} is not supported by an index and is done in memory",
+            sort, namespace
+        ));
+    }
+    Ok(warnings)
+}
+
+/// `options.filter` and the part of `options.predicate` that is translated into a mongodb filter.
+fn scan_filter(options: &MongoScanOptions) -> PolarsResult<Option<Document>> {
+    match options.predicate {
+        Some(_) => Ok(scan_mongo_source(options.clone(), None)?
+            .scan
+            .filter
+            .clone()),
+        None => Ok(options.filter.clone()),
+    }
+}
+
+/// Collect the `stage` names anywhere in a query plan, including the plans of every shard.
+fn plan_stages<'a>(plan: &'a Bson, stages: &mut Vec<&'a str>) {
+    match plan {
+        Bson::Document(doc) => {
+            for (key, value) in doc {
+                match (key.as_str(), value) {
+                    // alternative plans were not chosen, so they don't tell how the query runs.
+                    ("rejectedPlans", _) => {}
+                    ("stage", Bson::String(stage)) => stages.push(stage),
+                    _ => plan_stages(value, stages),
+                }
+            }
+        }
+        Bson::Array(arr) => arr.iter().for_each(|value| plan_stages(value, stages)),
+        _ => {}
+    }
+}
@@ -0,0 +1,97 @@
+//! Periodic progress updates of long running scans.
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "sync")]
+use std::time::Instant;
+
+/// Number of documents a worker reads between two progress updates.
+#[cfg(feature = "sync")]
+const PROGRESS_INTERVAL: usize = 10_000;
+
+/// Progress of a single worker of a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanProgress {
+    /// Index of the partition the worker is reading.
+    pub worker: usize,
+    /// Documents the worker has read so far.
+    pub documents: usize,
+    /// Approximate bytes the worker has read so far, from the average document size
+    /// of the collection. `0` if the collection statistics are unavailable.
+    pub bytes: usize,
+    /// Time since the scan started.
+    pub elapsed: Duration,
+    /// Whether the worker has read all documents of its partition.
+    pub done: bool,
+}
+
+/// Callback receiving the [`ScanProgress`] of every worker of a scan.
+///
+/// It is called from the worker threads, every 10,000 documents and once a partition is read.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(ScanProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(f: impl Fn(ScanProgress) + Send + Sync + 'static) -> Self {
+        ProgressCallback(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Counts the documents read by a worker and reports them to the callback, if any.
+#[cfg(feature = "sync")]
+pub(crate) struct ProgressTracker<'a> {
+    callback: Option<&'a ProgressCallback>,
+    worker: usize,
+    start: Instant,
+    avg_document_size: usize,
+    documents: usize,
+}
+
+#[cfg(feature = "sync")]
+impl<'a> ProgressTracker<'a> {
+    pub(crate) fn new(
+        callback: Option<&'a ProgressCallback>,
+        worker: usize,
+        start: Instant,
+        avg_document_size: usize,
+    ) -> Self {
+        ProgressTracker {
+            callback,
+            worker,
+            start,
+            avg_document_size,
+            documents: 0,
+        }
+    }
+
+    /// Count a document, and report every [`PROGRESS_INTERVAL`] documents.
+    pub(crate) fn add_document(&mut self) {
+        self.documents += 1;
+        if self.documents % PROGRESS_INTERVAL == 0 {
+            self.report(false);
+        }
+    }
+
+    /// Report the final count of the worker.
+    pub(crate) fn finish(&self) {
+        self.report(true);
+    }
+
+    fn report(&self, done: bool) {
+        if let Some(callback) = self.callback {
+            (callback.0)(ScanProgress {
+                worker: self.worker,
+                documents: self.documents,
+                bytes: self.documents * self.avg_document_size,
+                elapsed: self.start.elapsed(),
+                done,
+            });
+        }
+    }
+}
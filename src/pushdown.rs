@@ -0,0 +1,1125 @@
+//! Compile polars queries on top of a collection scan into aggregation pipelines.
+//!
+//! The query plan is walked from the scan upwards. Every node that has an equivalent
+//! pipeline stage is appended to the pipeline, until the first node that can't be translated.
+//! The translated part of the plan is replaced by a scan of the pipeline output, and the
+//! rest of the plan runs in polars on top of it.
+use std::sync::Arc;
+use std::time::Duration;
+
+use polars::prelude::*;
+
+use mongodb::bson::{doc, spec::ElementType, Bson, Document};
+
+use crate::predicate::{FilterContext, SourceTypes};
+use crate::{predicate, scan_mongo, scan_mongo_source, MongoScan, MongoScanOptions, MongoSource};
+
+/// Collection the output of a query run with
+/// [`MongoLazyReader::merge_mongo_query`](crate::MongoLazyReader::merge_mongo_query)
+/// is written to, in the same database as the scanned collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineOutput {
+    /// Replace the collection with the output, with `$out`.
+    Out(String),
+    /// Merge the output into the collection, with `$merge`.
+    Merge {
+        collection: String,
+        /// Fields identifying the documents to merge with. Defaults to `_id`.
+        /// Other fields need a unique index on them.
+        on: Vec<String>,
+        /// Action for output documents that match a document of the collection,
+        /// e.g. `replace` or `keepExisting`. Defaults to `merge`.
+        when_matched: Option<String>,
+    },
+}
+
+impl PipelineOutput {
+    fn stage(&self) -> Document {
+        match self {
+            PipelineOutput::Out(collection) => doc! {"$out": collection},
+            PipelineOutput::Merge {
+                collection,
+                on,
+                when_matched,
+            } => {
+                let mut merge = doc! {"into": collection};
+                if !on.is_empty() {
+                    merge.insert("on", on.clone());
+                }
+                if let Some(when_matched) = when_matched {
+                    merge.insert("whenMatched", when_matched);
+                }
+                doc! {"$merge": merge}
+            }
+        }
+    }
+}
+
+/// Part of a query that has been translated into pipeline stages.
+struct Pushed {
+    stages: Vec<Document>,
+    schema: SchemaRef,
+    /// Bson types the columns are read from, for the columns that are stored fields.
+    source_types: SourceTypes,
+}
+
+/// The stages of a node, and the schema and source types of its output if they change.
+type Translation = (Vec<Document>, Option<SchemaRef>, Option<SourceTypes>);
+
+enum Compiled {
+    Pushed(Pushed),
+    Plan(Box<LogicalPlan>),
+}
+
+struct Compiler {
+    options: MongoScanOptions,
+    /// The collection scan that the query starts from.
+    scan: Arc<dyn AnonymousScan>,
+    source: Arc<MongoScan>,
+    /// Stages for the filter, sort and row limit of the scan options.
+    base: Vec<Document>,
+}
+
+/// Field names that can be used as is in pipeline stages.
+pub(crate) fn is_plain_field(name: &str) -> bool {
+    !name.is_empty() && !name.contains('.') && !name.starts_with('$')
+}
+
+pub(crate) fn field_path(name: &str) -> Option<Bson> {
+    is_plain_field(name).then(|| Bson::String(format!("${}", name)))
+}
+
+/// The name of the output column and the field it is read from,
+/// for plain column selections and renames.
+fn column_selection(expr: &Expr) -> Option<(&str, &str)> {
+    match expr {
+        Expr::Column(name) => Some((name, name)),
+        Expr::Alias(input, alias) => match input.as_ref() {
+            Expr::Column(name) => Some((alias, name)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The source types of `columns` selected from their source fields.
+fn selected_source_types<'a>(
+    source_types: &SourceTypes,
+    columns: impl Iterator<Item = (&'a str, &'a str)>,
+) -> SourceTypes {
+    columns
+        .filter_map(|(name, source)| Some((name.to_string(), *source_types.get(source)?)))
+        .collect()
+}
+
+/// `$project` the `columns` from their source fields, excluding `_id` unless it is one of them.
+fn project_stage<'a>(columns: impl Iterator<Item = (&'a str, &'a str)>) -> Option<Document> {
+    let mut projection = doc! {"_id": 0};
+    for (name, source) in columns {
+        if !is_plain_field(name) {
+            return None;
+        }
+        projection.insert(name, field_path(source)?);
+    }
+    Some(doc! {"$project": projection})
+}
+
+fn sort_stage(by_column: &[Expr], args: &SortArguments, ctx: &FilterContext) -> Option<Document> {
+    let SortArguments {
+        reverse,
+        nulls_last,
+        ..
+    } = args;
+    if reverse.len() != by_column.len() {
+        return None;
+    }
+    // mongodb sorts nulls and missing fields before all other values, so they come first in
+    // an ascending and last in a descending sort, while polars places them the same for
+    // every key.
+    if !reverse.iter().all(|reverse| reverse == nulls_last) {
+        return None;
+    }
+
+    let mut sort = Document::new();
+    for (expr, reverse) in by_column.iter().zip(reverse) {
+        match expr {
+            Expr::Column(name)
+                if is_plain_field(name) && predicate::sorts_like_column(name, ctx) =>
+            {
+                sort.insert(name.as_ref(), if *reverse { -1 } else { 1 });
+            }
+            _ => return None,
+        }
+    }
+    Some(doc! {"$sort": sort})
+}
+
+/// The sort, skip and limit of a top-k query, if `stages` are nothing but that.
+fn top_k(stages: &[Document]) -> Option<(Document, Option<usize>, usize)> {
+    let (sort, rest) = stages.split_first()?;
+    let sort = sort.get_document("$sort").ok()?.clone();
+    let (skip, limit) = match rest {
+        [limit] => (None, limit),
+        [skip, limit] => (Some(skip.get_i64("$skip").ok()? as usize), limit),
+        _ => return None,
+    };
+    Some((sort, skip, limit.get_i64("$limit").ok()? as usize))
+}
+
+fn slice_stages(offset: i64, len: IdxSize) -> Option<Vec<Document>> {
+    // a negative offset counts from the end, which mongodb can't do.
+    // a `$limit` of 0 is rejected by mongodb.
+    if offset < 0 || len == 0 {
+        return None;
+    }
+    let mut stages = vec![];
+    if offset > 0 {
+        stages.push(doc! {"$skip": offset});
+    }
+    stages.push(doc! {"$limit": len as i64});
+    Some(stages)
+}
+
+/// Count the rows with `$count`, for a projection of nothing but `count()`.
+fn count_stages(expr: &[Expr], schema: &Schema) -> Option<Vec<Document>> {
+    match expr {
+        [Expr::Count] => {}
+        [Expr::Alias(count, _)] if matches!(**count, Expr::Count) => {}
+        _ => return None,
+    }
+    let (name, _) = schema.iter().next()?;
+    if !is_plain_field(name) {
+        return None;
+    }
+    // `$count` returns no document at all for an empty input, while polars counts 0 rows.
+    Some(vec![
+        doc! {"$facet": {"rows": [{"$count": "count"}]}},
+        doc! {"$project": {
+            name: {"$ifNull": [{"$arrayElemAt": ["$rows.count", 0]}, 0]},
+        }},
+    ])
+}
+
+/// Deduplicate complete rows by grouping on all columns.
+fn distinct_stages(options: &DistinctOptions, schema: &Schema) -> Option<Vec<Document>> {
+    if options.subset.is_some() || options.maintain_order {
+        return None;
+    }
+    let mut key = Document::new();
+    for name in schema.iter_names() {
+        // a missing field and a null are the same row in polars, but different groups in mongodb.
+        key.insert(name, doc! {"$ifNull": [field_path(name)?, Bson::Null]});
+    }
+    Some(vec![
+        doc! {"$group": {"_id": key}},
+        doc! {"$replaceRoot": {"newRoot": "$_id"}},
+    ])
+}
+
+/// Explode a list column with `$unwind`, keeping the rows with a null or empty list like polars.
+fn unwind_stage(
+    columns: &[String],
+    schema: &Schema,
+    source_types: &SourceTypes,
+) -> Option<Document> {
+    // polars explodes several columns in lockstep, while consecutive `$unwind`s would
+    // produce every combination of their elements.
+    let column = match columns {
+        [column] => column,
+        _ => return None,
+    };
+    // binaries are read as lists of bytes too, but are a single value to `$unwind`.
+    if !matches!(schema.get(column)?, DataType::List(_))
+        || source_types.get(column) != Some(&Some(ElementType::Array))
+    {
+        return None;
+    }
+    Some(doc! {
+        "$unwind": {
+            "path": field_path(column)?,
+            "preserveNullAndEmptyArrays": true,
+        }
+    })
+}
+
+/// The `$group` accumulator computing an aggregation.
+fn accumulator(expr: &Expr, schema: &Schema) -> Option<Document> {
+    let (op, input) = match expr {
+        Expr::Alias(input, _) => return accumulator(input, schema),
+        Expr::Count | Expr::Agg(AggExpr::Count(_)) => return Some(doc! {"$sum": 1}),
+        Expr::Agg(agg) => match agg {
+            AggExpr::Sum(input) => ("$sum", input),
+            AggExpr::Mean(input) => ("$avg", input),
+            AggExpr::Min(input) => ("$min", input),
+            AggExpr::Max(input) => ("$max", input),
+            AggExpr::First(input) => ("$first", input),
+            AggExpr::Last(input) => ("$last", input),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let name = match input.as_ref() {
+        Expr::Column(name) => name,
+        _ => return None,
+    };
+    // `$sum` and `$avg` silently skip values that are not numbers.
+    if matches!(op, "$sum" | "$avg") && !schema.get(name)?.is_numeric() {
+        return None;
+    }
+    Some(doc! {op: field_path(name)?})
+}
+
+/// Whether an aggregation counts the rows of a group.
+fn is_count(expr: &Expr) -> bool {
+    match expr {
+        Expr::Alias(input, _) => is_count(input),
+        Expr::Count | Expr::Agg(AggExpr::Count(_)) => true,
+        _ => false,
+    }
+}
+
+/// Group by the `keys` columns with a `$group` stage, and move the keys out of the `_id`.
+fn group_stages(
+    keys: &[Expr],
+    aggs: &[Expr],
+    input_schema: &Schema,
+    schema: &Schema,
+) -> Option<Vec<Document>> {
+    let mut id = Document::new();
+    let mut project = doc! {"_id": 0};
+    for key in keys {
+        let name = match key {
+            Expr::Column(name) if is_plain_field(name) => name,
+            _ => return None,
+        };
+        // a missing field and a null are the same group in polars, but different groups in mongodb.
+        id.insert(
+            name.as_ref(),
+            doc! {"$ifNull": [field_path(name)?, Bson::Null]},
+        );
+        project.insert(name.as_ref(), format!("$_id.{}", name));
+    }
+
+    // a frequency table of a single column is a `$sortByCount`, which the server can
+    // compute with a sorted group.
+    if let ([_], [agg]) = (keys, aggs) {
+        let name = schema.iter_names().nth(1)?;
+        if is_count(agg) && is_plain_field(name) && name != "_id" {
+            let (key, key_expr) = id.into_iter().next()?;
+            return Some(vec![
+                doc! {"$sortByCount": key_expr},
+                doc! {"$project": {"_id": 0, key: "$_id", name: "$count"}},
+            ]);
+        }
+    }
+
+    let mut group = doc! {"_id": id};
+    for (agg, name) in aggs.iter().zip(schema.iter_names().skip(keys.len())) {
+        if !is_plain_field(name) || name == "_id" {
+            return None;
+        }
+        group.insert(name, accumulator(agg, input_schema)?);
+        project.insert(name, 1);
+    }
+    Some(vec![doc! {"$group": group}, doc! {"$project": project}])
+}
+
+/// The `$dateTrunc` unit and bin size of a polars duration like `15m`, `1h` or `1mo`.
+fn date_trunc_unit(every: &str) -> Option<(&'static str, i64)> {
+    let (n, unit) = every.split_at(every.find(|c: char| !c.is_ascii_digit())?);
+    let bin_size = n.parse().ok().filter(|n| *n > 0)?;
+    let unit = match unit {
+        "ms" => "millisecond",
+        "s" => "second",
+        "m" => "minute",
+        "h" => "hour",
+        "d" => "day",
+        "w" => "week",
+        "mo" => "month",
+        "y" => "year",
+        _ => return None,
+    };
+    Some((unit, bin_size))
+}
+
+/// The name of the column an aggregation outputs.
+fn agg_name(expr: &Expr) -> Option<&str> {
+    let input = match expr {
+        Expr::Alias(_, name) => return Some(name),
+        Expr::Count => return Some("count"),
+        Expr::Agg(agg) => match agg {
+            AggExpr::Count(input)
+            | AggExpr::Sum(input)
+            | AggExpr::Mean(input)
+            | AggExpr::Min(input)
+            | AggExpr::Max(input)
+            | AggExpr::First(input)
+            | AggExpr::Last(input) => input,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match input.as_ref() {
+        Expr::Column(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// Group by the `by` columns and windows of `every` of the `index_column` with a `$group` stage
+/// on `$dateTrunc`, sorted by the groups and the start of the windows.
+fn downsample_stages(
+    index_column: &str,
+    every: &str,
+    by: &[String],
+    aggs: &[Expr],
+    schema: &Schema,
+) -> Option<Vec<Document>> {
+    let (unit, bin_size) = date_trunc_unit(every)?;
+    if !is_plain_field(index_column)
+        || !matches!(
+            schema.get(index_column)?,
+            DataType::Datetime(_, _) | DataType::Date
+        )
+    {
+        return None;
+    }
+
+    let mut id = Document::new();
+    let mut sort = Document::new();
+    let mut project = doc! {"_id": 0};
+    for name in by {
+        if !is_plain_field(name) || name == index_column {
+            return None;
+        }
+        // a missing field and a null are the same group in polars, but different groups in mongodb.
+        id.insert(name, doc! {"$ifNull": [field_path(name)?, Bson::Null]});
+        sort.insert(format!("_id.{}", name), 1);
+        project.insert(name, format!("$_id.{}", name));
+    }
+    // polars windows start on mondays, mongodb weeks on sundays.
+    id.insert(
+        index_column,
+        doc! {"$dateTrunc": {
+            "date": field_path(index_column)?,
+            "unit": unit,
+            "binSize": bin_size,
+            "startOfWeek": "monday",
+        }},
+    );
+    sort.insert(format!("_id.{}", index_column), 1);
+    project.insert(index_column, format!("$_id.{}", index_column));
+
+    let mut group = doc! {"_id": id};
+    for agg in aggs {
+        let name = agg_name(agg)?;
+        if !is_plain_field(name) || name == "_id" || project.contains_key(name) {
+            return None;
+        }
+        group.insert(name, accumulator(agg, schema)?);
+        project.insert(name, 1);
+    }
+    Some(vec![
+        doc! {"$group": group},
+        doc! {"$sort": sort},
+        doc! {"$project": project},
+    ])
+}
+
+fn empty_frame(schema: &Schema) -> PolarsResult<LazyFrame> {
+    let columns = schema
+        .iter_fields()
+        .map(|fld| Series::new_empty(fld.name(), fld.data_type()))
+        .collect();
+    Ok(DataFrame::new(columns)?.lazy())
+}
+
+impl Compiler {
+    fn compile(&self, plan: LogicalPlan) -> PolarsResult<Compiled> {
+        use LogicalPlan::*;
+        let compiled = match plan {
+            AnonymousScan {
+                function, schema, ..
+            } if Arc::ptr_eq(&function, &self.scan) => Compiled::Pushed(Pushed {
+                stages: vec![],
+                source_types: self.source.source_types(&schema),
+                schema,
+            }),
+            Selection { input, predicate } => {
+                let input = self.compile(*input)?;
+                let filter_predicate = predicate.clone();
+                let translate = move |pushed: &Pushed| {
+                    let ctx = FilterContext {
+                        schema: &pushed.schema,
+                        source_types: &pushed.source_types,
+                        conversion: &self.source.conversion,
+                    };
+                    let filter = predicate::expr_to_filter(&filter_predicate, &ctx)?;
+                    Some((vec![doc! {"$match": filter}], None, None))
+                };
+                self.push(input, translate, |input| Selection { input, predicate })?
+            }
+            Projection {
+                expr,
+                input,
+                schema,
+            } => {
+                let input = self.compile(*input)?;
+                let columns = expr
+                    .iter()
+                    .map(|expr| {
+                        let (name, source) = column_selection(expr)?;
+                        Some((name.to_string(), source.to_string()))
+                    })
+                    .collect::<Option<Vec<_>>>();
+                let count = count_stages(&expr, &schema);
+                let output = schema.clone();
+                let translate = move |pushed: &Pushed| {
+                    if let Some(stages) = count {
+                        return Some((stages, Some(output), None));
+                    }
+                    let columns = columns?;
+                    let columns = || columns.iter().map(|(name, source)| (&**name, &**source));
+                    let stage = project_stage(columns())?;
+                    let source_types = selected_source_types(&pushed.source_types, columns());
+                    Some((vec![stage], Some(output), Some(source_types)))
+                };
+                self.push(input, translate, |input| Projection {
+                    expr,
+                    input,
+                    schema,
+                })?
+            }
+            HStack {
+                input,
+                exprs,
+                schema,
+            } => {
+                let input = self.compile(*input)?;
+                let columns = exprs
+                    .iter()
+                    .map(|expr| {
+                        let (name, source) = column_selection(expr)?;
+                        Some((name.to_string(), source.to_string()))
+                    })
+                    .collect::<Option<Vec<_>>>();
+                let output = schema.clone();
+                let translate = move |pushed: &Pushed| {
+                    let mut fields = Document::new();
+                    let mut source_types = pushed.source_types.clone();
+                    for (name, source) in columns? {
+                        fields.insert(
+                            is_plain_field(&name).then(|| name.clone())?,
+                            field_path(&source)?,
+                        );
+                        match pushed.source_types.get(&source) {
+                            Some(source_type) => source_types.insert(name, *source_type),
+                            None => source_types.remove(&name),
+                        };
+                    }
+                    Some((
+                        vec![doc! {"$addFields": fields}],
+                        Some(output),
+                        Some(source_types),
+                    ))
+                };
+                self.push(input, translate, |input| HStack {
+                    input,
+                    exprs,
+                    schema,
+                })?
+            }
+            // `LazyFrame::rename` adds the renamed columns with `with_columns`, and then moves
+            // them into the place of the originals.
+            MapFunction { input, function } if function.to_string() == "RENAME" => {
+                let input = self.compile(*input)?;
+                let rename = function.clone();
+                let translate = move |pushed: &Pushed| {
+                    let node = MapFunction {
+                        input: Box::new(empty_frame(&pushed.schema).ok()?.logical_plan),
+                        function: rename,
+                    };
+                    let output = LazyFrame::from(node).schema().ok()?;
+                    let columns = || {
+                        output
+                            .iter_names()
+                            .zip(pushed.schema.iter_names())
+                            .map(|(name, source)| (&**name, &**source))
+                    };
+                    let stage = project_stage(columns())?;
+                    let source_types = selected_source_types(&pushed.source_types, columns());
+                    Some((vec![stage], Some(output.clone()), Some(source_types)))
+                };
+                self.push(input, translate, |input| MapFunction { input, function })?
+            }
+            Sort {
+                input,
+                by_column,
+                args,
+            } => {
+                let input = self.compile(*input)?;
+                let (sort_by, sort_args) = (by_column.clone(), args.clone());
+                let translate = move |pushed: &Pushed| {
+                    let ctx = FilterContext {
+                        schema: &pushed.schema,
+                        source_types: &pushed.source_types,
+                        conversion: &self.source.conversion,
+                    };
+                    let mut stages = vec![sort_stage(&sort_by, &sort_args, &ctx)?];
+                    // a `limit` fused into the sort by the optimizer.
+                    if let Some((offset, len)) = sort_args.slice {
+                        stages.extend(slice_stages(offset, len as IdxSize)?);
+                    }
+                    Some((stages, None, None))
+                };
+                self.push(input, translate, |input| Sort {
+                    input,
+                    by_column,
+                    args,
+                })?
+            }
+            Slice { input, offset, len } => {
+                let input = self.compile(*input)?;
+                let stages = slice_stages(offset, len).map(|stages| (stages, None, None));
+                self.push(input, |_| stages, |input| Slice { input, offset, len })?
+            }
+            Distinct { input, options } => {
+                let input = self.compile(*input)?;
+                let distinct = options.clone();
+                let translate = move |pushed: &Pushed| {
+                    Some((distinct_stages(&distinct, &pushed.schema)?, None, None))
+                };
+                self.push(input, translate, |input| Distinct { input, options })?
+            }
+            Explode {
+                input,
+                columns,
+                schema,
+            } => {
+                let input = self.compile(*input)?;
+                let (unwound, explode_schema) = (columns.clone(), schema.clone());
+                let translate = move |pushed: &Pushed| {
+                    let stage = unwind_stage(&unwound, &pushed.schema, &pushed.source_types)?;
+                    Some((vec![stage], Some(explode_schema), None))
+                };
+                self.push(input, translate, |input| Explode {
+                    input,
+                    columns,
+                    schema,
+                })?
+            }
+            Aggregate {
+                input,
+                keys,
+                aggs,
+                schema,
+                apply,
+                maintain_order,
+                options,
+            } => {
+                let input = self.compile(*input)?;
+                let pushable = apply.is_none()
+                    && !maintain_order
+                    && options.dynamic.is_none()
+                    && options.rolling.is_none()
+                    && options.slice.is_none();
+                let (group_keys, group_aggs, group_schema) =
+                    (keys.clone(), aggs.clone(), schema.clone());
+                let translate = move |pushed: &Pushed| {
+                    let stages = pushable.then(|| {
+                        group_stages(&group_keys, &group_aggs, &pushed.schema, &group_schema)
+                    })??;
+                    Some((stages, Some(group_schema), None))
+                };
+                self.push(input, translate, |input| Aggregate {
+                    input,
+                    keys,
+                    aggs,
+                    schema,
+                    apply,
+                    maintain_order,
+                    options,
+                })?
+            }
+            plan => Compiled::Plan(Box::new(plan)),
+        };
+        Ok(compiled)
+    }
+
+    /// Append the stages of a node to its pushed down input.
+    ///
+    /// `translate` gets the pushed down input and returns the stages, and the new schema and
+    /// source types if they change. A new schema without source types clears them, as the
+    /// columns may be computed by the stages. If the node can't be translated, or the input
+    /// was not pushed down, the node is rebuilt with `node` on top of its input instead.
+    fn push(
+        &self,
+        input: Compiled,
+        translate: impl FnOnce(&Pushed) -> Option<Translation>,
+        node: impl FnOnce(Box<LogicalPlan>) -> LogicalPlan,
+    ) -> PolarsResult<Compiled> {
+        match input {
+            Compiled::Pushed(mut pushed) => match translate(&pushed) {
+                Some((stages, schema, source_types)) => {
+                    pushed.stages.extend(stages);
+                    if let Some(schema) = schema {
+                        pushed.schema = schema;
+                        pushed.source_types = source_types.unwrap_or_default();
+                    }
+                    Ok(Compiled::Pushed(pushed))
+                }
+                None => Ok(Compiled::Plan(Box::new(node(Box::new(
+                    self.materialize(pushed)?,
+                ))))),
+            },
+            Compiled::Plan(plan) => Ok(Compiled::Plan(Box::new(node(plan)))),
+        }
+    }
+
+    /// Scan the output of the pushed down stages.
+    fn materialize(&self, pushed: Pushed) -> PolarsResult<LogicalPlan> {
+        let mut options = MongoScanOptions {
+            schema: Some(pushed.schema.as_ref().clone()),
+            columns: None,
+            include_id: true,
+            ..self.options.clone()
+        };
+        // nothing to translate, so the collection can be scanned in parallel as usual.
+        if pushed.stages.is_empty() {
+            return Ok(scan_mongo(options, None)?.logical_plan);
+        }
+        // a top-k query is a sorted and limited `find`, which mongodb can answer from an index
+        // without sorting the whole collection.
+        let unlimited =
+            options.sort.is_none() && options.n_rows.is_none() && options.skip_rows.is_none();
+        if let Some((sort, skip, limit)) = top_k(&pushed.stages).filter(|_| unlimited) {
+            options.sort = Some(sort);
+            options.skip_rows = skip;
+            options.n_rows = Some(limit);
+            return Ok(scan_mongo(options, None)?.logical_plan);
+        }
+
+        let mut pipeline = self.base.clone();
+        pipeline.extend(pushed.stages);
+        // these are part of the pipeline now.
+        options.filter = None;
+        options.predicate = None;
+        options.sort = None;
+        options.skip_rows = None;
+        options.n_rows = None;
+        options.dtype_overrides = None;
+        // the rows are in the order of the pushed down sort, which a `$sort` on `_id` appended
+        // after the stages would undo.
+        if pipeline.iter().any(|stage| stage.contains_key("$sort")) {
+            options.ordered = false;
+        }
+        Ok(scan_mongo(options, Some(pipeline))?.logical_plan)
+    }
+}
+
+/// Stages for the filter, sort and row limit of the scan options, or `None` if a query on
+/// top of the scan can't be pushed down.
+fn base_stages(options: &MongoScanOptions, source: &MongoSource) -> Option<Vec<Document>> {
+    // the query runs on the filtered rows, so it can only be pushed down
+    // if all of the filtering happens server side.
+    // columns flattened with another separator than `.` don't name the fields they are read
+    // from, and neither do the fields of unnested columns or renamed columns.
+    // row counts are added on top of the scan, which can't be translated into a stage.
+    let flattened_names = !matches!(options.flattening().as_deref(), None | Some("."));
+    if source.remaining.is_some()
+        || options.n_rows == Some(0)
+        || flattened_names
+        || !options.unnest_columns.is_empty()
+        || options.rename.is_some()
+        || options.row_count.is_some()
+    {
+        return None;
+    }
+
+    let mut base = vec![];
+    // `options.filter` and the translated part of `options.predicate`.
+    if let Some(filter) = &source.scan.filter {
+        base.push(doc! {"$match": filter.clone()});
+    }
+    if let Some(sort) = &options.sort {
+        base.push(doc! {"$sort": sort.clone()});
+    }
+    if let Some(skip_rows) = options.skip_rows {
+        base.push(doc! {"$skip": skip_rows as i64});
+    }
+    if let Some(n_rows) = options.n_rows {
+        base.push(doc! {"$limit": n_rows as i64});
+    }
+    Some(base)
+}
+
+/// Compile `query` on top of the scan of the collection.
+fn compile_query(
+    options: MongoScanOptions,
+    source: MongoSource,
+    base: Vec<Document>,
+    query: impl FnOnce(LazyFrame) -> LazyFrame,
+) -> PolarsResult<(Compiler, Compiled)> {
+    let scan = match &source.lf.logical_plan {
+        LogicalPlan::AnonymousScan { function, .. } => function.clone(),
+        _ => unreachable!("a scan without a remaining predicate is a single node"),
+    };
+    let compiler = Compiler {
+        options,
+        scan,
+        source: source.scan,
+        base,
+    };
+    let compiled = compiler.compile(query(source.lf).logical_plan)?;
+    Ok((compiler, compiled))
+}
+
+pub(crate) fn scan_mongo_query(
+    options: MongoScanOptions,
+    query: impl FnOnce(LazyFrame) -> LazyFrame,
+) -> PolarsResult<LazyFrame> {
+    let source = scan_mongo_source(options.clone(), None)?;
+    let base = match base_stages(&options, &source) {
+        Some(base) => base,
+        None => return Ok(query(source.lf)),
+    };
+    let (compiler, compiled) = compile_query(options, source, base, query)?;
+    let plan = match compiled {
+        Compiled::Pushed(pushed) => compiler.materialize(pushed)?,
+        Compiled::Plan(plan) => *plan,
+    };
+    Ok(LazyFrame::from(plan))
+}
+
+pub(crate) fn scan_mongo_downsample(
+    options: MongoScanOptions,
+    index_column: &str,
+    every: &str,
+    by: &[String],
+    aggs: &[Expr],
+) -> PolarsResult<LazyFrame> {
+    let untranslatable = || {
+        PolarsError::InvalidOperation(
+            format!(
+                "the downsampling of {} by {} can't be translated into an aggregation pipeline",
+                index_column, every
+            )
+            .into(),
+        )
+    };
+    let source = scan_mongo_source(options.clone(), None)?;
+    let mut pipeline = base_stages(&options, &source).ok_or_else(untranslatable)?;
+    let schema = source.schema;
+    pipeline.extend(
+        downsample_stages(index_column, every, by, aggs, &schema).ok_or_else(untranslatable)?,
+    );
+
+    // these are part of the pipeline now, and the schema is inferred from its output.
+    let options = MongoScanOptions {
+        schema: None,
+        columns: None,
+        include_id: true,
+        filter: None,
+        predicate: None,
+        sort: None,
+        skip_rows: None,
+        n_rows: None,
+        dtype_overrides: None,
+        ..options
+    };
+    scan_mongo(options, Some(pipeline))
+}
+
+pub(crate) fn merge_mongo_query(
+    options: MongoScanOptions,
+    query: impl FnOnce(LazyFrame) -> LazyFrame,
+    output: &PipelineOutput,
+) -> PolarsResult<()> {
+    let untranslatable = || {
+        PolarsError::InvalidOperation(
+            "the query can't be fully translated into an aggregation pipeline".into(),
+        )
+    };
+    let source = scan_mongo_source(options.clone(), None)?;
+    let base = base_stages(&options, &source).ok_or_else(untranslatable)?;
+    let scan = MongoScan::from_options(&options)?
+        .with_max_time(options.max_time_ms.map(Duration::from_millis))
+        .with_allow_disk_use(options.allow_disk_use);
+    let (compiler, compiled) = compile_query(options, source, base, query)?;
+    let pushed = match compiled {
+        Compiled::Pushed(pushed) => pushed,
+        Compiled::Plan(_) => return Err(untranslatable()),
+    };
+
+    let mut pipeline = compiler.base;
+    pipeline.extend(pushed.stages);
+    pipeline.push(output.stage());
+    // the output is written when the pipeline runs, the cursor itself is empty.
+    scan.get_collection()?
+        .aggregate(pipeline, scan.aggregate_options())
+        .map_err(|err| scan.driver_error(err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::ConversionOptions;
+
+    fn schema() -> Schema {
+        Schema::from(
+            vec![
+                Field::new("a", DataType::Int64),
+                Field::new("name", DataType::Utf8),
+                Field::new("mixed", DataType::Utf8),
+            ]
+            .into_iter(),
+        )
+    }
+
+    fn source_types() -> SourceTypes {
+        [
+            ("a", Some(ElementType::Double)),
+            ("name", Some(ElementType::String)),
+            ("mixed", None),
+        ]
+        .into_iter()
+        .map(|(name, source)| (name.to_string(), source))
+        .collect()
+    }
+
+    /// The `$sort` stage of sorting [`schema`] by `columns`.
+    fn sort(columns: &[&str], reverse: bool) -> Option<Document> {
+        let (schema, source_types) = (schema(), source_types());
+        let conversion = ConversionOptions::default();
+        let ctx = FilterContext {
+            schema: &schema,
+            source_types: &source_types,
+            conversion: &conversion,
+        };
+        let args = SortArguments {
+            reverse: vec![reverse; columns.len()],
+            nulls_last: reverse,
+            slice: None,
+        };
+        let by_column = columns.iter().map(|name| col(name)).collect::<Vec<_>>();
+        sort_stage(&by_column, &args, &ctx)
+    }
+
+    #[test]
+    fn sort_on_single_typed_columns() {
+        assert_eq!(
+            sort(&["a", "name"], false),
+            Some(doc! {"$sort": {"a": 1, "name": 1}})
+        );
+        assert_eq!(sort(&["a"], true), Some(doc! {"$sort": {"a": -1}}));
+    }
+
+    #[test]
+    fn sort_on_mixed_or_unknown_columns_is_not_pushed() {
+        assert_eq!(sort(&["mixed"], false), None);
+        assert_eq!(sort(&["a", "missing"], false), None);
+    }
+
+    #[test]
+    fn slices_skip_and_limit() {
+        assert_eq!(slice_stages(0, 10), Some(vec![doc! {"$limit": 10_i64}]));
+        assert_eq!(
+            slice_stages(5, 10),
+            Some(vec![doc! {"$skip": 5_i64}, doc! {"$limit": 10_i64}])
+        );
+        assert_eq!(slice_stages(-5, 10), None);
+        assert_eq!(slice_stages(0, 0), None);
+    }
+
+    #[test]
+    fn project_renamed_columns() {
+        assert_eq!(
+            project_stage([("b", "a"), ("name", "name")].into_iter()),
+            Some(doc! {"$project": {"_id": 0, "b": "$a", "name": "$name"}})
+        );
+        assert_eq!(project_stage([("a.b", "a")].into_iter()), None);
+    }
+
+    #[test]
+    fn distinct_rows_group_on_every_column() {
+        let options = DistinctOptions {
+            subset: None,
+            maintain_order: false,
+            keep_strategy: UniqueKeepStrategy::First,
+        };
+        let stages = distinct_stages(&options, &schema()).unwrap();
+        assert_eq!(
+            stages[0],
+            doc! {"$group": {"_id": {
+                "a": {"$ifNull": ["$a", Bson::Null]},
+                "name": {"$ifNull": ["$name", Bson::Null]},
+                "mixed": {"$ifNull": ["$mixed", Bson::Null]},
+            }}}
+        );
+
+        let ordered = DistinctOptions {
+            maintain_order: true,
+            ..options
+        };
+        assert_eq!(distinct_stages(&ordered, &schema()), None);
+    }
+
+    #[test]
+    fn count_is_zero_for_an_empty_input() {
+        let schema = Schema::from(vec![Field::new("n", DataType::UInt32)].into_iter());
+        assert_eq!(
+            count_stages(&[count().alias("n")], &schema),
+            Some(vec![
+                doc! {"$facet": {"rows": [{"$count": "count"}]}},
+                doc! {"$project": {
+                    "n": {"$ifNull": [{"$arrayElemAt": ["$rows.count", 0]}, 0]},
+                }},
+            ])
+        );
+        assert_eq!(count_stages(&[count(), col("a")], &schema), None);
+    }
+
+    #[test]
+    fn unwind_only_arrays() {
+        let schema = Schema::from(
+            vec![
+                Field::new("tags", DataType::List(Box::new(DataType::Utf8))),
+                Field::new("bytes", DataType::List(Box::new(DataType::UInt8))),
+                Field::new("name", DataType::Utf8),
+            ]
+            .into_iter(),
+        );
+        let source_types: SourceTypes = [
+            ("tags", Some(ElementType::Array)),
+            ("bytes", Some(ElementType::Binary)),
+            ("name", Some(ElementType::String)),
+        ]
+        .into_iter()
+        .map(|(name, source)| (name.to_string(), source))
+        .collect();
+        let unwind = |columns: &[&str]| {
+            let columns = columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+            unwind_stage(&columns, &schema, &source_types)
+        };
+
+        assert_eq!(
+            unwind(&["tags"]),
+            Some(doc! {"$unwind": {"path": "$tags", "preserveNullAndEmptyArrays": true}})
+        );
+        assert_eq!(unwind(&["bytes"]), None);
+        assert_eq!(unwind(&["name"]), None);
+        assert_eq!(unwind(&["tags", "tags"]), None);
+    }
+
+    #[test]
+    fn group_with_accumulators() {
+        let output = Schema::from(
+            vec![
+                Field::new("name", DataType::Utf8),
+                Field::new("total", DataType::Int64),
+                Field::new("last", DataType::Int64),
+            ]
+            .into_iter(),
+        );
+        let aggs = [col("a").sum().alias("total"), col("a").last().alias("last")];
+        assert_eq!(
+            group_stages(&[col("name")], &aggs, &schema(), &output),
+            Some(vec![
+                doc! {"$group": {
+                    "_id": {"name": {"$ifNull": ["$name", Bson::Null]}},
+                    "total": {"$sum": "$a"},
+                    "last": {"$last": "$a"},
+                }},
+                doc! {"$project": {"_id": 0, "name": "$_id.name", "total": 1, "last": 1}},
+            ])
+        );
+        // `$sum` skips strings instead of failing like polars.
+        let aggs = [col("name").sum().alias("total")];
+        assert_eq!(group_stages(&[col("a")], &aggs, &schema(), &output), None);
+    }
+
+    #[test]
+    fn count_of_a_single_key_is_a_sort_by_count() {
+        let output = Schema::from(
+            vec![
+                Field::new("name", DataType::Utf8),
+                Field::new("n", DataType::UInt32),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(
+            group_stages(&[col("name")], &[count().alias("n")], &schema(), &output),
+            Some(vec![
+                doc! {"$sortByCount": {"$ifNull": ["$name", Bson::Null]}},
+                doc! {"$project": {"_id": 0, "name": "$_id", "n": "$count"}},
+            ])
+        );
+    }
+
+    #[test]
+    fn top_k_of_a_sort_and_limit() {
+        let sort = doc! {"$sort": {"a": -1}};
+        assert_eq!(
+            top_k(&[sort.clone(), doc! {"$limit": 10_i64}]),
+            Some((doc! {"a": -1}, None, 10))
+        );
+        assert_eq!(
+            top_k(&[sort.clone(), doc! {"$skip": 5_i64}, doc! {"$limit": 10_i64}]),
+            Some((doc! {"a": -1}, Some(5), 10))
+        );
+        assert_eq!(top_k(&[sort.clone()]), None);
+        assert_eq!(
+            top_k(&[doc! {"$match": {"a": 1}}, doc! {"$limit": 10_i64}]),
+            None
+        );
+    }
+
+    #[test]
+    fn date_trunc_units() {
+        assert_eq!(date_trunc_unit("15m"), Some(("minute", 15)));
+        assert_eq!(date_trunc_unit("1mo"), Some(("month", 1)));
+        assert_eq!(date_trunc_unit("0h"), None);
+        assert_eq!(date_trunc_unit("1h30m"), None);
+        assert_eq!(date_trunc_unit("h"), None);
+    }
+
+    #[test]
+    fn downsample_groups_by_truncated_dates() {
+        let schema = Schema::from(
+            vec![
+                Field::new("ts", DataType::Datetime(TimeUnit::Milliseconds, None)),
+                Field::new("sensor", DataType::Utf8),
+                Field::new("a", DataType::Int64),
+            ]
+            .into_iter(),
+        );
+        let aggs = [col("a").mean()];
+        assert_eq!(
+            downsample_stages("ts", "1h", &["sensor".to_string()], &aggs, &schema),
+            Some(vec![
+                doc! {"$group": {
+                    "_id": {
+                        "sensor": {"$ifNull": ["$sensor", Bson::Null]},
+                        "ts": {"$dateTrunc": {
+                            "date": "$ts",
+                            "unit": "hour",
+                            "binSize": 1_i64,
+                            "startOfWeek": "monday",
+                        }},
+                    },
+                    "a": {"$avg": "$a"},
+                }},
+                doc! {"$sort": {"_id.sensor": 1, "_id.ts": 1}},
+                doc! {"$project": {"_id": 0, "sensor": "$_id.sensor", "ts": "$_id.ts", "a": 1}},
+            ])
+        );
+        // the index column must be a date.
+        assert_eq!(downsample_stages("sensor", "1h", &[], &aggs, &schema), None);
+        // an aggregation can't overwrite a group column.
+        let aggs = [col("sensor").first()];
+        assert_eq!(
+            downsample_stages("ts", "1h", &["sensor".to_string()], &aggs, &schema),
+            None
+        );
+    }
+}
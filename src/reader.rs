@@ -0,0 +1,68 @@
+//! Eager reads of a collection into a [`DataFrame`].
+use polars::prelude::*;
+
+use crate::{scan_mongo, MongoScanOptions};
+
+/// Read a collection into a [`DataFrame`] in one go.
+///
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::prelude::*;
+///
+/// # fn main() -> PolarsResult<()> {
+/// let options = MongoScanOptions {
+///     connection_str: "mongodb://localhost:27017".into(),
+///     db: "db".into(),
+///     collection: "users".into(),
+///     ..Default::default()
+/// };
+/// let df = MongoReader::new(options)
+///     .with_columns(Some(vec!["name".into(), "age".into()]))
+///     .finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MongoReader {
+    options: MongoScanOptions,
+    columns: Option<Vec<String>>,
+    rechunk: bool,
+}
+
+impl MongoReader {
+    pub fn new(options: MongoScanOptions) -> Self {
+        MongoReader {
+            options,
+            columns: None,
+            rechunk: false,
+        }
+    }
+
+    /// Only read these columns, in this order. The other fields are not fetched from mongodb.
+    pub fn with_columns(mut self, columns: Option<Vec<String>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Stop reading after `n_rows` documents.
+    pub fn with_n_rows(mut self, n_rows: Option<usize>) -> Self {
+        self.options.n_rows = n_rows;
+        self
+    }
+
+    pub fn set_rechunk(mut self, rechunk: bool) -> Self {
+        self.rechunk = rechunk;
+        self
+    }
+
+    pub fn finish(self) -> PolarsResult<DataFrame> {
+        let mut lf = scan_mongo(self.options, None)?;
+        if let Some(columns) = &self.columns {
+            lf = lf.select(columns.iter().map(|name| col(name)).collect::<Vec<_>>());
+        }
+        let mut df = lf.collect()?;
+        if self.rechunk {
+            df.rechunk();
+        }
+        Ok(df)
+    }
+}
@@ -0,0 +1,110 @@
+//! Retrying the reads of a scan after transient server errors.
+use std::time::Duration;
+
+#[cfg(feature = "sync")]
+use polars::prelude::*;
+
+use mongodb::error::{Error, ErrorKind};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Server error codes of unreachable hosts, network failures and replica set elections.
+const TRANSIENT_CODES: [i32; 12] = [
+    6,     // HostUnreachable
+    7,     // HostNotFound
+    89,    // NetworkTimeout
+    91,    // ShutdownInProgress
+    189,   // PrimarySteppedDown
+    262,   // ExceededTimeLimit
+    9001,  // SocketException
+    10107, // NotWritablePrimary
+    11600, // InterruptedAtShutdown
+    11602, // InterruptedDueToReplStateChange
+    13435, // NotPrimaryNoSecondaryOk
+    13436, // NotPrimaryOrSecondary
+];
+
+/// How often and how fast a failed read is retried.
+///
+/// A partition of a scan that fails with a retryable error is read again from the start,
+/// so documents that were already read are not duplicated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RetryPolicy {
+    /// Attempts per read, including the first one.
+    pub max_attempts: usize,
+    /// Wait before the first retry, doubled after every further attempt.
+    pub backoff: Duration,
+    /// Upper bound of the wait between two attempts.
+    pub max_backoff: Duration,
+    /// Server error codes that are retried. Network errors are always retried.
+    pub retryable_codes: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            retryable_codes: TRANSIENT_CODES.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a read that failed with `err` should be attempted again.
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        match &*err.kind {
+            ErrorKind::Io(_) | ErrorKind::ServerSelection { .. } => true,
+            ErrorKind::Command(cmd) => self.retryable_codes.contains(&cmd.code),
+            _ => false,
+        }
+    }
+
+    /// Run `read` until it succeeds, fails with an error that is not retryable,
+    /// or runs out of attempts.
+    #[cfg(feature = "sync")]
+    pub(crate) fn run<T>(
+        &self,
+        mut read: impl FnMut() -> Result<T, ReadError>,
+    ) -> Result<T, ReadError> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match read() {
+                Err(ReadError::Driver(err))
+                    if attempt < self.max_attempts && self.is_retryable(&err) =>
+                {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(attempt, error = %err, ?backoff, "retrying read");
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// An error of a single read, keeping errors of the driver apart so they can be retried.
+#[cfg(feature = "sync")]
+pub(crate) enum ReadError {
+    Driver(Error),
+    Polars(PolarsError),
+}
+
+#[cfg(feature = "sync")]
+impl From<PolarsError> for ReadError {
+    fn from(err: PolarsError) -> Self {
+        ReadError::Polars(err)
+    }
+}
+
+#[cfg(feature = "sync")]
+impl From<Error> for ReadError {
+    fn from(err: Error) -> Self {
+        ReadError::Driver(err)
+    }
+}
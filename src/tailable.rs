@@ -0,0 +1,144 @@
+//! Follow a capped collection with a tailable cursor.
+use std::time::{Duration, Instant};
+
+use polars::prelude::*;
+
+use mongodb::{
+    bson::Document,
+    options::{CursorType, FindOptions},
+    sync::Cursor,
+};
+
+use crate::{read_documents, MongoScan, MongoScanOptions};
+
+/// Tails a capped collection and yields the new documents as dataframes.
+///
+/// A tailable cursor stays open after the last document, and returns the documents
+/// that are inserted afterwards. Reading blocks until the next document arrives, so a batch
+/// is returned once `batch_size` documents are read, or with the first document that
+/// arrives after the flush interval has passed.
+pub struct MongoTailableReader {
+    scan: MongoScan,
+    cursor: Option<Cursor<Document>>,
+    schema: Option<Schema>,
+    pub batch_size: usize,
+    pub await_data: bool,
+    pub flush_interval: Duration,
+}
+
+impl MongoTailableReader {
+    /// Tail the collection in `options`, starting from the documents matching `options.filter`.
+    ///
+    /// `options.batch_size` is used as the number of documents per batch. If no schema is provided,
+    /// it is inferred from every batch.
+    pub fn new(options: MongoScanOptions) -> PolarsResult<Self> {
+        let scan = MongoScan::from_options(&options)?
+            .with_batch_size(options.batch_size)
+            .with_flatten(options.flattening())
+            .with_filter(options.filter)
+            .with_decimal128_as_float(options.decimal128_as_float)
+            .with_object_id_as(options.object_id_as)
+            .with_timestamp_as(options.timestamp_as)
+            .with_regex_as(options.regex_as)
+            .with_uuid_as(options.uuid_as)
+            .with_fallback_dtype(options.fallback_dtype)
+            .with_datetime_time_unit(options.datetime_time_unit)
+            .with_parse_mode(options.parse_mode)
+            .with_parse_report(options.parse_report);
+
+        Ok(MongoTailableReader {
+            scan,
+            cursor: None,
+            schema: options.schema,
+            batch_size: options.batch_size.unwrap_or(1000).max(1),
+            await_data: true,
+            flush_interval: Duration::from_secs(1),
+        })
+    }
+
+    /// Let the server wait for new documents before returning an empty batch, instead of
+    /// polling continuously. Defaults to `true`.
+    pub fn with_await_data(mut self, await_data: bool) -> Self {
+        self.await_data = await_data;
+        self
+    }
+
+    /// Return a batch with fewer than `batch_size` documents once this interval has passed
+    /// since the first document of the batch. Defaults to one second.
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    fn cursor(&mut self) -> PolarsResult<&mut Cursor<Document>> {
+        if self.cursor.is_none() {
+            let cursor_type = match self.await_data {
+                true => CursorType::TailableAwait,
+                false => CursorType::Tailable,
+            };
+            let find_options = FindOptions::builder()
+                .cursor_type(cursor_type)
+                .batch_size(self.scan.batch_size.map(|b| b as u32))
+                .max_await_time(Some(self.flush_interval).filter(|_| self.await_data))
+                .build();
+            let cursor = self
+                .scan
+                .get_collection()?
+                .find(self.scan.filter.clone(), Some(find_options))
+                .map_err(|err| self.scan.driver_error(err))?;
+            self.cursor = Some(cursor);
+        }
+        Ok(self.cursor.as_mut().unwrap())
+    }
+
+    /// Block until new documents are available and return the next batch.
+    ///
+    /// Returns `None` once the cursor is closed by the server, e.g. because
+    /// the collection is not capped or was dropped.
+    pub fn next_batch(&mut self) -> PolarsResult<Option<DataFrame>> {
+        let batch_size = self.batch_size;
+        let flush_interval = self.flush_interval;
+        let cursor = self.cursor()?;
+
+        let mut docs = Vec::with_capacity(batch_size);
+        let mut started: Option<Instant> = None;
+        while docs.len() < batch_size {
+            if started.is_some_and(|t| t.elapsed() >= flush_interval) {
+                break;
+            }
+            let advanced = cursor
+                .advance()
+                .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+            if !advanced {
+                break;
+            }
+            let doc = cursor
+                .deserialize_current()
+                .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+            docs.push(doc);
+            started.get_or_insert_with(Instant::now);
+        }
+        if docs.is_empty() {
+            return Ok(None);
+        }
+
+        let n_docs = docs.len();
+        let mut docs = docs.into_iter();
+        read_documents(
+            || Ok(docs.next()),
+            self.schema.clone(),
+            Some(n_docs),
+            None,
+            &self.scan.conversion,
+        )
+        .map(Some)
+    }
+}
+
+impl Iterator for MongoTailableReader {
+    type Item = PolarsResult<DataFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
+    }
+}
@@ -0,0 +1,90 @@
+//! Limiting the load scans put on the cluster, shared by all workers of a scan.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Rate of documents and number of concurrent partition reads of a scan.
+pub(crate) struct Throttle {
+    pub(crate) max_docs_per_second: Option<u32>,
+    pub(crate) max_batches_in_flight: Option<usize>,
+    /// Documents read by all workers, paid for in chunks.
+    documents: AtomicUsize,
+    /// Earliest time the next chunk of documents may be read.
+    next_slot: Mutex<Option<Instant>>,
+    in_flight: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle {
+            max_docs_per_second: None,
+            max_batches_in_flight: None,
+            documents: AtomicUsize::new(0),
+            next_slot: Mutex::new(None),
+            in_flight: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+}
+
+/// A throttle with the same limits, that counts the documents of its own scan.
+impl Clone for Throttle {
+    fn clone(&self) -> Self {
+        Throttle {
+            max_docs_per_second: self.max_docs_per_second,
+            max_batches_in_flight: self.max_batches_in_flight,
+            ..Throttle::default()
+        }
+    }
+}
+
+impl Throttle {
+    /// Count a document, and wait if the workers are reading faster than `max_docs_per_second`.
+    ///
+    /// Waiting for every single document would serialize the workers on the lock, so the
+    /// documents are paid for in chunks of about a tenth of a second.
+    pub(crate) fn document(&self) {
+        let rate = match self.max_docs_per_second {
+            Some(rate) if rate > 0 => rate as usize,
+            _ => return,
+        };
+        let chunk = (rate / 10).clamp(1, 1000);
+        if (self.documents.fetch_add(1, Ordering::Relaxed) + 1) % chunk != 0 {
+            return;
+        }
+        let now = Instant::now();
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = next_slot.map_or(now, |next| next.max(now));
+            *next_slot = Some(slot + Duration::from_secs_f64(chunk as f64 / rate as f64));
+            slot
+        };
+        if slot > now {
+            std::thread::sleep(slot - now);
+        }
+    }
+
+    /// Wait until less than `max_batches_in_flight` partitions are read, and hold a slot
+    /// until the returned guard is dropped.
+    pub(crate) fn enter(&self) -> Option<InFlight<'_>> {
+        let max = self.max_batches_in_flight?.max(1);
+        let mut in_flight = self
+            .released
+            .wait_while(self.in_flight.lock().unwrap(), |n| *n >= max)
+            .unwrap();
+        *in_flight += 1;
+        drop(in_flight);
+        Some(InFlight(self))
+    }
+}
+
+/// A partition that is being read, released on drop.
+pub(crate) struct InFlight<'a>(&'a Throttle);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        *self.0.in_flight.lock().unwrap() -= 1;
+        self.0.released.notify_one();
+    }
+}
@@ -0,0 +1,175 @@
+//! Scan collections with a schema derived from a rust struct, instead of inferring it.
+use polars::prelude::*;
+
+use mongodb::{
+    bson::{self, oid::ObjectId, DateTime, Document},
+    options::FindOptions,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::conversion::ConversionOptions;
+use crate::{projection, read_documents, MongoScan, MongoScanOptions};
+
+/// The dtype of the column a rust type is read into, from the bson it serializes to.
+pub trait ToDataType {
+    fn dtype() -> DataType;
+}
+
+/// The schema of a struct, with a column per field.
+///
+/// Implemented for structs declared with [`mongo_schema!`](crate::mongo_schema).
+pub trait ToSchema {
+    fn schema() -> Schema;
+}
+
+macro_rules! impl_to_data_type {
+    ($($ty:ty => $dtype:expr),* $(,)?) => {
+        $(
+            impl ToDataType for $ty {
+                fn dtype() -> DataType {
+                    $dtype
+                }
+            }
+        )*
+    };
+}
+
+// bson only has signed 32 and 64 bit integers, so the smaller types are widened.
+impl_to_data_type!(
+    bool => DataType::Boolean,
+    i8 => DataType::Int32,
+    i16 => DataType::Int32,
+    i32 => DataType::Int32,
+    i64 => DataType::Int64,
+    u8 => DataType::Int32,
+    u16 => DataType::Int32,
+    u32 => DataType::Int64,
+    f32 => DataType::Float64,
+    f64 => DataType::Float64,
+    String => DataType::Utf8,
+    ObjectId => DataType::Utf8,
+    DateTime => DataType::Datetime(TimeUnit::Milliseconds, None),
+);
+
+impl<T: ToDataType> ToDataType for Option<T> {
+    fn dtype() -> DataType {
+        T::dtype()
+    }
+}
+
+impl<T: ToDataType> ToDataType for Vec<T> {
+    fn dtype() -> DataType {
+        DataType::List(Box::new(T::dtype()))
+    }
+}
+
+/// Declare a struct and implement [`ToSchema`](crate::typed::ToSchema) for it, so it can be
+/// scanned with [`scan_mongo_typed`](crate::typed::scan_mongo_typed).
+///
+/// This declarative macro stands in for a derive on purpose, which would need a separate
+/// proc-macro crate. The columns are named after the fields, so serde renames of the fields
+/// are not supported.
+/// Structs declared this way can be nested in each other, and are read as `Struct` columns.
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::{mongo_schema, prelude::*, typed::scan_mongo_typed};
+/// use serde::{Deserialize, Serialize};
+///
+/// mongo_schema! {
+///     #[derive(Serialize, Deserialize)]
+///     struct User {
+///         name: String,
+///         age: Option<i32>,
+///         tags: Vec<String>,
+///     }
+/// }
+///
+/// # fn main() -> PolarsResult<()> {
+/// # let options = MongoScanOptions::default();
+/// let df = scan_mongo_typed::<User>(options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! mongo_schema {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::typed::ToSchema for $name {
+            fn schema() -> $crate::polars::prelude::Schema {
+                let mut schema = $crate::polars::prelude::Schema::new();
+                $(
+                    schema.with_column(
+                        stringify!($field).to_string(),
+                        <$ty as $crate::typed::ToDataType>::dtype(),
+                    );
+                )*
+                schema
+            }
+        }
+
+        impl $crate::typed::ToDataType for $name {
+            fn dtype() -> $crate::polars::prelude::DataType {
+                let schema = <$name as $crate::typed::ToSchema>::schema();
+                $crate::polars::prelude::DataType::Struct(schema.iter_fields().collect())
+            }
+        }
+    };
+}
+
+/// Read the documents of a collection into a [`DataFrame`] with the schema of `T`.
+///
+/// Only the fields of `T` are fetched, and every document is deserialized into a `T`, so
+/// documents that don't match the struct fail the scan instead of being read as nulls.
+/// `ObjectId`s are read as hex strings and datetimes with millisecond precision.
+pub fn scan_mongo_typed<T>(options: MongoScanOptions) -> PolarsResult<DataFrame>
+where
+    T: DeserializeOwned + Serialize + ToSchema,
+{
+    let schema = T::schema();
+    let conversion = ConversionOptions::default();
+    let scan = MongoScan::from_options(&options)?;
+
+    let find_options = FindOptions::builder()
+        .projection(projection(&schema, &conversion))
+        // mongodb treats a limit of 0 as no limit at all.
+        .limit(options.n_rows.filter(|n| *n > 0).map(|n| n as i64))
+        .batch_size(options.batch_size.map(|b| b as u32))
+        .sort(options.sort)
+        .max_time(options.max_time_ms.map(std::time::Duration::from_millis))
+        .allow_disk_use(options.allow_disk_use.then_some(true))
+        .build();
+    let mut cursor = scan
+        .get_collection()?
+        .find(options.filter, Some(find_options))
+        .map_err(|err| scan.driver_error(err))?;
+
+    let next_document = || -> PolarsResult<Option<Document>> {
+        let doc = match cursor.next() {
+            Some(doc) => {
+                doc.map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?
+            }
+            None => return Ok(None),
+        };
+        let value: T = bson::from_document(doc)
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))?;
+        bson::to_document(&value)
+            .map(Some)
+            .map_err(|err| PolarsError::ComputeError(format!("{:#?}", err).into()))
+    };
+    read_documents(
+        next_document,
+        Some(schema),
+        None,
+        options.n_rows,
+        &conversion,
+    )
+}
@@ -0,0 +1,112 @@
+//! Atlas vector search results as a [`LazyFrame`].
+use polars::prelude::*;
+
+use mongodb::bson::{doc, Document};
+
+use crate::{scan_mongo, MongoScanOptions};
+
+/// Runs an Atlas `$vectorSearch` on a collection and scans the nearest documents, together
+/// with their similarity score.
+///
+/// `options.filter`, `options.predicate` and `options.sort` are applied to the results of the
+/// search, i.e. to the `k` nearest documents. Use [`MongoVectorSearch::with_filter`] to only
+/// search the documents matching a filter on fields indexed as `filter` fields.
+///
+/// ```no_run
+/// use polars::prelude::*;
+/// use polars_mongo::prelude::*;
+///
+/// # fn main() -> PolarsResult<()> {
+/// # let options = MongoScanOptions::default();
+/// # let embedding = vec![0.0; 1536];
+/// let df = MongoVectorSearch::new(options, "embedding_index", "embedding", embedding)
+///     .with_k(20)
+///     .finish()?
+///     .sort("score", SortOptions { descending: true, ..Default::default() })
+///     .collect()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MongoVectorSearch {
+    options: MongoScanOptions,
+    index: String,
+    path: String,
+    query_vector: Vec<f64>,
+    k: usize,
+    num_candidates: Option<usize>,
+    filter: Option<Document>,
+    score_column: String,
+}
+
+impl MongoVectorSearch {
+    /// Search the `path` field of the collection, indexed by the vector search index `index`,
+    /// for the documents nearest to `query_vector`.
+    pub fn new(
+        options: MongoScanOptions,
+        index: impl Into<String>,
+        path: impl Into<String>,
+        query_vector: Vec<f64>,
+    ) -> Self {
+        MongoVectorSearch {
+            options,
+            index: index.into(),
+            path: path.into(),
+            query_vector,
+            k: 10,
+            num_candidates: None,
+            filter: None,
+            score_column: "score".into(),
+        }
+    }
+
+    /// Number of documents to return. Defaults to `10`.
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Number of nearest neighbors considered by the approximate search.
+    /// Defaults to `10 * k`; more candidates are slower but more accurate.
+    pub fn with_num_candidates(mut self, num_candidates: Option<usize>) -> Self {
+        self.num_candidates = num_candidates;
+        self
+    }
+
+    /// Only search the documents matching `filter`.
+    pub fn with_filter(mut self, filter: Option<Document>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Name of the `Float64` similarity score column. Defaults to `"score"`.
+    pub fn with_score_column(mut self, name: impl Into<String>) -> Self {
+        self.score_column = name.into();
+        self
+    }
+
+    fn search_stage(&self) -> Document {
+        let mut search = doc! {
+            "index": self.index.clone(),
+            "path": self.path.clone(),
+            "queryVector": self.query_vector.clone(),
+            "numCandidates": self.num_candidates.unwrap_or(self.k * 10) as i64,
+            "limit": self.k as i64,
+        };
+        if let Some(filter) = &self.filter {
+            search.insert("filter", filter.clone());
+        }
+        doc! {"$vectorSearch": search}
+    }
+
+    pub fn finish(self) -> PolarsResult<LazyFrame> {
+        let pipeline = vec![
+            self.search_stage(),
+            doc! {"$addFields": {self.score_column.clone(): {"$meta": "vectorSearchScore"}}},
+        ];
+        let mut options = self.options;
+        if let Some(schema) = &mut options.schema {
+            schema.with_column(self.score_column, DataType::Float64);
+        }
+        scan_mongo(options, Some(pipeline))
+    }
+}
@@ -0,0 +1,700 @@
+//! Write polars dataframes into a mongodb collection.
+use std::time::Duration;
+
+use polars::export::rayon::prelude::*;
+use polars::prelude::*;
+use polars_core::POOL;
+
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document, Timestamp},
+    options::{
+        ClientOptions, CreateCollectionOptions, DeleteOptions, InsertManyOptions, ReplaceOptions,
+        SessionOptions, TimeseriesOptions, TransactionOptions, UpdateOptions, WriteConcern,
+    },
+    sync::{Client, ClientSession, Collection},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::causal;
+use crate::conversion::df_to_documents;
+
+/// How a [`MongoCollectionWriter`] writes the rows of a dataframe into the collection.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WriteMode {
+    /// Insert every row as a new document.
+    #[default]
+    Append,
+    /// Delete all documents in the collection before inserting the rows.
+    Truncate,
+    /// Update the document with the same values in the key columns, or insert the row
+    /// if there is no such document. Writing the same rows twice is idempotent.
+    Upsert(Vec<String>),
+    /// Replace the document with the same `_id`, or insert the row if there is no such document.
+    ReplaceById,
+    /// Delete the documents with the same values in the key columns as any of the rows.
+    /// Nothing is inserted, so the dataframe only needs the key columns.
+    Delete(Vec<String>),
+}
+
+/// Where the `_id` of the written documents comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IdMode {
+    /// Write the `_id` column as is, if there is one. The server assigns the missing ones.
+    #[default]
+    Keep,
+    /// Use the named column as `_id`, in place of the column itself.
+    Column(String),
+    /// Generate a new `ObjectId` for every row.
+    Generate,
+    /// Drop the `_id` column, if there is one, and let the server assign it.
+    Omit,
+}
+
+pub struct MongoCollectionWriter {
+    client: Client,
+    /// Hosts of the cluster the causally consistent writes are recorded for.
+    cluster: String,
+    collection: Collection<Document>,
+    pub mode: WriteMode,
+    pub id_mode: IdMode,
+    pub batch_size: Option<usize>,
+    pub write_concern: Option<WriteConcern>,
+    pub ordered: bool,
+    pub max_in_flight: Option<usize>,
+    pub transaction: bool,
+    pub time_series: Option<TimeseriesOptions>,
+    pub expire_after_seconds: Option<u64>,
+    pub capped_size_bytes: Option<u64>,
+    pub capped_max_docs: Option<u64>,
+    pub causal_consistency: bool,
+}
+
+impl MongoCollectionWriter {
+    pub fn new(connection_str: String, db: String, collection: String) -> PolarsResult<Self> {
+        let client_options = ClientOptions::parse(connection_str).map_err(|e| {
+            PolarsError::InvalidOperation(format!("unable to connect to mongodb: {}", e).into())
+        })?;
+        let cluster = causal::cluster_key(&client_options);
+        let client = Client::with_options(client_options)
+            .map_err(|e| PolarsError::ComputeError(format!("{:#?}", e).into()))?;
+
+        Ok(MongoCollectionWriter {
+            collection: client.database(&db).collection(&collection),
+            client,
+            cluster,
+            mode: WriteMode::default(),
+            id_mode: IdMode::default(),
+            batch_size: None,
+            write_concern: None,
+            ordered: true,
+            max_in_flight: None,
+            transaction: false,
+            time_series: None,
+            expire_after_seconds: None,
+            capped_size_bytes: None,
+            capped_max_docs: None,
+            causal_consistency: false,
+        })
+    }
+
+    pub fn with_mode(mut self, mode: WriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_id_mode(mut self, id_mode: IdMode) -> Self {
+        self.id_mode = id_mode;
+        self
+    }
+
+    /// Convert and write at most `batch_size` rows at a time. Defaults to `1000`.
+    pub fn with_batch_size(mut self, batch_size: Option<usize>) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Acknowledgement requested from the server for every write, e.g. `w: "majority"`
+    /// for critical loads or `w: 1` for throughput. Defaults to the write concern of the client.
+    pub fn with_write_concern(mut self, write_concern: Option<WriteConcern>) -> Self {
+        self.write_concern = write_concern;
+        self
+    }
+
+    /// Insert the documents of a batch in order, stopping at the first error. Defaults to `true`.
+    ///
+    /// Unordered inserts let the server apply them in parallel, and insert all valid
+    /// documents of the batch even if some fail.
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Insert up to `max_in_flight` batches at a time on the polars thread pool. Defaults to `1`.
+    ///
+    /// Concurrent inserts help to saturate a sharded cluster on bulk loads, at the cost of
+    /// holding that many batches of documents in memory. Batches may be written out of order,
+    /// and upserts and replacements are always written one batch at a time.
+    pub fn with_max_in_flight(mut self, max_in_flight: Option<usize>) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Write all batches in a single transaction, so the write either fully commits or
+    /// is rolled back. Needs a replica set or sharded cluster.
+    ///
+    /// The server limits how long a transaction may run, 60 seconds by default, and
+    /// batches are always written one at a time.
+    pub fn with_transaction(mut self, transaction: bool) -> Self {
+        self.transaction = transaction;
+        self
+    }
+
+    /// Create the collection as a time series collection with the time field, meta field
+    /// and granularity of `time_series`, if it doesn't exist yet.
+    ///
+    /// The collection is created before the first batch is written. An existing collection
+    /// is written as is, even if it is not a time series collection.
+    pub fn with_time_series(mut self, time_series: Option<TimeseriesOptions>) -> Self {
+        self.time_series = time_series;
+        self
+    }
+
+    /// Let the server delete the documents of a time series collection created by the writer
+    /// once their time field is older than `expire_after_seconds`.
+    pub fn with_expire_after_seconds(mut self, expire_after_seconds: Option<u64>) -> Self {
+        self.expire_after_seconds = expire_after_seconds;
+        self
+    }
+
+    /// Create the collection as a capped collection of at most `size_bytes` and `max_docs`
+    /// documents, if it doesn't exist yet. Once it is full, the oldest documents are overwritten.
+    ///
+    /// Capped collections keep the documents in insertion order, so they can be read
+    /// as they are written with a [`MongoTailableReader`](crate::tailable::MongoTailableReader).
+    /// Documents can't be deleted from them, so they can't be truncated into or deleted from.
+    pub fn create_capped(mut self, size_bytes: u64, max_docs: Option<u64>) -> Self {
+        self.capped_size_bytes = Some(size_bytes);
+        self.capped_max_docs = max_docs;
+        self
+    }
+
+    /// Write in a causally consistent session, so scans of the same cluster in this program
+    /// with [`MongoScan::with_causal_consistency`](crate::MongoScan::with_causal_consistency)
+    /// read these writes, even from secondaries. Needs a replica set or sharded cluster.
+    ///
+    /// Like in a transaction, batches are written one at a time.
+    pub fn with_causal_consistency(mut self, causal_consistency: bool) -> Self {
+        self.causal_consistency = causal_consistency;
+        self
+    }
+
+    /// Upsert the rows, matching existing documents on the `keys` columns.
+    pub fn upsert(self, keys: &[&str]) -> Self {
+        self.with_mode(WriteMode::Upsert(
+            keys.iter().map(|key| key.to_string()).collect(),
+        ))
+    }
+
+    /// Delete the documents matching the rows on the `keys` columns.
+    ///
+    /// `_id`s read as hex strings don't match `ObjectId`s, so delete by another key, or
+    /// convert them back in the query.
+    pub fn delete(self, keys: &[&str]) -> Self {
+        self.with_mode(WriteMode::Delete(
+            keys.iter().map(|key| key.to_string()).collect(),
+        ))
+    }
+
+    /// Write all rows of `df` into the collection.
+    pub fn finish(&mut self, df: &DataFrame) -> PolarsResult<()> {
+        if let WriteMode::Upsert(keys) | WriteMode::Delete(keys) = &self.mode {
+            // fail before writing anything if a key column is missing.
+            for key in keys {
+                df.column(key)?;
+            }
+        }
+        if let IdMode::Column(name) = &self.id_mode {
+            df.column(name)?;
+        }
+        if let Some(time_series) = &self.time_series {
+            df.column(&time_series.time_field)?;
+        }
+        if self.mode == WriteMode::ReplaceById {
+            match self.id_mode {
+                IdMode::Keep => {
+                    df.column("_id")?;
+                }
+                IdMode::Omit => {
+                    return Err(PolarsError::InvalidOperation(
+                        "replacing documents by `_id` needs the rows to have an `_id`".into(),
+                    ))
+                }
+                IdMode::Column(_) | IdMode::Generate => {}
+            }
+        }
+        if self.capped_size_bytes.is_some() && self.time_series.is_some() {
+            return Err(PolarsError::InvalidOperation(
+                "a time series collection can't be capped".into(),
+            ));
+        }
+        // collections can't be created in a transaction before mongodb 4.4.
+        self.create_collection()?;
+        if !self.transaction && !self.causal_consistency {
+            return self.write(df, None);
+        }
+        let session_options = SessionOptions::builder()
+            .causal_consistency(self.causal_consistency)
+            .build();
+        let mut session = self
+            .client
+            .start_session(session_options)
+            .map_err(to_compute_err)?;
+        if self.transaction {
+            let options = TransactionOptions::builder()
+                .write_concern(self.write_concern.clone())
+                .build();
+            session.start_transaction(options).map_err(to_compute_err)?;
+            match self.write(df, Some(&mut session)) {
+                Ok(()) => session.commit_transaction().map_err(to_compute_err)?,
+                Err(err) => {
+                    // the error of the write is more useful than a failed abort.
+                    let _ = session.abort_transaction();
+                    return Err(err);
+                }
+            }
+        } else {
+            self.write(df, Some(&mut session))?;
+        }
+        if self.causal_consistency {
+            if let Some(operation_time) = self.operation_time(&mut session)? {
+                causal::record_write(self.cluster.clone(), operation_time);
+            }
+        }
+        Ok(())
+    }
+
+    /// The operation time of the session after its writes, read from the reply to a `ping` in
+    /// the session, as the sync session doesn't expose it. `None` for a standalone server.
+    fn operation_time(&self, session: &mut ClientSession) -> PolarsResult<Option<Timestamp>> {
+        let db = self.client.database(&self.collection.namespace().db);
+        let response = db
+            .run_command_with_session(doc! {"ping": 1}, None, session)
+            .map_err(to_compute_err)?;
+        let cluster_time = response
+            .get_document("$clusterTime")
+            .and_then(|cluster_time| cluster_time.get_timestamp("clusterTime"));
+        Ok(response
+            .get_timestamp("operationTime")
+            .or(cluster_time)
+            .ok())
+    }
+
+    /// Create the time series or capped collection, unless it is neither or it already exists.
+    fn create_collection(&self) -> PolarsResult<()> {
+        if self.time_series.is_none() && self.capped_size_bytes.is_none() {
+            return Ok(());
+        }
+        let namespace = self.collection.namespace();
+        let db = self.client.database(&namespace.db);
+        let existing = db
+            .list_collection_names(doc! {"name": namespace.coll.as_str()})
+            .map_err(to_compute_err)?;
+        if !existing.is_empty() {
+            return Ok(());
+        }
+        let options = CreateCollectionOptions::builder()
+            .timeseries(self.time_series.clone())
+            .expire_after_seconds(self.expire_after_seconds.map(Duration::from_secs))
+            .capped(self.capped_size_bytes.map(|_| true))
+            .size(self.capped_size_bytes)
+            .max(self.capped_max_docs)
+            .write_concern(self.write_concern.clone())
+            .build();
+        db.create_collection(&namespace.coll, options)
+            .map_err(to_compute_err)
+    }
+
+    fn write(&self, df: &DataFrame, mut session: Option<&mut ClientSession>) -> PolarsResult<()> {
+        if self.mode == WriteMode::Truncate {
+            let options = DeleteOptions::builder()
+                .write_concern(self.op_write_concern(&session))
+                .build();
+            match session.as_deref_mut() {
+                Some(session) => {
+                    self.collection
+                        .delete_many_with_session(doc! {}, options, session)
+                }
+                None => self.collection.delete_many(doc! {}, options),
+            }
+            .map_err(to_compute_err)?;
+        }
+
+        // only `max_in_flight` batches of rows are converted to documents at a time.
+        let batch_size = self.batch_size.unwrap_or(1000).max(1);
+        let max_in_flight = self.max_in_flight.unwrap_or(1).max(1);
+        let offsets = (0..df.height()).step_by(batch_size).collect::<Vec<_>>();
+        match self.mode {
+            // a session can't be shared between threads.
+            WriteMode::Append | WriteMode::Truncate if max_in_flight > 1 && session.is_none() => {
+                for group in offsets.chunks(max_in_flight) {
+                    POOL.install(|| {
+                        group.par_iter().try_for_each(|&offset| {
+                            self.insert(&self.batch_documents(df, offset, batch_size), None)
+                        })
+                    })?;
+                }
+            }
+            _ => {
+                for offset in offsets {
+                    let docs = self.batch_documents(df, offset, batch_size);
+                    self.write_batch(docs, session.as_deref_mut())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The rows of the batch starting at `offset`, as documents.
+    fn batch_documents(&self, df: &DataFrame, offset: usize, batch_size: usize) -> Vec<Document> {
+        df_to_documents(&df.slice(offset as i64, batch_size))
+            .into_iter()
+            .map(|doc| self.with_id(doc))
+            .collect()
+    }
+
+    /// The document with its `_id` set according to the id mode, as the first field.
+    fn with_id(&self, mut doc: Document) -> Document {
+        let id = match &self.id_mode {
+            IdMode::Keep => return doc,
+            IdMode::Column(name) => doc.remove(name).unwrap_or(Bson::Null),
+            IdMode::Generate => Bson::ObjectId(ObjectId::new()),
+            IdMode::Omit => {
+                doc.remove("_id");
+                return doc;
+            }
+        };
+        doc.remove("_id");
+        let mut with_id = doc! {"_id": id};
+        with_id.extend(doc);
+        with_id
+    }
+
+    /// Write concern of a single operation. Operations in a transaction use the write
+    /// concern of the transaction instead.
+    fn op_write_concern(&self, session: &Option<&mut ClientSession>) -> Option<WriteConcern> {
+        match session {
+            Some(_) => None,
+            None => self.write_concern.clone(),
+        }
+    }
+
+    fn write_batch(
+        &self,
+        docs: Vec<Document>,
+        mut session: Option<&mut ClientSession>,
+    ) -> PolarsResult<()> {
+        match &self.mode {
+            WriteMode::Append | WriteMode::Truncate => self.insert(&docs, session),
+            WriteMode::Upsert(keys) => {
+                let options = UpdateOptions::builder()
+                    .upsert(true)
+                    .write_concern(self.op_write_concern(&session))
+                    .build();
+                for doc in docs {
+                    let filter = key_filter(keys, &doc);
+                    let update = upsert_update(doc);
+                    match session.as_deref_mut() {
+                        Some(session) => self.collection.update_one_with_session(
+                            filter,
+                            update,
+                            options.clone(),
+                            session,
+                        ),
+                        None => self.collection.update_one(filter, update, options.clone()),
+                    }
+                    .map_err(to_compute_err)?;
+                }
+                Ok(())
+            }
+            WriteMode::ReplaceById => {
+                let options = ReplaceOptions::builder()
+                    .upsert(true)
+                    .write_concern(self.op_write_concern(&session))
+                    .build();
+                for doc in docs {
+                    let filter = doc! {"_id": doc.get("_id").cloned().unwrap_or(Bson::Null)};
+                    match session.as_deref_mut() {
+                        Some(session) => self.collection.replace_one_with_session(
+                            filter,
+                            doc,
+                            options.clone(),
+                            session,
+                        ),
+                        None => self.collection.replace_one(filter, doc, options.clone()),
+                    }
+                    .map_err(to_compute_err)?;
+                }
+                Ok(())
+            }
+            WriteMode::Delete(keys) => {
+                if docs.is_empty() {
+                    return Ok(());
+                }
+                let filter = delete_filter(keys, &docs);
+                let options = DeleteOptions::builder()
+                    .write_concern(self.op_write_concern(&session))
+                    .build();
+                match session {
+                    Some(session) => self
+                        .collection
+                        .delete_many_with_session(filter, options, session),
+                    None => self.collection.delete_many(filter, options),
+                }
+                .map_err(to_compute_err)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn insert(&self, docs: &[Document], session: Option<&mut ClientSession>) -> PolarsResult<()> {
+        // mongodb refuses to insert an empty batch.
+        if docs.is_empty() {
+            return Ok(());
+        }
+        let options = InsertManyOptions::builder()
+            .ordered(self.ordered)
+            .write_concern(self.op_write_concern(&session))
+            .build();
+        match session {
+            Some(session) => self
+                .collection
+                .insert_many_with_session(docs, options, session),
+            None => self.collection.insert_many(docs, options),
+        }
+        .map_err(to_compute_err)?;
+        Ok(())
+    }
+}
+
+/// Filter matching the values of the `keys` fields in `doc`.
+fn key_filter(keys: &[String], doc: &Document) -> Document {
+    keys.iter()
+        .map(|key| (key.clone(), doc.get(key).cloned().unwrap_or(Bson::Null)))
+        .collect()
+}
+
+/// Filter matching the documents with the same `keys` as any of `docs`, so a whole batch is
+/// deleted at once.
+fn delete_filter(keys: &[String], docs: &[Document]) -> Document {
+    match keys {
+        [key] => {
+            let values = docs
+                .iter()
+                .map(|doc| doc.get(key).cloned().unwrap_or(Bson::Null))
+                .collect::<Vec<_>>();
+            doc! {key: {"$in": values}}
+        }
+        _ => {
+            let filters = docs
+                .iter()
+                .map(|doc| Bson::Document(key_filter(keys, doc)))
+                .collect::<Vec<_>>();
+            doc! {"$or": filters}
+        }
+    }
+}
+
+/// Update setting the fields of `doc`, except for its `_id` which is only set when the document
+/// is inserted, as the `_id` of an existing document can't be changed.
+fn upsert_update(mut doc: Document) -> Document {
+    let mut update = Document::new();
+    let id = doc.remove("_id");
+    // mongodb before 5.0 rejects an empty `$set`.
+    if !doc.is_empty() {
+        update.insert("$set", doc);
+    }
+    if let Some(id) = id {
+        update.insert("$setOnInsert", doc! {"_id": id});
+    }
+    update
+}
+
+fn to_compute_err(err: mongodb::error::Error) -> PolarsError {
+    PolarsError::ComputeError(format!("{:#?}", err).into())
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MongoWriteOptions {
+    /// mongodb style connection string. `mongodb://<user>:<password>@host.domain`
+    pub connection_str: String,
+    /// the name of the mongodb database
+    pub db: String,
+    /// the name of the mongodb collection
+    pub collection: String,
+    /// How the rows are written into the collection. Defaults to [`WriteMode::Append`].
+    pub mode: WriteMode,
+    /// Where the `_id` of the documents comes from. Defaults to [`IdMode::Keep`].
+    pub id_mode: IdMode,
+    /// Number of rows converted and sent to mongodb at a time. Defaults to `1000`.
+    pub batch_size: Option<usize>,
+    /// Acknowledgement requested for every write (`w`, `journal` and `w_timeout`).
+    /// Defaults to the write concern of the connection string.
+    pub write_concern: Option<WriteConcern>,
+    /// Insert the documents in order, stopping at the first error. Defaults to `true`.
+    /// Only applies to inserts, upserts and replacements are always sent one at a time.
+    pub ordered: bool,
+    /// Number of batches inserted concurrently. Defaults to `1`.
+    /// Each batch in flight is held in memory as documents.
+    pub max_in_flight: Option<usize>,
+    /// Write all batches in a single transaction that is aborted if any of them fails.
+    pub transaction: bool,
+    /// Create the collection as a time series collection if it doesn't exist yet.
+    pub time_series: Option<TimeseriesOptions>,
+    /// Expire the documents of a created time series collection after this many seconds.
+    pub expire_after_seconds: Option<u64>,
+    /// Create the collection as a capped collection of this many bytes if it doesn't exist yet.
+    pub capped_size_bytes: Option<u64>,
+    /// Number of documents a created capped collection holds at most.
+    pub capped_max_docs: Option<u64>,
+    /// Write in a causally consistent session, so causally consistent scans in this program
+    /// read the written documents.
+    pub causal_consistency: bool,
+}
+
+impl Default for MongoWriteOptions {
+    fn default() -> Self {
+        MongoWriteOptions {
+            connection_str: String::new(),
+            db: String::new(),
+            collection: String::new(),
+            mode: WriteMode::default(),
+            id_mode: IdMode::default(),
+            batch_size: None,
+            write_concern: None,
+            ordered: true,
+            max_in_flight: None,
+            transaction: false,
+            time_series: None,
+            expire_after_seconds: None,
+            capped_size_bytes: None,
+            capped_max_docs: None,
+            causal_consistency: false,
+        }
+    }
+}
+
+pub trait MongoLazyWriter {
+    /// Evaluate the query and write the result into a mongodb collection.
+    ///
+    /// The rows are converted and written in batches of `batch_size`, so only a single
+    /// batch of documents is held in memory. polars does not have a streaming engine yet,
+    /// so the query result itself is still materialized before it is written.
+    fn sink_mongo(self, options: MongoWriteOptions) -> PolarsResult<()>;
+}
+
+impl MongoLazyWriter for LazyFrame {
+    fn sink_mongo(self, options: MongoWriteOptions) -> PolarsResult<()> {
+        let df = self.collect()?;
+        let mut writer =
+            MongoCollectionWriter::new(options.connection_str, options.db, options.collection)?
+                .with_mode(options.mode)
+                .with_id_mode(options.id_mode)
+                .with_batch_size(options.batch_size)
+                .with_write_concern(options.write_concern)
+                .with_ordered(options.ordered)
+                .with_max_in_flight(options.max_in_flight)
+                .with_transaction(options.transaction)
+                .with_time_series(options.time_series)
+                .with_expire_after_seconds(options.expire_after_seconds)
+                .with_causal_consistency(options.causal_consistency);
+        if let Some(size_bytes) = options.capped_size_bytes {
+            writer = writer.create_capped(size_bytes, options.capped_max_docs);
+        }
+        writer.finish(&df)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsert_only_sets_the_id_on_insert() {
+        let doc = doc! {"_id": "62d0b0f1a1b2c3d4e5f60718", "key": 1, "a": "x"};
+        assert_eq!(
+            upsert_update(doc),
+            doc! {
+                "$set": {"key": 1, "a": "x"},
+                "$setOnInsert": {"_id": "62d0b0f1a1b2c3d4e5f60718"},
+            }
+        );
+        assert_eq!(upsert_update(doc! {"key": 1}), doc! {"$set": {"key": 1}});
+    }
+
+    #[test]
+    fn key_filter_matches_missing_keys_as_null() {
+        let doc = doc! {"a": 1, "b": "x"};
+        assert_eq!(
+            key_filter(&["a".into(), "c".into()], &doc),
+            doc! {"a": 1, "c": Bson::Null}
+        );
+    }
+
+    #[test]
+    fn causally_consistent_writer() {
+        let writer = MongoCollectionWriter::new(
+            "mongodb://b.example.com:27017,a.example.com:27017".into(),
+            "db".into(),
+            "coll".into(),
+        )
+        .unwrap()
+        .with_causal_consistency(true);
+        assert!(writer.causal_consistency);
+        assert_eq!(writer.cluster, "a.example.com:27017,b.example.com:27017");
+    }
+
+    #[test]
+    fn id_modes() {
+        let writer = |id_mode| {
+            MongoCollectionWriter::new("mongodb://localhost:27017".into(), "db".into(), "c".into())
+                .unwrap()
+                .with_id_mode(id_mode)
+        };
+        let doc = doc! {"a": 1, "_id": 2, "key": "x"};
+
+        assert_eq!(writer(IdMode::Keep).with_id(doc.clone()), doc);
+        assert_eq!(
+            writer(IdMode::Column("key".into())).with_id(doc.clone()),
+            doc! {"_id": "x", "a": 1}
+        );
+        assert_eq!(
+            writer(IdMode::Column("missing".into())).with_id(doc.clone()),
+            doc! {"_id": Bson::Null, "a": 1, "key": "x"}
+        );
+        assert_eq!(
+            writer(IdMode::Omit).with_id(doc.clone()),
+            doc! {"a": 1, "key": "x"}
+        );
+        let generated = writer(IdMode::Generate).with_id(doc);
+        assert!(matches!(generated.iter().next(), Some((id, Bson::ObjectId(_))) if id == "_id"));
+        assert_eq!(generated.len(), 3);
+    }
+
+    #[test]
+    fn delete_filter_of_a_batch() {
+        let docs = [doc! {"a": 1, "b": "x"}, doc! {"b": "y"}];
+        assert_eq!(
+            delete_filter(&["a".into()], &docs),
+            doc! {"a": {"$in": [1, Bson::Null]}}
+        );
+        assert_eq!(
+            delete_filter(&["a".into(), "b".into()], &docs),
+            doc! {"$or": [{"a": 1, "b": "x"}, {"a": Bson::Null, "b": "y"}]}
+        );
+    }
+}